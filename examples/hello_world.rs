@@ -8,6 +8,8 @@ use repeekooz::ZooKeeper;
 
 #[tokio::main]
 async fn main() -> ZKResult<()> {
+    // repeekooz 不会初始化日志，由应用自行决定
+    pretty_env_logger::init();
     let client = ZooKeeper::new("127.0.0.1:2181", Duration::from_secs(5)).await?;
     info!("{:?}", client);
     Ok(())
@@ -0,0 +1,348 @@
+//! # ACL 权限计算模块
+//! 按照服务端的匹配规则在客户端计算一组 ACL 的有效权限，并解释每一种权限由哪一条 ACL 授予或者为什么被拒绝，
+//! 用于排查 `NoAuth` 错误
+
+use std::fmt::{Display, Formatter};
+use std::net::IpAddr;
+
+use crate::constants::{AUTH, DIGEST, IP, SUPER, WORLD};
+use crate::{Perms, Scheme, ACL};
+
+/// 权限计算器，收集会话的身份后对 ACL 列表求值
+/// # Examples
+///
+/// ```rust,ignore
+/// let acl_list = zk.get_acl("/your/path", None).await?;
+/// let effective = zk
+///     .permission_evaluator()
+///     .ip("10.0.0.1".parse().unwrap())
+///     .evaluate(&acl_list);
+/// if !effective.allows(Perms::WRITE) {
+///     println!("{}", effective.explain(Perms::WRITE).unwrap());
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct PermissionEvaluator {
+    identities: Vec<Scheme>,
+}
+
+impl PermissionEvaluator {
+    /// 创建没有任何身份的计算器，此时只有 `world:anyone` 能够授予权限
+    pub fn new() -> PermissionEvaluator {
+        PermissionEvaluator::default()
+    }
+
+    /// 服务端看到的客户端 IP，用于匹配 `ip` scheme
+    pub fn ip(self, ip: IpAddr) -> Self {
+        self.identity(Scheme::IP(ip, None))
+    }
+
+    /// 添加一个已经通过服务端鉴权的身份，例如 `Scheme::X509(dn)`、`Scheme::Sasl(user)`，
+    /// 超级管理员为 `Scheme::Other("super", "")`
+    pub fn identity(mut self, identity: Scheme) -> Self {
+        if !self.identities.contains(&identity) {
+            self.identities.push(identity);
+        }
+        self
+    }
+
+    /// 添加与 [`crate::ZooKeeper::add_auth`] 参数相同的鉴权信息，`digest` 会转换成服务端保存的格式
+    pub fn auth(self, scheme: &str, auth: impl AsRef<[u8]>) -> Self {
+        let auth = String::from_utf8_lossy(auth.as_ref());
+        let identity = match scheme {
+            DIGEST => Scheme::Digest(Scheme::generate_digest(&auth)),
+            _ => match Scheme::new(scheme, &auth) {
+                Ok(identity) => identity,
+                Err(_) => return self,
+            },
+        };
+        self.identity(identity)
+    }
+
+    /// 当前的所有身份
+    pub fn identities(&self) -> &[Scheme] {
+        &self.identities
+    }
+
+    /// 计算 ACL 列表的有效权限
+    pub fn evaluate(&self, acl_list: &[ACL]) -> EffectivePerms {
+        let decisions = Perms::ALL
+            .iter()
+            .map(|perm| self.decide(perm, acl_list))
+            .collect::<Vec<_>>();
+        let perms = decisions
+            .iter()
+            .filter(|d| d.granted)
+            .fold(Perms::NONE, |perms, d| perms | d.perm);
+        EffectivePerms { perms, decisions }
+    }
+
+    fn decide(&self, perm: Perms, acl_list: &[ACL]) -> PermDecision {
+        if self.is_super() {
+            return PermDecision::granted(perm, None, "session is authenticated as super".into());
+        }
+        if acl_list.is_empty() {
+            return PermDecision::granted(perm, None, "ACL list is empty".into());
+        }
+        let mut rejected = Vec::new();
+        for acl in acl_list.iter().filter(|acl| acl.perms.contains(perm)) {
+            match self.matches(&acl.scheme) {
+                Ok(reason) => {
+                    return PermDecision::granted(
+                        perm,
+                        Some(acl.clone()),
+                        format!("granted by {}: {}", acl, reason),
+                    )
+                }
+                Err(reason) => rejected.push(format!("{}: {}", acl, reason)),
+            }
+        }
+        let reason = if rejected.is_empty() {
+            "no ACL entry contains this permission".to_string()
+        } else {
+            rejected.join("; ")
+        };
+        PermDecision {
+            perm,
+            granted: false,
+            acl: None,
+            reason,
+        }
+    }
+
+    fn is_super(&self) -> bool {
+        self.identities
+            .iter()
+            .any(|identity| identity.scheme() == SUPER)
+    }
+
+    /// 与服务端的 AuthenticationProvider 一致：ip 按照 CIDR 匹配，其余 scheme 要求 id 完全相同
+    fn matches(&self, scheme: &Scheme) -> Result<String, String> {
+        match scheme {
+            Scheme::World => Ok("anyone is allowed".into()),
+            Scheme::IP(addr, prefix) => {
+                let ips = self
+                    .identities
+                    .iter()
+                    .filter_map(|identity| match identity {
+                        Scheme::IP(ip, _) => Some(*ip),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>();
+                match ips.iter().find(|ip| ip_matches(**ip, *addr, *prefix)) {
+                    Some(ip) => Ok(format!("client ip {} is in range", ip)),
+                    None if ips.is_empty() => Err("client ip is unknown".into()),
+                    None => Err(format!(
+                        "client ip {} is not in range",
+                        ips.iter()
+                            .map(IpAddr::to_string)
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    )),
+                }
+            }
+            Scheme::Auth(_) => {
+                // 创建节点时服务端把 auth 替换成创建者鉴权过的身份
+                match self
+                    .identities
+                    .iter()
+                    .find(|identity| !matches!(identity.scheme(), WORLD | IP | AUTH))
+                {
+                    Some(identity) => Ok(format!("session is authenticated as {}", identity)),
+                    None => Err("session has no authenticated identity".into()),
+                }
+            }
+            _ => {
+                if self.identities.contains(scheme) {
+                    Ok(format!("session is authenticated as {}", scheme))
+                } else if self
+                    .identities
+                    .iter()
+                    .any(|identity| identity.scheme() == scheme.scheme())
+                {
+                    Err(format!(
+                        "no {} identity of the session matches",
+                        scheme.scheme()
+                    ))
+                } else {
+                    Err(format!(
+                        "session is not authenticated with {}",
+                        scheme.scheme()
+                    ))
+                }
+            }
+        }
+    }
+}
+
+/// 判断 `ip` 是否在 `addr/prefix` 范围内，没有前缀时要求完全相同，IPv4 与 IPv6 之间不匹配
+fn ip_matches(ip: IpAddr, addr: IpAddr, prefix: Option<u8>) -> bool {
+    let (ip, addr, bits) = match (ip, addr) {
+        (IpAddr::V4(ip), IpAddr::V4(addr)) => (ip.octets().to_vec(), addr.octets().to_vec(), 32),
+        (IpAddr::V6(ip), IpAddr::V6(addr)) => (ip.octets().to_vec(), addr.octets().to_vec(), 128),
+        _ => return false,
+    };
+    let prefix = prefix.map_or(bits, usize::from).min(bits);
+    (0..prefix).all(|i| {
+        let mask = 0x80 >> (i % 8);
+        ip[i / 8] & mask == addr[i / 8] & mask
+    })
+}
+
+/// 单个权限的计算结果
+/// - `perm`： 单个权限，参考 [`Perms`]
+/// - `granted`： 是否拥有该权限
+/// - `acl`： 授予该权限的 ACL，超级管理员或者 ACL 列表为空时为 `None`
+/// - `reason`： 授予或者拒绝的原因
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct PermDecision {
+    pub perm: Perms,
+    pub granted: bool,
+    pub acl: Option<ACL>,
+    pub reason: String,
+}
+
+impl PermDecision {
+    fn granted(perm: Perms, acl: Option<ACL>, reason: String) -> PermDecision {
+        PermDecision {
+            perm,
+            granted: true,
+            acl,
+            reason,
+        }
+    }
+}
+
+impl Display for PermDecision {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let result = if self.granted { "granted" } else { "denied" };
+        write!(f, "{} {}: {}", self.perm, result, self.reason)
+    }
+}
+
+/// ACL 列表的有效权限，包含每一种权限的计算结果
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct EffectivePerms {
+    pub perms: Perms,
+    pub decisions: Vec<PermDecision>,
+}
+
+impl EffectivePerms {
+    /// 是否拥有 `perms` 中的所有权限
+    pub fn allows(&self, perms: Perms) -> bool {
+        self.perms.contains(perms)
+    }
+
+    /// 单个权限的计算结果，`perm` 必须是单个权限
+    pub fn explain(&self, perm: Perms) -> Option<&PermDecision> {
+        self.decisions.iter().find(|d| d.perm == perm)
+    }
+}
+
+impl Display for EffectivePerms {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "effective perms: {}", self.perms)?;
+        for decision in &self.decisions {
+            write!(f, "\n  {}", decision)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn acl(s: &str) -> Vec<ACL> {
+        ACL::parse_list(s).unwrap()
+    }
+
+    #[test]
+    fn test_world() {
+        let effective = PermissionEvaluator::new().evaluate(&acl("world:anyone:r"));
+        assert_eq!(effective.perms, Perms::READ);
+        let decision = effective.explain(Perms::READ).unwrap();
+        assert_eq!(decision.acl, Some(acl("world:anyone:r").remove(0)));
+        assert!(!effective.explain(Perms::WRITE).unwrap().granted);
+        assert!(PermissionEvaluator::new().evaluate(&[]).allows(Perms::ALL));
+    }
+
+    #[test]
+    fn test_ip() {
+        let list = acl("ip:10.0.0.0/8:rw,ip:192.168.1.1:c,ip:2001:db8::/32:d");
+        let evaluator = PermissionEvaluator::new().ip("10.1.2.3".parse().unwrap());
+        assert_eq!(evaluator.evaluate(&list).perms, Perms::READ | Perms::WRITE);
+        let evaluator = PermissionEvaluator::new().ip("192.168.1.1".parse().unwrap());
+        assert_eq!(evaluator.evaluate(&list).perms, Perms::CREATE);
+        let evaluator = PermissionEvaluator::new().ip("2001:db8:1::1".parse().unwrap());
+        assert_eq!(evaluator.evaluate(&list).perms, Perms::DELETE);
+        let evaluator = PermissionEvaluator::new().ip("11.0.0.1".parse().unwrap());
+        let effective = evaluator.evaluate(&list);
+        assert!(effective.perms.is_empty());
+        assert!(effective
+            .explain(Perms::READ)
+            .unwrap()
+            .reason
+            .contains("11.0.0.1 is not in range"));
+        let effective = PermissionEvaluator::new().evaluate(&list);
+        assert!(effective
+            .explain(Perms::READ)
+            .unwrap()
+            .reason
+            .contains("client ip is unknown"));
+        assert!(ip_matches(
+            "10.0.0.1".parse().unwrap(),
+            "0.0.0.0".parse().unwrap(),
+            Some(0)
+        ));
+    }
+
+    #[test]
+    fn test_digest() {
+        let id = Scheme::generate_digest("bob:secret");
+        let list = acl(&format!("digest:{}:cdrwa,world:anyone:r", id));
+        let evaluator = PermissionEvaluator::new().auth("digest", "bob:secret");
+        assert!(evaluator.evaluate(&list).allows(Perms::ALL));
+
+        let effective = PermissionEvaluator::new()
+            .auth("digest", "bob:wrong")
+            .evaluate(&list);
+        assert_eq!(effective.perms, Perms::READ);
+        assert!(effective
+            .explain(Perms::WRITE)
+            .unwrap()
+            .reason
+            .contains("no digest identity of the session matches"));
+        let effective = PermissionEvaluator::new().evaluate(&list);
+        assert!(effective
+            .explain(Perms::ADMIN)
+            .unwrap()
+            .reason
+            .contains("session is not authenticated with digest"));
+    }
+
+    #[test]
+    fn test_auth_and_super() {
+        let list = acl("auth::cdrwa");
+        assert!(PermissionEvaluator::new().evaluate(&list).perms.is_empty());
+        let evaluator = PermissionEvaluator::new().identity(Scheme::Sasl("bob".into()));
+        assert!(evaluator.evaluate(&list).allows(Perms::ALL));
+        let evaluator = PermissionEvaluator::new().ip("10.0.0.1".parse().unwrap());
+        assert!(evaluator.evaluate(&list).perms.is_empty());
+
+        let evaluator =
+            PermissionEvaluator::new().identity(Scheme::Other("super".into(), "".into()));
+        let effective = evaluator.evaluate(&acl("world:anyone:"));
+        assert!(effective.allows(Perms::ALL));
+        assert_eq!(effective.explain(Perms::ADMIN).unwrap().acl, None);
+    }
+
+    #[test]
+    fn test_display() {
+        let effective = PermissionEvaluator::new().evaluate(&acl("world:anyone:r"));
+        let s = effective.to_string();
+        assert!(s.starts_with("effective perms: r\n"));
+        assert!(s.contains("r granted: granted by world:anyone:r: anyone is allowed"));
+        assert!(s.contains("w denied: no ACL entry contains this permission"));
+    }
+}
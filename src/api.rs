@@ -1,40 +1,64 @@
 //! # ZooKeeper API 模块
 //! 作为整个项目的入口文件，提供友好的 API 方法用于操作 ZK。
 
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::Duration;
 
 use bytes::BytesMut;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
 
+use crate::acl::PermissionEvaluator;
+use crate::builder::ZooKeeperBuilder;
 use crate::client::Client;
 use crate::constants::{AddWatchMode, CreateMode, OpCode, States, VersionType};
 use crate::error::ServerErrorCode;
 use crate::protocol::req::{
-    AddWatchRequest, CreateRequest, DeleteRequest, PathAndWatchRequest, PathRequest, RequestHeader,
-    SetACLRequest, SetDataRequest, ACL,
+    AddWatchRequest, CreateRequest, DeleteRequest, MultiRequest, Op, PathAndWatchRequest,
+    PathRequest, RequestHeader, SetACLRequest, SetDataRequest, ACL,
 };
 use crate::protocol::resp::{
-    CreateResponse, DummyResponse, GetACLResponse, GetAllChildrenNumberResponse,
-    GetChildren2Response, GetDataResponse, IgnoreResponse, PathListResponse, SetDataResponse, Stat,
+    Create2Response, CreateResponse, DummyResponse, GetACLResponse, GetAllChildrenNumberResponse,
+    GetChildren2Response, GetDataResponse, IgnoreResponse, MultiResponse, OpResult,
+    PathListResponse, SetDataResponse, Stat,
 };
-use crate::protocol::Serializer;
+use crate::protocol::{Deserializer, Serializer};
+use crate::retry::{self, RetryPolicy};
+use crate::state::StateListener;
 use crate::watcher::Watcher;
-use crate::{paths, WatchedEvent, WatcherType, ZKError, ZKResult};
+#[cfg(feature = "json")]
+use crate::JsonCodec;
+use crate::{paths, Codec, WatchedEvent, WatcherType, ZKError, ZKResult};
 
-/// 整个模块的 API 入口对象
-#[derive(Debug)]
+/// 整个模块的 API 入口对象，可以 clone 后在多个 task 之间共享同一个会话
+#[derive(Debug, Clone)]
 pub struct ZooKeeper {
-    client: Client,
+    pub(crate) client: Client,
 }
 
 #[derive(Debug, Hash)]
 struct DummyWatcher;
 
+fn encode<T: Serialize + ?Sized>(path: &str, value: &T, codec: &impl Codec) -> ZKResult<Vec<u8>> {
+    codec.encode(value).map_err(|source| ZKError::EncodeError {
+        path: path.to_string(),
+        codec: codec.name(),
+        source,
+    })
+}
+
+/// 顺序节点的保护前缀，与 Curator 的格式一致
+fn protected_prefix() -> String {
+    format!("_c_{:032x}-", rand::random::<u128>())
+}
+
 impl Watcher for DummyWatcher {
     fn process(&self, _: &WatchedEvent) {}
 }
 
 impl ZooKeeper {
-    /// 创建 ZooKeeper 客户端
+    /// 创建 ZooKeeper 客户端，需要更多配置项时请使用 [`ZooKeeper::builder`]
     /// # Examples
     ///
     /// ```rust,ignore
@@ -42,7 +66,8 @@ impl ZooKeeper {
     /// ```
     ///
     /// # Args
-    /// - `connect_string`: 连接字符串格式为 "ip1:port1,ip2:port2,ip3:port3.../chroot"，其中 chroot 为可选
+    /// - `connect_string`: 连接字符串格式为 "host1:port1,host2:port2,host3:port3.../chroot"，其中 chroot 为可选，
+    ///   host 可以是 IPv4 地址、主机名或者 "[ipv6]"
     /// - `session_timeout`: 会话超时时间, 参考 [`Duration`]
     /// # Returns
     /// - `ZooKeeper`：ZooKeeper 客户端对象
@@ -53,9 +78,42 @@ impl ZooKeeper {
         connect_string: impl Into<&str>,
         session_timeout: Duration,
     ) -> ZKResult<ZooKeeper> {
-        pretty_env_logger::try_init()?;
-        let client = Client::new(connect_string.into(), session_timeout.as_millis() as u32).await?;
-        Ok(ZooKeeper { client })
+        ZooKeeperBuilder::new(connect_string.into())
+            .session_timeout(session_timeout)
+            .build()
+            .await
+    }
+
+    /// 获取客户端构造器，可以设置更多的配置项，参考 [`ZooKeeperBuilder`]
+    /// # Examples
+    ///
+    /// ```rust,ignore
+    /// let zk = ZooKeeper::builder("127.0.0.1:2181")
+    ///     .session_timeout(Duration::from_secs(5))
+    ///     .read_only(true)
+    ///     .build()
+    ///     .await?;
+    /// ```
+    pub fn builder(connect_string: impl Into<String>) -> ZooKeeperBuilder {
+        ZooKeeperBuilder::new(connect_string)
+    }
+
+    /// 获取构造客户端时设置的默认 ACL 列表，没有设置时为 [`ACL::world_acl`]
+    pub fn default_acl(&self) -> Vec<ACL> {
+        self.client.default_acl()
+    }
+
+    /// 获取包含当前会话所有鉴权信息（[`ZooKeeper::add_auth`]、SASL）的权限计算器，
+    /// 服务端看到的客户端 IP 需要通过 [`PermissionEvaluator::ip`] 补充
+    /// # Examples
+    ///
+    /// ```rust,ignore
+    /// let acl_list = zk.get_acl("/your/path", None).await?;
+    /// let effective = zk.permission_evaluator().evaluate(&acl_list);
+    /// println!("{}", effective);
+    /// ```
+    pub fn permission_evaluator(&self) -> PermissionEvaluator {
+        self.client.permission_evaluator()
     }
 
     /// 创建目标路径的节点，数据是可选的
@@ -79,26 +137,157 @@ impl ZooKeeper {
     /// # Returns
     /// - `String`：目标路径，同参数 `path`
     pub async fn create(
-        &mut self,
-        path: impl Into<&str>,
+        &self,
+        path: impl AsRef<str>,
         data: Option<&[u8]>,
         acl_list: Vec<ACL>,
         create_model: CreateMode,
     ) -> ZKResult<String> {
-        let path = path.into();
+        let resp = CreateResponse::default();
+        let resp = self
+            .submit_create(
+                path.as_ref(),
+                data,
+                acl_list,
+                create_model,
+                OpCode::Create,
+                resp,
+            )
+            .await?;
+        Ok(resp.path)
+    }
+
+    /// 创建节点并返回节点的统计数据，使用 `Create2` 请求，参考 [`ZooKeeper::create`]
+    pub(crate) async fn create2(
+        &self,
+        path: &str,
+        data: Option<&[u8]>,
+        acl_list: Vec<ACL>,
+        create_model: CreateMode,
+    ) -> ZKResult<(String, Stat)> {
+        let resp = Create2Response::default();
+        let resp = self
+            .submit_create(path, data, acl_list, create_model, OpCode::Create2, resp)
+            .await?;
+        Ok((resp.path, resp.stat))
+    }
+
+    /// 容器节点只能通过 `CreateContainer` 创建，它的响应与 `Create2` 相同
+    async fn submit_create<D: Deserializer>(
+        &self,
+        path: &str,
+        data: Option<&[u8]>,
+        acl_list: Vec<ACL>,
+        create_model: CreateMode,
+        rtype: OpCode,
+        resp: D,
+    ) -> ZKResult<D> {
         paths::validate_path(path)?;
         let rtype = match create_model {
             CreateMode::Container => OpCode::CreateContainer,
-            _ => OpCode::Create,
+            _ => rtype,
         };
-        let rh = Some(RequestHeader::new(rtype));
+        let data = data
+            .map(|data| self.client.compress(path, data))
+            .transpose()?;
+        let rh = RequestHeader::new(rtype);
         let mut req = BytesMut::new();
-        let request =
-            CreateRequest::new_full(self.client.get_path(path), data, acl_list, create_model);
+        let request = CreateRequest::new_full(
+            self.client.get_path(path),
+            data.as_deref(),
+            acl_list,
+            create_model,
+        );
         request.write(&mut req)?;
-        let resp = CreateResponse::default();
-        let resp = self.client.submit_request(rh, req, resp).await?;
-        Ok(resp.path)
+        self.client.submit_request(rh, path, req, resp).await
+    }
+
+    /// 按照客户端的重试策略创建节点，连接断开导致的失败会自动重试，参考 [`ZooKeeperBuilder::retry_policy`]
+    ///
+    /// 创建节点不是幂等操作，请求可能已经在服务端生效而响应丢失，因此：
+    /// - 顺序节点的名称前会添加 `_c_<guid>-` 保护前缀，重试前先在父节点下查找带有该前缀的节点，
+    ///   找到时直接返回，不会重复创建
+    /// - 非顺序的临时节点重试时返回 `NodeExists`，并且节点属于当前会话时视为创建成功
+    /// - 非顺序的持久节点重试时返回的 `NodeExists` 无法区分是否由本次调用创建，直接返回错误
+    /// # Examples
+    /// ```rust,ignore
+    /// // 实际创建的路径类似 /queue/_c_1f0e...-item-0000000003
+    /// let path = zk.create_with_retry("/queue/item-", None, ACL::world_acl(), CreateMode::PersistentSequential)
+    ///        .await?;
+    /// ```
+    ///
+    /// # Args
+    /// - `path`： 目标路径，必须以 "/" 开头
+    /// - `data`： 节点的数据，可选
+    /// - `acl`： 该节点的权限数据，可以有多个，参考 [`ACL`]
+    /// - `create_model`： 节点的模式，参考 [`CreateMode`]
+    /// # Returns
+    /// - `String`：实际创建的路径
+    pub async fn create_with_retry(
+        &self,
+        path: impl AsRef<str>,
+        data: Option<&[u8]>,
+        acl_list: Vec<ACL>,
+        create_model: CreateMode,
+    ) -> ZKResult<String> {
+        let path = path.as_ref();
+        paths::validate_path(path)?;
+        let target = if create_model.is_sequential() {
+            let (parent, name) = paths::split(path);
+            let protected_name = format!("{}{}", protected_prefix(), name);
+            paths::join(parent, &protected_name)
+        } else {
+            path.to_string()
+        };
+        // 之前的尝试是否因为连接问题失败，此时请求可能已经生效
+        let maybe_created = AtomicBool::new(false);
+        let policy = self.client.retry_policy();
+        retry::run(policy.as_ref(), || async {
+            if maybe_created.load(Ordering::Acquire) && create_model.is_sequential() {
+                if let Some(created) = self.find_protected(&target).await? {
+                    return Ok(created);
+                }
+            }
+            match self
+                .create(target.as_str(), data, acl_list.clone(), create_model)
+                .await
+            {
+                Err(e)
+                    if e.code() == Some(ServerErrorCode::NodeExists)
+                        && create_model.is_ephemeral()
+                        && maybe_created.load(Ordering::Acquire) =>
+                {
+                    match self.exists(target.as_str()).await? {
+                        Some(stat) if stat.ephemeral_owner == self.client.session_id() => {
+                            Ok(target.clone())
+                        }
+                        _ => Err(e),
+                    }
+                }
+                Err(e) => {
+                    if e.is_retryable() {
+                        maybe_created.store(true, Ordering::Release);
+                    }
+                    Err(e)
+                }
+                created => created,
+            }
+        })
+        .await
+    }
+
+    /// 在父节点下查找带有相同保护前缀的顺序节点
+    async fn find_protected(&self, target: &str) -> ZKResult<Option<String>> {
+        let (parent, protected_name) = paths::split(target);
+        let children = match self.children(parent).await {
+            Ok(children) => children,
+            Err(e) if e.code() == Some(ServerErrorCode::NoNode) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        Ok(children
+            .into_iter()
+            .find(|child| child.starts_with(protected_name))
+            .map(|child| paths::join(parent, &child)))
     }
 
     /// 删除目标路径的节点数据
@@ -109,7 +298,7 @@ impl ZooKeeper {
     ///
     /// # Args
     /// - `path`： 目标路径，必须以 "/" 开头
-    pub async fn delete(&mut self, path: impl Into<&str>) -> ZKResult<()> {
+    pub async fn delete(&self, path: impl AsRef<str>) -> ZKResult<()> {
         self.deletev(path, VersionType::NoVersion).await?;
         Ok(())
     }
@@ -123,15 +312,15 @@ impl ZooKeeper {
     /// # Args
     /// - `path`： 目标路径，必须以 "/" 开头
     /// - `version`： 节点指定的版本号，参考 [`VersionType`]
-    pub async fn deletev(&mut self, path: impl Into<&str>, version: VersionType) -> ZKResult<()> {
-        let path = path.into();
+    pub async fn deletev(&self, path: impl AsRef<str>, version: VersionType) -> ZKResult<()> {
+        let path = path.as_ref();
         paths::validate_path(path)?;
-        let rh = Some(RequestHeader::new(OpCode::Delete));
+        let rh = RequestHeader::new(OpCode::Delete);
         let mut req = BytesMut::new();
         let request = DeleteRequest::new(self.client.get_path(path), version);
         request.write(&mut req)?;
         let resp = IgnoreResponse::default();
-        self.client.submit_request(rh, req, resp).await?;
+        self.client.submit_request(rh, path, req, resp).await?;
         Ok(())
     }
 
@@ -146,7 +335,7 @@ impl ZooKeeper {
     /// - `data`： 节点数据
     /// # Returns
     /// - `Stat`： 统计对象，请查看 [`Stat`]
-    pub async fn set(&mut self, path: impl Into<&str>, data: &[u8]) -> ZKResult<Stat> {
+    pub async fn set(&self, path: impl AsRef<str>, data: &[u8]) -> ZKResult<Stat> {
         self.setv(path, data, VersionType::NoVersion).await
     }
 
@@ -163,19 +352,20 @@ impl ZooKeeper {
     /// # Returns
     /// - `Stat`： 统计对象，请查看 [`Stat`]
     pub async fn setv(
-        &mut self,
-        path: impl Into<&str>,
+        &self,
+        path: impl AsRef<str>,
         data: &[u8],
         version: VersionType,
     ) -> ZKResult<Stat> {
-        let path = path.into();
+        let path = path.as_ref();
         paths::validate_path(path)?;
-        let rh = Some(RequestHeader::new(OpCode::SetData));
+        let data = self.client.compress(path, data)?;
+        let rh = RequestHeader::new(OpCode::SetData);
         let mut req = BytesMut::new();
-        let request = SetDataRequest::new(self.client.get_path(path), data, version);
+        let request = SetDataRequest::new(self.client.get_path(path), &data, version);
         request.write(&mut req)?;
         let resp = SetDataResponse::default();
-        let resp = self.client.submit_request(rh, req, resp).await?;
+        let resp = self.client.submit_request(rh, path, req, resp).await?;
         Ok(resp.stat)
     }
 
@@ -190,11 +380,7 @@ impl ZooKeeper {
     /// - `stat`： 统计数据，可选，如果不为 None 则会将节点统计结果写入该对象, 关于更多统计对象，请查看 [`Stat`]
     /// # Returns
     /// - `Vec<u8>`： 目标节点的数据以字节数组的形式
-    pub async fn get(
-        &mut self,
-        path: impl Into<&str>,
-        stat: Option<&mut Stat>,
-    ) -> ZKResult<Vec<u8>> {
+    pub async fn get(&self, path: impl AsRef<str>, stat: Option<&mut Stat>) -> ZKResult<Vec<u8>> {
         self.getw(path, None::<DummyWatcher>, stat).await
     }
 
@@ -211,14 +397,14 @@ impl ZooKeeper {
     /// # Returns
     /// - `Vec<u8>`： 目标节点的数据以字节数组的形式
     pub async fn getw(
-        &mut self,
-        path: impl Into<&str>,
+        &self,
+        path: impl AsRef<str>,
         watcher: Option<impl Watcher + 'static>,
         stat: Option<&mut Stat>,
     ) -> ZKResult<Vec<u8>> {
-        let path = path.into();
+        let path = path.as_ref();
         paths::validate_path(path)?;
-        let rh = Some(RequestHeader::new(OpCode::GetData));
+        let rh = RequestHeader::new(OpCode::GetData);
         let mut req = BytesMut::new();
         let full_path = self.client.get_path(path);
         let watch = match watcher {
@@ -233,11 +419,230 @@ impl ZooKeeper {
         let request = PathAndWatchRequest::new(full_path, watch);
         request.write(&mut req)?;
         let resp = GetDataResponse::default();
-        let resp = self.client.submit_request(rh, req, resp).await?;
+        let resp = self.client.submit_request(rh, path, req, resp).await?;
         if let Some(s) = stat {
             *s = resp.stat;
         }
-        Ok(resp.data)
+        self.client.decompress(path, resp.data)
+    }
+
+    /// 使用 JSON 编码后创建节点，参考 [`ZooKeeper::create`]，需要开启 `json` feature
+    /// # Examples
+    /// ```rust,ignore
+    /// let path = zk.create_as("/your/path", &config, ACL::world_acl(), CreateMode::Persistent).await?;
+    /// ```
+    ///
+    /// # Args
+    /// - `path`： 目标路径，必须以 "/" 开头
+    /// - `value`： 节点的数据，会通过 [`JsonCodec`] 编码
+    /// - `acl`： 该节点的权限数据，可以有多个，参考 [`ACL`]
+    /// - `create_model`： 节点的模式，参考 [`CreateMode`]
+    /// # Returns
+    /// - `String`：目标路径，同参数 `path`
+    /// # Errors
+    ///
+    /// 编码失败时返回 [`ZKError::EncodeError`]
+    #[cfg(feature = "json")]
+    pub async fn create_as<T: Serialize + ?Sized>(
+        &self,
+        path: impl AsRef<str>,
+        value: &T,
+        acl_list: Vec<ACL>,
+        create_model: CreateMode,
+    ) -> ZKResult<String> {
+        self.create_as_with(path, value, acl_list, create_model, &JsonCodec)
+            .await
+    }
+
+    /// 使用指定的编解码规则创建节点，参考 [`ZooKeeper::create`]
+    /// # Examples
+    /// ```rust,ignore
+    /// let path = zk.create_as_with("/your/path", &config, ACL::world_acl(), CreateMode::Persistent, &BincodeCodec)
+    ///        .await?;
+    /// ```
+    ///
+    /// # Args
+    /// - `path`： 目标路径，必须以 "/" 开头
+    /// - `value`： 节点的数据，会通过 `codec` 编码
+    /// - `acl`： 该节点的权限数据，可以有多个，参考 [`ACL`]
+    /// - `create_model`： 节点的模式，参考 [`CreateMode`]
+    /// - `codec`： 编解码规则，参考 [`Codec`]
+    /// # Returns
+    /// - `String`：目标路径，同参数 `path`
+    /// # Errors
+    ///
+    /// 编码失败时返回 [`ZKError::EncodeError`]
+    pub async fn create_as_with<T: Serialize + ?Sized>(
+        &self,
+        path: impl AsRef<str>,
+        value: &T,
+        acl_list: Vec<ACL>,
+        create_model: CreateMode,
+        codec: &impl Codec,
+    ) -> ZKResult<String> {
+        let path = path.as_ref();
+        let data = encode(path, value, codec)?;
+        self.create(path, Some(&data), acl_list, create_model).await
+    }
+
+    /// 使用 JSON 编码后设置节点数据，参考 [`ZooKeeper::set`]，需要开启 `json` feature
+    /// # Examples
+    /// ```rust,ignore
+    /// let stat = zk.set_as("/your/path", &config).await?;
+    /// ```
+    ///
+    /// # Args
+    /// - `path`： 目标路径，必须以 "/" 开头
+    /// - `value`： 节点的数据，会通过 [`JsonCodec`] 编码
+    /// # Returns
+    /// - `Stat`： 统计对象，请查看 [`Stat`]
+    /// # Errors
+    ///
+    /// 编码失败时返回 [`ZKError::EncodeError`]
+    #[cfg(feature = "json")]
+    pub async fn set_as<T: Serialize + ?Sized>(
+        &self,
+        path: impl AsRef<str>,
+        value: &T,
+    ) -> ZKResult<Stat> {
+        self.set_as_with(path, value, &JsonCodec).await
+    }
+
+    /// 使用指定的编解码规则设置节点数据，参考 [`ZooKeeper::set`]
+    /// # Examples
+    /// ```rust,ignore
+    /// let stat = zk.set_as_with("/your/path", &config, &BincodeCodec).await?;
+    /// ```
+    ///
+    /// # Args
+    /// - `path`： 目标路径，必须以 "/" 开头
+    /// - `value`： 节点的数据，会通过 `codec` 编码
+    /// - `codec`： 编解码规则，参考 [`Codec`]
+    /// # Returns
+    /// - `Stat`： 统计对象，请查看 [`Stat`]
+    /// # Errors
+    ///
+    /// 编码失败时返回 [`ZKError::EncodeError`]
+    pub async fn set_as_with<T: Serialize + ?Sized>(
+        &self,
+        path: impl AsRef<str>,
+        value: &T,
+        codec: &impl Codec,
+    ) -> ZKResult<Stat> {
+        let path = path.as_ref();
+        let data = encode(path, value, codec)?;
+        self.set(path, &data).await
+    }
+
+    /// 获取节点数据并使用 JSON 解码，参考 [`ZooKeeper::get`]，需要开启 `json` feature
+    /// # Examples
+    /// ```rust,ignore
+    /// let config = zk.get_as::<Config>("/your/path").await?;
+    /// ```
+    ///
+    /// # Args
+    /// - `path`： 目标路径，必须以 "/" 开头
+    /// # Returns
+    /// - `T`： 解码后的对象
+    /// # Errors
+    ///
+    /// 解码失败时返回 [`ZKError::DecodeError`]，其中包含节点路径
+    #[cfg(feature = "json")]
+    pub async fn get_as<T: DeserializeOwned>(&self, path: impl AsRef<str>) -> ZKResult<T> {
+        self.get_as_with(path, &JsonCodec, None).await
+    }
+
+    /// 获取节点数据并使用指定的编解码规则解码，参考 [`ZooKeeper::get`]
+    /// # Examples
+    /// ```rust,ignore
+    /// let mut stat = Stat::default();
+    /// let config = zk.get_as_with::<Config>("/your/path", &BincodeCodec, Some(&mut stat)).await?;
+    /// ```
+    ///
+    /// # Args
+    /// - `path`： 目标路径，必须以 "/" 开头
+    /// - `codec`： 编解码规则，参考 [`Codec`]
+    /// - `stat`： 统计数据，可选，如果不为 None 则会将节点统计结果写入该对象, 关于更多统计对象，请查看 [`Stat`]
+    /// # Returns
+    /// - `T`： 解码后的对象
+    /// # Errors
+    ///
+    /// 解码失败时返回 [`ZKError::DecodeError`]，其中包含节点路径
+    pub async fn get_as_with<T: DeserializeOwned>(
+        &self,
+        path: impl AsRef<str>,
+        codec: &impl Codec,
+        stat: Option<&mut Stat>,
+    ) -> ZKResult<T> {
+        let path = path.as_ref();
+        let data = self.get(path, stat).await?;
+        codec.decode(&data).map_err(|source| ZKError::DecodeError {
+            path: path.to_string(),
+            codec: codec.name(),
+            source,
+        })
+    }
+
+    /// 原子地提交多个操作，全部成功或者全部失败，参考 [`Op`]
+    /// # Examples
+    /// ```rust,ignore
+    /// let results = zk
+    ///     .multi(vec![
+    ///         Op::create("/a", Some(b"1"), ACL::world_acl(), CreateMode::Persistent),
+    ///         Op::set_data("/b", b"2", 5),
+    ///         Op::delete("/c", -1),
+    ///     ])
+    ///     .await?;
+    /// ```
+    ///
+    /// # Args
+    /// - `ops`： 操作列表，按顺序执行，路径必须以 "/" 开头
+    /// # Returns
+    /// - `Vec<OpResult>`：与 `ops` 一一对应的结果，参考 [`OpResult`]
+    /// # Errors
+    ///
+    /// 任意操作失败时整个事务回滚，返回的错误中 [`ZKError::op`] 和 [`ZKError::path`] 为失败的操作
+    pub async fn multi(&self, ops: Vec<Op>) -> ZKResult<Vec<OpResult>> {
+        let first = match ops.first() {
+            Some(op) => op.path().to_string(),
+            None => return Ok(Vec::new()),
+        };
+        let mut full_ops = Vec::with_capacity(ops.len());
+        for op in ops.iter() {
+            paths::validate_path(op.path())?;
+            let mut op = op.clone();
+            match &mut op {
+                Op::Create {
+                    path,
+                    data: Some(data),
+                    ..
+                }
+                | Op::SetData { path, data, .. } => *data = self.client.compress(path, data)?,
+                _ => {}
+            }
+            *op.path_mut() = self.client.get_path(op.path());
+            full_ops.push(op);
+        }
+        let rh = RequestHeader::new(OpCode::Multi);
+        let mut req = BytesMut::new();
+        MultiRequest::new(full_ops).write(&mut req)?;
+        let resp = MultiResponse::default();
+        let resp = self.client.submit_request(rh, &first, req, resp).await?;
+        // 失败操作之后的操作都是 RuntimeInconsistency，需要找到真正失败的操作
+        let failed = resp
+            .results
+            .iter()
+            .enumerate()
+            .filter_map(|(i, result)| match result {
+                OpResult::Error { code, err } if *err != 0 => Some((i, *code, *err)),
+                _ => None,
+            })
+            .min_by_key(|(_, code, _)| *code == ServerErrorCode::RuntimeInconsistency);
+        if let Some((i, code, err)) = failed {
+            let op = &ops[i.min(ops.len() - 1)];
+            return Err(ZKError::ServerError(code, err).with_operation(op.op_code(), op.path()));
+        }
+        Ok(resp.results)
     }
 
     /// 判断目标路径是否存在，不需要回调
@@ -250,7 +655,7 @@ impl ZooKeeper {
     /// - `path`： 目标路径，必须以 "/" 开头
     /// # Returns
     /// - `Stat`： 统计对象，请查看 [`Stat`]
-    pub async fn exists(&mut self, path: impl Into<&str>) -> ZKResult<Option<Stat>> {
+    pub async fn exists(&self, path: impl AsRef<str>) -> ZKResult<Option<Stat>> {
         self.existsw(path, None::<DummyWatcher>).await
     }
 
@@ -266,13 +671,13 @@ impl ZooKeeper {
     /// # Returns
     /// - `Stat`： 统计对象，请查看 [`Stat`]
     pub async fn existsw(
-        &mut self,
-        path: impl Into<&str>,
+        &self,
+        path: impl AsRef<str>,
         watcher: Option<impl Watcher + 'static>,
     ) -> ZKResult<Option<Stat>> {
-        let path = path.into();
+        let path = path.as_ref();
         paths::validate_path(path)?;
-        let rh = Some(RequestHeader::new(OpCode::Exists));
+        let rh = RequestHeader::new(OpCode::Exists);
         let mut req = BytesMut::new();
         let full_path = self.client.get_path(path);
         let watch = match watcher {
@@ -287,10 +692,10 @@ impl ZooKeeper {
         let request = PathAndWatchRequest::new(full_path, watch);
         request.write(&mut req)?;
         let resp = SetDataResponse::default();
-        match self.client.submit_request(rh, req, resp).await {
+        match self.client.submit_request(rh, path, req, resp).await {
             Ok(resp) => Ok(Some(resp.stat)),
             Err(e) => match e {
-                ZKError::ServerError(ServerErrorCode::NoNode, _) => Ok(None),
+                _ if e.code() == Some(ServerErrorCode::NoNode) => Ok(None),
                 _ => Err(e),
             },
         }
@@ -306,7 +711,7 @@ impl ZooKeeper {
     /// - `path`： 目标路径，必须以 "/" 开头
     /// # Returns
     /// - `Vec<String>`： 子节点列表
-    pub async fn children(&mut self, path: impl Into<&str>) -> ZKResult<Vec<String>> {
+    pub async fn children(&self, path: impl AsRef<str>) -> ZKResult<Vec<String>> {
         self.childrenw(path, None::<DummyWatcher>).await
     }
 
@@ -322,13 +727,13 @@ impl ZooKeeper {
     /// # Returns
     /// - `Vec<String>`： 子节点列表
     pub async fn childrenw(
-        &mut self,
-        path: impl Into<&str>,
+        &self,
+        path: impl AsRef<str>,
         watcher: Option<impl Watcher + 'static>,
     ) -> ZKResult<Vec<String>> {
-        let path = path.into();
+        let path = path.as_ref();
         paths::validate_path(path)?;
-        let rh = Some(RequestHeader::new(OpCode::GetChildren));
+        let rh = RequestHeader::new(OpCode::GetChildren);
         let mut req = BytesMut::new();
         let full_path = self.client.get_path(path);
         let watch = match watcher {
@@ -343,7 +748,7 @@ impl ZooKeeper {
         let request = PathAndWatchRequest::new(full_path, watch);
         request.write(&mut req)?;
         let resp = PathListResponse::default();
-        let resp = self.client.submit_request(rh, req, resp).await?;
+        let resp = self.client.submit_request(rh, path, req, resp).await?;
         Ok(resp.path_list)
     }
 
@@ -359,11 +764,7 @@ impl ZooKeeper {
     /// - `stat`： 统计数据，统计结果会写入该对象, 关于更多统计对象，请查看 [`Stat`]
     /// # Returns
     /// - `Vec<String>`： 子节点列表
-    pub async fn childrens(
-        &mut self,
-        path: impl Into<&str>,
-        stat: &mut Stat,
-    ) -> ZKResult<Vec<String>> {
+    pub async fn childrens(&self, path: impl AsRef<str>, stat: &mut Stat) -> ZKResult<Vec<String>> {
         self.childrensw(path, None::<DummyWatcher>, stat).await
     }
 
@@ -381,14 +782,14 @@ impl ZooKeeper {
     /// # Returns
     /// - `Vec<String>`： 子节点列表
     pub async fn childrensw(
-        &mut self,
-        path: impl Into<&str>,
+        &self,
+        path: impl AsRef<str>,
         watcher: Option<impl Watcher + 'static>,
         stat: &mut Stat,
     ) -> ZKResult<Vec<String>> {
-        let path = path.into();
+        let path = path.as_ref();
         paths::validate_path(path)?;
-        let rh = Some(RequestHeader::new(OpCode::GetChildren2));
+        let rh = RequestHeader::new(OpCode::GetChildren2);
         let mut req = BytesMut::new();
         let full_path = self.client.get_path(path);
         let watch = match watcher {
@@ -403,7 +804,7 @@ impl ZooKeeper {
         let request = PathAndWatchRequest::new(full_path, watch);
         request.write(&mut req)?;
         let resp = GetChildren2Response::default();
-        let resp = self.client.submit_request(rh, req, resp).await?;
+        let resp = self.client.submit_request(rh, path, req, resp).await?;
         *stat = resp.stat;
         Ok(resp.path_list)
     }
@@ -418,16 +819,16 @@ impl ZooKeeper {
     /// - `path`： 目标路径，必须以 "/" 开头
     /// # Returns
     /// - `u32`： 目标路径下的所有子节点数量
-    pub async fn children_count(&mut self, path: impl Into<&str>) -> ZKResult<u32> {
-        let path = path.into();
+    pub async fn children_count(&self, path: impl AsRef<str>) -> ZKResult<u32> {
+        let path = path.as_ref();
         paths::validate_path(path)?;
-        let rh = Some(RequestHeader::new(OpCode::GetAllChildrenNumber));
+        let rh = RequestHeader::new(OpCode::GetAllChildrenNumber);
         let mut req = BytesMut::new();
         let full_path = self.client.get_path(path);
         let request = PathRequest::new(full_path);
         request.write(&mut req)?;
         let resp = GetAllChildrenNumberResponse::default();
-        let resp = self.client.submit_request(rh, req, resp).await?;
+        let resp = self.client.submit_request(rh, path, req, resp).await?;
         Ok(resp.total_number)
     }
 
@@ -441,16 +842,16 @@ impl ZooKeeper {
     /// - `path`： 目标路径，必须以 "/" 开头，不会拼接 chroot
     /// # Returns
     /// - `Vec<String>`： 所有符合条件临时节点的列表
-    pub async fn get_ephemerals(&mut self, path: impl Into<&str>) -> ZKResult<Vec<String>> {
-        let path = path.into();
+    pub async fn get_ephemerals(&self, path: impl AsRef<str>) -> ZKResult<Vec<String>> {
+        let path = path.as_ref();
         paths::validate_path(path)?;
-        let rh = Some(RequestHeader::new(OpCode::GetEphemerals));
+        let rh = RequestHeader::new(OpCode::GetEphemerals);
         let mut req = BytesMut::new();
         // 不需要拼接 chroot
         let request = PathRequest::new(path.to_string());
         request.write(&mut req)?;
         let resp = PathListResponse::default();
-        let resp = self.client.submit_request(rh, req, resp).await?;
+        let resp = self.client.submit_request(rh, path, req, resp).await?;
         Ok(resp.path_list)
     }
 
@@ -466,19 +867,19 @@ impl ZooKeeper {
     /// # Returns
     /// - `Vec<ACL>`： 节点的 ACL 列表
     pub async fn get_acl(
-        &mut self,
-        path: impl Into<&str>,
+        &self,
+        path: impl AsRef<str>,
         stat: Option<&mut Stat>,
     ) -> ZKResult<Vec<ACL>> {
-        let path = path.into();
+        let path = path.as_ref();
         paths::validate_path(path)?;
-        let rh = Some(RequestHeader::new(OpCode::GetACL));
+        let rh = RequestHeader::new(OpCode::GetACL);
         let mut req = BytesMut::new();
         let full_path = self.client.get_path(path);
         let request = PathRequest::new(full_path);
         request.write(&mut req)?;
         let resp = GetACLResponse::default();
-        let resp = self.client.submit_request(rh, req, resp).await?;
+        let resp = self.client.submit_request(rh, path, req, resp).await?;
         if let Some(s) = stat {
             *s = resp.stat;
         }
@@ -497,20 +898,20 @@ impl ZooKeeper {
     /// # Returns
     /// - `Stat`： 统计对象，请查看 [`Stat`]
     pub async fn set_acl(
-        &mut self,
-        path: impl Into<&str>,
+        &self,
+        path: impl AsRef<str>,
         acl_list: Vec<ACL>,
         version: i32,
     ) -> ZKResult<Stat> {
-        let path = path.into();
+        let path = path.as_ref();
         paths::validate_path(path)?;
-        let rh = Some(RequestHeader::new(OpCode::SetACL));
+        let rh = RequestHeader::new(OpCode::SetACL);
         let mut req = BytesMut::new();
         let full_path = self.client.get_path(path);
         let request = SetACLRequest::new(full_path, acl_list, version);
         request.write(&mut req)?;
         let resp = SetDataResponse::default();
-        let resp = self.client.submit_request(rh, req, resp).await?;
+        let resp = self.client.submit_request(rh, path, req, resp).await?;
         Ok(resp.stat)
     }
 
@@ -525,14 +926,14 @@ impl ZooKeeper {
     /// - `watcher`： 回调对象，必须实现 [`Watcher`] trait
     /// - `mode`： 添加回调的种类，请查看 [`AddWatchMode`]
     pub async fn add_watch<W: Watcher + 'static>(
-        &mut self,
-        path: impl Into<&str>,
+        &self,
+        path: impl AsRef<str>,
         watcher: W,
         mode: AddWatchMode,
     ) -> ZKResult<()> {
-        let path = path.into();
+        let path = path.as_ref();
         paths::validate_path(path)?;
-        let rh = Some(RequestHeader::new(OpCode::AddWatch));
+        let rh = RequestHeader::new(OpCode::AddWatch);
         let mut req = BytesMut::new();
         let full_path = self.client.get_path(path);
         self.client.register_persistent_watcher(
@@ -543,7 +944,7 @@ impl ZooKeeper {
         let request = AddWatchRequest::new(full_path, mode);
         request.write(&mut req)?;
         self.client
-            .submit_request(rh, req, DummyResponse::default())
+            .submit_request(rh, path, req, DummyResponse)
             .await?;
         Ok(())
     }
@@ -561,8 +962,8 @@ impl ZooKeeper {
     /// - `local`：
     #[allow(unused)]
     pub async fn remove_watches<W: Watcher + 'static>(
-        &mut self,
-        path: impl Into<&str>,
+        &self,
+        path: impl AsRef<str>,
         watcher: W,
         watcher_type: WatcherType,
         local: bool,
@@ -579,7 +980,7 @@ impl ZooKeeper {
     /// # Returns
     /// - `States`： 关于更多客户端状态，请查看 [`States`]
     pub fn state(&self) -> ZKResult<States> {
-        Ok(self.client.state.clone())
+        Ok(self.client.state())
     }
 
     /// 获取客户端当前 session_id
@@ -591,7 +992,7 @@ impl ZooKeeper {
     /// # Returns
     /// - `i64`： 服务端分配的唯一会话 ID
     pub fn session_id(&self) -> ZKResult<i64> {
-        Ok(self.client.session_id)
+        Ok(self.client.session_id())
     }
 
     /// 获取客户端当前会话超时时间
@@ -603,6 +1004,112 @@ impl ZooKeeper {
     /// # Returns
     /// - `u32`： 客户端的会话超时时间
     pub fn session_timeout(&self) -> ZKResult<u32> {
-        Ok(self.client.session_timeout)
+        Ok(self.client.session_timeout())
+    }
+
+    /// 订阅客户端连接状态的变化
+    /// # Examples
+    /// ```rust,ignore
+    /// let mut listener = zk.subscribe_state();
+    /// while let Some(event) = listener.recv().await {
+    ///     if event.state == ConnectionState::Suspended {
+    ///         // 暂停所有依赖会话的工作
+    ///     }
+    /// }
+    /// ```
+    ///
+    /// # Returns
+    /// - `StateListener`： 状态订阅者，只会收到订阅之后发生的状态变化，请查看 [`StateListener`]
+    pub fn subscribe_state(&self) -> StateListener {
+        self.client.subscribe_state()
+    }
+
+    /// 等待客户端连接成功，已经连接时直接返回
+    /// # Examples
+    /// ```rust,ignore
+    /// zk.wait_until_connected(Duration::from_secs(5)).await?;
+    /// ```
+    ///
+    /// # Args
+    /// - `timeout`： 最长等待时间
+    /// # Errors
+    ///
+    /// 等待超时或者客户端已经关闭、会话已经过期时返回异常
+    pub async fn wait_until_connected(&self, timeout: Duration) -> ZKResult<()> {
+        self.client.wait_until_connected(timeout).await
+    }
+
+    /// 按照客户端的重试策略执行操作，只有 [`ZKError::is_retryable`] 的错误才会重试，
+    /// 参考 [`ZooKeeperBuilder::retry_policy`]
+    ///
+    /// 每次执行都会传入一个 clone 出来的客户端，便于在 `async move` 中使用。注意非幂等的操作
+    /// （例如创建顺序节点）在响应丢失后重试可能会重复执行，创建节点请使用 [`ZooKeeper::create_with_retry`]
+    /// # Examples
+    /// ```rust,ignore
+    /// let data = zk.retry(|zk| async move { zk.get("/your/path", None).await }).await?;
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// 不可重试的错误或者重试策略不再允许重试时，返回最后一次的错误
+    ///
+    /// [`ZKError::is_retryable`]: crate::ZKError::is_retryable
+    pub async fn retry<T, F, Fut>(&self, operation: F) -> ZKResult<T>
+    where
+        F: FnMut(ZooKeeper) -> Fut,
+        Fut: Future<Output = ZKResult<T>>,
+    {
+        let policy = self.client.retry_policy();
+        self.retry_with(policy.as_ref(), operation).await
+    }
+
+    /// 按照指定的重试策略执行操作，参考 [`ZooKeeper::retry`]
+    /// # Examples
+    /// ```rust,ignore
+    /// let policy = RetryUntilElapsed::new(Duration::from_secs(10), Duration::from_millis(200));
+    /// zk.retry_with(&policy, |zk| async move { zk.set("/your/path", b"data").await }).await?;
+    /// ```
+    pub async fn retry_with<T, F, Fut>(
+        &self,
+        policy: &dyn RetryPolicy,
+        mut operation: F,
+    ) -> ZKResult<T>
+    where
+        F: FnMut(ZooKeeper) -> Fut,
+        Fut: Future<Output = ZKResult<T>>,
+    {
+        retry::run(policy, || operation(self.clone())).await
+    }
+
+    /// 为当前会话添加鉴权信息，服务端接受后鉴权信息会被保存下来，之后每次重连都会自动重新发送
+    /// # Examples
+    /// ```rust,ignore
+    /// zk.add_auth("digest", "user:password").await?;
+    /// ```
+    ///
+    /// # Args
+    /// - `scheme`： 鉴权模式，例如 "digest"
+    /// - `auth`： 鉴权数据，例如 "user:password"
+    /// # Errors
+    ///
+    /// 鉴权失败时返回 `AuthFailed`，同时客户端状态变为 [`KeeperState::AuthFailed`]，客户端不再可用；
+    /// 未连接时返回 `ConnectionLoss`，鉴权信息不会被保存，需要在重连后再次调用
+    ///
+    /// [`KeeperState::AuthFailed`]: crate::KeeperState::AuthFailed
+    pub async fn add_auth(
+        &self,
+        scheme: impl Into<String>,
+        auth: impl Into<Vec<u8>>,
+    ) -> ZKResult<()> {
+        self.client.add_auth(scheme.into(), auth.into()).await
+    }
+
+    /// 关闭客户端会话，所有 clone 出来的客户端都会一起关闭
+    /// # Examples
+    /// ```rust,ignore
+    /// zk.close().await?;
+    /// ```
+    pub async fn close(&self) -> ZKResult<()> {
+        self.client.close().await
     }
 }
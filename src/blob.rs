@@ -0,0 +1,406 @@
+//! # 分块存储模块
+//! 单个节点的数据受服务端 `jute.maxbuffer`（默认约 1 MB）限制，[`ZooKeeper::put_blob`] 将较大的数据切分为多个分块，
+//! 保存在清单节点下的顺序子节点中，清单节点记录分块列表、总长度和 SHA-1 校验和
+//!
+//! 写入时先通过 [`ZooKeeper::multi`] 分批原子地创建分块，再带版本条件更新清单节点发布新的分块列表，
+//! 最后删除旧的分块。读取方只会看到完整发布的数据，读取时会重新计算校验和
+//!
+//! ```text
+//! /config/routes                  清单节点
+//! /config/routes/chunk-0000000000 分块
+//! /config/routes/chunk-0000000001 分块
+//! ```
+
+use sha1::{Digest, Sha1};
+
+use crate::constants::{CreateMode, VersionType};
+use crate::error::ServerErrorCode;
+use crate::protocol::req::Op;
+use crate::protocol::resp::{OpResult, Stat};
+use crate::{paths, ZKError, ZKResult, ZooKeeper};
+
+const MANIFEST_HEADER: &str = "repeekooz-blob/1";
+const CHUNK_PREFIX: &str = "chunk-";
+/// 分块在读取过程中被并发的写入删除时，重新读取清单的次数
+const READ_ATTEMPTS: usize = 3;
+
+/// 分块存储的配置
+/// # Examples
+///
+/// ```rust,ignore
+/// let options = BlobOptions::default().chunk_size(128 * 1024).batch_size(512 * 1024);
+/// zk.put_blob_with("/config/routes", &data, &options).await?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct BlobOptions {
+    chunk_size: usize,
+    batch_size: usize,
+}
+
+impl Default for BlobOptions {
+    fn default() -> Self {
+        BlobOptions {
+            chunk_size: 256 * 1024,
+            batch_size: 768 * 1024,
+        }
+    }
+}
+
+impl BlobOptions {
+    /// 单个分块的最大字节数，默认 256 KB
+    pub fn chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    /// 单个 multi 请求中分块的最大总字节数，整个请求需要小于服务端的 `jute.maxbuffer`，默认 768 KB。
+    /// 每个请求至少包含一个分块
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size;
+        self
+    }
+
+    /// 将分块按照 `batch_size` 分组
+    fn batches<'a>(&self, chunks: &'a [&'a [u8]]) -> Vec<&'a [&'a [u8]]> {
+        let mut batches = Vec::new();
+        let mut start = 0;
+        let mut size = 0;
+        for (i, chunk) in chunks.iter().enumerate() {
+            if i > start && size + chunk.len() > self.batch_size {
+                batches.push(&chunks[start..i]);
+                start = i;
+                size = 0;
+            }
+            size += chunk.len();
+        }
+        if start < chunks.len() {
+            batches.push(&chunks[start..]);
+        }
+        batches
+    }
+}
+
+/// 清单节点的数据，按行保存
+#[derive(Debug, Default, Eq, PartialEq)]
+struct Manifest {
+    size: usize,
+    sha1: String,
+    chunks: Vec<String>,
+}
+
+impl Manifest {
+    fn new(data: &[u8], chunks: Vec<String>) -> Manifest {
+        Manifest {
+            size: data.len(),
+            sha1: checksum(data),
+            chunks,
+        }
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        format!(
+            "{}\nsize={}\nsha1={}\nchunks={}\n",
+            MANIFEST_HEADER,
+            self.size,
+            self.sha1,
+            self.chunks.join(",")
+        )
+        .into_bytes()
+    }
+
+    fn decode(data: &[u8]) -> Option<Manifest> {
+        let text = std::str::from_utf8(data).ok()?;
+        let mut lines = text.lines();
+        if lines.next()? != MANIFEST_HEADER {
+            return None;
+        }
+        let mut manifest = Manifest::default();
+        for line in lines {
+            match line.split_once('=')? {
+                ("size", size) => manifest.size = size.parse().ok()?,
+                ("sha1", sha1) => manifest.sha1 = sha1.to_string(),
+                ("chunks", "") => {}
+                ("chunks", chunks) => {
+                    manifest.chunks = chunks.split(',').map(String::from).collect()
+                }
+                // 忽略之后版本新增的字段
+                _ => {}
+            }
+        }
+        Some(manifest)
+    }
+}
+
+fn checksum(data: &[u8]) -> String {
+    format!("{:x}", Sha1::digest(data))
+}
+
+fn blob_error(path: &str, reason: String) -> ZKError {
+    ZKError::DecodeError {
+        path: path.to_string(),
+        codec: "blob",
+        source: reason.into(),
+    }
+}
+
+impl ZooKeeper {
+    /// 使用默认配置分块写入数据，参考 [`ZooKeeper::put_blob_with`]
+    /// # Examples
+    /// ```rust,ignore
+    /// let stat = zk.put_blob("/config/routes", &data).await?;
+    /// ```
+    pub async fn put_blob(&self, path: impl AsRef<str>, data: &[u8]) -> ZKResult<Stat> {
+        self.put_blob_with(path, data, &BlobOptions::default())
+            .await
+    }
+
+    /// 将数据切分为多个分块写入清单节点下，清单节点不存在时会自动创建
+    ///
+    /// 分块通过 multi 原子地创建，再带版本条件更新清单节点，因此读取方只会看到完整发布的数据。
+    /// 多个客户端同时写入时只有一个能够成功，其余返回 `BadVersion`，失败时已经创建的分块会被删除
+    /// # Examples
+    /// ```rust,ignore
+    /// let stat = zk.put_blob_with("/config/routes", &data, &BlobOptions::default()).await?;
+    /// ```
+    ///
+    /// # Args
+    /// - `path`： 清单节点路径，必须以 "/" 开头
+    /// - `data`： 完整的数据
+    /// - `options`： 分块配置，参考 [`BlobOptions`]
+    /// # Returns
+    /// - `Stat`： 清单节点的统计对象，请查看 [`Stat`]
+    /// # Errors
+    ///
+    /// 节点已经存在并且保存的不是清单（也不是空数据）时返回 [`ZKError::DecodeError`]，节点数据不会被覆盖
+    pub async fn put_blob_with(
+        &self,
+        path: impl AsRef<str>,
+        data: &[u8],
+        options: &BlobOptions,
+    ) -> ZKResult<Stat> {
+        let path = path.as_ref();
+        paths::validate_path(path)?;
+        let (version, old_chunks) = self.read_manifest_for_update(path).await?;
+
+        let chunks: Vec<&[u8]> = data.chunks(options.chunk_size).collect();
+        let chunk_prefix = paths::join(path, CHUNK_PREFIX);
+        let mut created = Vec::with_capacity(chunks.len());
+        for batch in options.batches(&chunks) {
+            let ops = batch
+                .iter()
+                .map(|chunk| {
+                    Op::create(
+                        chunk_prefix.as_str(),
+                        Some(chunk),
+                        self.default_acl(),
+                        CreateMode::PersistentSequential,
+                    )
+                })
+                .collect();
+            match self.multi(ops).await {
+                Ok(results) => created.extend(results.into_iter().filter_map(|r| match r {
+                    OpResult::Create { path } => Some(paths::split(&path).1.to_string()),
+                    _ => None,
+                })),
+                Err(e) => {
+                    self.delete_chunks(path, &created).await;
+                    return Err(e);
+                }
+            }
+        }
+
+        let manifest = Manifest::new(data, created);
+        let stat = match self
+            .setv(path, &manifest.encode(), VersionType::Version(version))
+            .await
+        {
+            Ok(stat) => stat,
+            Err(e) => {
+                self.delete_chunks(path, &manifest.chunks).await;
+                return Err(e);
+            }
+        };
+        self.delete_chunks(path, &old_chunks).await;
+        Ok(stat)
+    }
+
+    /// 读取清单节点的版本和当前的分块列表，清单节点不存在时创建一个空的清单，
+    /// 节点中保存的不是清单时返回错误
+    async fn read_manifest_for_update(&self, path: &str) -> ZKResult<(i32, Vec<String>)> {
+        loop {
+            let mut stat = Stat::default();
+            match self.get(path, Some(&mut stat)).await {
+                // 空节点可以作为清单使用，其他数据不能被覆盖
+                Ok(data) if data.is_empty() => return Ok((stat.version, Vec::new())),
+                Ok(data) => {
+                    return match Manifest::decode(&data) {
+                        Some(manifest) => Ok((stat.version, manifest.chunks)),
+                        None => Err(blob_error(path, "not a blob manifest".to_string())),
+                    };
+                }
+                Err(e) if e.code() == Some(ServerErrorCode::NoNode) => {}
+                Err(e) => return Err(e),
+            }
+            let empty = Manifest::new(&[], Vec::new()).encode();
+            match self
+                .create(
+                    path,
+                    Some(&empty),
+                    self.default_acl(),
+                    CreateMode::Persistent,
+                )
+                .await
+            {
+                Ok(_) => return Ok((0, Vec::new())),
+                // 其他客户端同时创建了清单节点，重新读取
+                Err(e) if e.code() == Some(ServerErrorCode::NodeExists) => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// 尽力删除分块，失败时只记录日志，残留的分块会在 [`ZooKeeper::delete_blob`] 时删除
+    async fn delete_chunks(&self, path: &str, chunks: &[String]) {
+        if chunks.is_empty() {
+            return;
+        }
+        let ops = chunks
+            .iter()
+            .map(|chunk| Op::delete(paths::join(path, chunk), -1))
+            .collect();
+        if let Err(e) = self.multi(ops).await {
+            warn!("Delete chunks of blob `{}` failed : {}", path, e);
+        }
+    }
+
+    /// 读取分块写入的数据，拼接所有分块并校验长度和 SHA-1 校验和
+    /// # Examples
+    /// ```rust,ignore
+    /// let data = zk.get_blob("/config/routes", None).await?;
+    /// ```
+    ///
+    /// # Args
+    /// - `path`： 清单节点路径，必须以 "/" 开头
+    /// - `stat`： 统计数据，可选，如果不为 None 则会将清单节点的统计结果写入该对象, 关于更多统计对象，请查看 [`Stat`]
+    /// # Returns
+    /// - `Vec<u8>`： 完整的数据
+    /// # Errors
+    ///
+    /// 节点不是清单或者校验失败时返回 [`ZKError::DecodeError`]
+    pub async fn get_blob(
+        &self,
+        path: impl AsRef<str>,
+        mut stat: Option<&mut Stat>,
+    ) -> ZKResult<Vec<u8>> {
+        let path = path.as_ref();
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let mut manifest_stat = Stat::default();
+            let data = self.get(path, Some(&mut manifest_stat)).await?;
+            let manifest = Manifest::decode(&data)
+                .ok_or_else(|| blob_error(path, "not a blob manifest".to_string()))?;
+            match self.read_chunks(path, &manifest).await {
+                Ok(blob) => {
+                    if let Some(s) = stat.as_deref_mut() {
+                        *s = manifest_stat;
+                    }
+                    return Ok(blob);
+                }
+                // 读取过程中有新的数据发布，旧的分块已经被删除
+                Err(e) if e.code() == Some(ServerErrorCode::NoNode) && attempt < READ_ATTEMPTS => {
+                    continue
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    async fn read_chunks(&self, path: &str, manifest: &Manifest) -> ZKResult<Vec<u8>> {
+        let mut blob = Vec::with_capacity(manifest.size);
+        for chunk in manifest.chunks.iter() {
+            let chunk_path = paths::join(path, chunk);
+            blob.extend(self.get(chunk_path.as_str(), None).await?);
+        }
+        if blob.len() != manifest.size {
+            return Err(blob_error(
+                path,
+                format!("expect {} bytes but got {}", manifest.size, blob.len()),
+            ));
+        }
+        let sha1 = checksum(&blob);
+        if sha1 != manifest.sha1 {
+            return Err(blob_error(
+                path,
+                format!(
+                    "checksum mismatch, expect {} but got {}",
+                    manifest.sha1, sha1
+                ),
+            ));
+        }
+        Ok(blob)
+    }
+
+    /// 删除清单节点以及下面所有的分块，包括写入失败时残留的分块
+    /// # Examples
+    /// ```rust,ignore
+    /// zk.delete_blob("/config/routes").await?;
+    /// ```
+    ///
+    /// # Args
+    /// - `path`： 清单节点路径，必须以 "/" 开头
+    pub async fn delete_blob(&self, path: impl AsRef<str>) -> ZKResult<()> {
+        let path = path.as_ref();
+        let mut stat = Stat::default();
+        let children = self.childrens(path, &mut stat).await?;
+        let mut ops: Vec<Op> = children
+            .iter()
+            .map(|child| Op::delete(paths::join(path, child), -1))
+            .collect();
+        // 版本条件保证删除期间没有新的数据发布
+        ops.push(Op::delete(path, stat.version));
+        self.multi(ops).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_manifest() {
+        let manifest = Manifest::new(
+            b"hello",
+            vec!["chunk-0000000000".into(), "chunk-0000000001".into()],
+        );
+        let encoded = manifest.encode();
+        assert_eq!(
+            String::from_utf8(encoded.clone()).unwrap(),
+            "repeekooz-blob/1\nsize=5\nsha1=aaf4c61ddcc5e8a2dabede0f3b482cd9aea9434d\n\
+             chunks=chunk-0000000000,chunk-0000000001\n"
+        );
+        assert_eq!(Manifest::decode(&encoded), Some(manifest));
+        assert_eq!(
+            Manifest::decode(&Manifest::new(&[], Vec::new()).encode())
+                .unwrap()
+                .chunks,
+            Vec::<String>::new()
+        );
+        assert_eq!(Manifest::decode(b"plain data"), None);
+    }
+
+    #[test]
+    fn test_batches() {
+        let options = BlobOptions::default().batch_size(10);
+        let chunks: Vec<&[u8]> = vec![b"aaaa", b"bbbb", b"cccc", b"dddddddddddd", b"e"];
+        let batches = options.batches(&chunks);
+        assert_eq!(batches.len(), 4);
+        assert_eq!(batches[0], &chunks[..2]);
+        assert_eq!(batches[1], &chunks[2..3]);
+        // 超过 batch_size 的分块单独一批
+        assert_eq!(batches[2], &chunks[3..4]);
+        assert_eq!(batches[3], &chunks[4..]);
+        assert!(options.batches(&[]).is_empty());
+    }
+}
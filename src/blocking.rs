@@ -0,0 +1,591 @@
+//! # 同步客户端模块
+//! 需要开启 `blocking` feature。[`ZooKeeper`] 在内部持有一个 tokio 运行时，与异步客户端的 API 一一对应，
+//! 适合没有异步运行时的命令行工具和构建脚本，可以在任意非 tokio 线程中使用，clone 后在多个线程之间共享同一个会话
+//!
+//! 运行时使用一个独立的工作线程，在两次调用之间也会继续发送心跳、分发回调通知。
+//! 注意不能在异步上下文（包括 [`Watcher`] 回调）中调用同步方法，否则会 panic，
+//! 回调中需要读写节点时请把事件发送到 channel 中，再由其他线程处理
+//!
+//! ```rust,ignore
+//! use repeekooz::blocking::ZooKeeper;
+//!
+//! let zk = ZooKeeper::new("127.0.0.1:2181", Duration::from_secs(5))?;
+//! zk.create("/app", Some(b"data"), ACL::world_acl(), CreateMode::Persistent)?;
+//! let data = zk.get("/app", None)?;
+//! ```
+
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures_util::stream::{Stream, StreamExt};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tokio::runtime::Runtime;
+
+use crate::acl::PermissionEvaluator;
+use crate::blob::BlobOptions;
+use crate::builder::ZooKeeperBuilder;
+use crate::constants::{AddWatchMode, CreateMode, States, VersionType};
+use crate::protocol::req::{Op, ACL};
+use crate::protocol::resp::{OpResult, Stat};
+use crate::recursive::{DeleteOptions, ParentOptions};
+use crate::retry::{self, RetryPolicy};
+use crate::sequential::SequentialName;
+use crate::state::StateEvent;
+use crate::update::UpdateOptions;
+use crate::walk::{WalkEntry, WalkOptions};
+use crate::watcher::Watcher;
+use crate::{Codec, WatcherType, ZKResult};
+
+/// 所有 clone 出来的客户端共享的运行时，最后一个引用释放时在后台关闭，
+/// 因此在异步上下文中释放同步客户端也不会 panic
+#[derive(Debug)]
+struct BlockingRuntime(Option<Runtime>);
+
+impl BlockingRuntime {
+    fn new() -> ZKResult<BlockingRuntime> {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .thread_name("repeekooz-blocking")
+            .enable_all()
+            .build()?;
+        Ok(BlockingRuntime(Some(runtime)))
+    }
+
+    fn block_on<F: Future>(&self, future: F) -> F::Output {
+        self.0.as_ref().unwrap().block_on(future)
+    }
+}
+
+impl Drop for BlockingRuntime {
+    fn drop(&mut self) {
+        if let Some(runtime) = self.0.take() {
+            runtime.shutdown_background();
+        }
+    }
+}
+
+/// 同步的 ZooKeeper 客户端，每个方法都会阻塞当前线程直到对应的异步方法完成，参考 [`crate::ZooKeeper`]
+#[derive(Debug, Clone)]
+pub struct ZooKeeper {
+    inner: crate::ZooKeeper,
+    runtime: Arc<BlockingRuntime>,
+}
+
+impl ZooKeeperBuilder {
+    /// 创建同步客户端，需要开启 `blocking` feature，参考 [`ZooKeeperBuilder::build`]
+    /// # Examples
+    ///
+    /// ```rust,ignore
+    /// let zk = ZooKeeperBuilder::new("127.0.0.1:2181")
+    ///     .session_timeout(Duration::from_secs(10))
+    ///     .build_blocking()?;
+    /// ```
+    pub fn build_blocking(self) -> ZKResult<ZooKeeper> {
+        let runtime = BlockingRuntime::new()?;
+        let inner = runtime.block_on(self.build())?;
+        Ok(ZooKeeper {
+            inner,
+            runtime: Arc::new(runtime),
+        })
+    }
+}
+
+impl ZooKeeper {
+    /// 创建同步客户端，参考 [`crate::ZooKeeper::new`]
+    pub fn new<'a>(
+        connect_string: impl Into<&'a str>,
+        session_timeout: Duration,
+    ) -> ZKResult<ZooKeeper> {
+        ZooKeeperBuilder::new(connect_string.into())
+            .session_timeout(session_timeout)
+            .build_blocking()
+    }
+
+    /// 获取客户端构造器，通过 [`ZooKeeperBuilder::build_blocking`] 创建同步客户端
+    pub fn builder(connect_string: impl Into<String>) -> ZooKeeperBuilder {
+        ZooKeeperBuilder::new(connect_string)
+    }
+
+    fn block_on<F: Future>(&self, future: F) -> F::Output {
+        self.runtime.block_on(future)
+    }
+
+    /// 参考 [`crate::ZooKeeper::default_acl`]
+    pub fn default_acl(&self) -> Vec<ACL> {
+        self.inner.default_acl()
+    }
+
+    /// 参考 [`crate::ZooKeeper::permission_evaluator`]
+    pub fn permission_evaluator(&self) -> PermissionEvaluator {
+        self.inner.permission_evaluator()
+    }
+
+    /// 参考 [`crate::ZooKeeper::create`]
+    pub fn create(
+        &self,
+        path: impl AsRef<str>,
+        data: Option<&[u8]>,
+        acl_list: Vec<ACL>,
+        create_model: CreateMode,
+    ) -> ZKResult<String> {
+        self.block_on(self.inner.create(path, data, acl_list, create_model))
+    }
+
+    /// 参考 [`crate::ZooKeeper::create_with_retry`]
+    pub fn create_with_retry(
+        &self,
+        path: impl AsRef<str>,
+        data: Option<&[u8]>,
+        acl_list: Vec<ACL>,
+        create_model: CreateMode,
+    ) -> ZKResult<String> {
+        self.block_on(
+            self.inner
+                .create_with_retry(path, data, acl_list, create_model),
+        )
+    }
+
+    /// 参考 [`crate::ZooKeeper::delete`]
+    pub fn delete(&self, path: impl AsRef<str>) -> ZKResult<()> {
+        self.block_on(self.inner.delete(path))
+    }
+
+    /// 参考 [`crate::ZooKeeper::deletev`]
+    pub fn deletev(&self, path: impl AsRef<str>, version: VersionType) -> ZKResult<()> {
+        self.block_on(self.inner.deletev(path, version))
+    }
+
+    /// 参考 [`crate::ZooKeeper::set`]
+    pub fn set(&self, path: impl AsRef<str>, data: &[u8]) -> ZKResult<Stat> {
+        self.block_on(self.inner.set(path, data))
+    }
+
+    /// 参考 [`crate::ZooKeeper::setv`]
+    pub fn setv(&self, path: impl AsRef<str>, data: &[u8], version: VersionType) -> ZKResult<Stat> {
+        self.block_on(self.inner.setv(path, data, version))
+    }
+
+    /// 参考 [`crate::ZooKeeper::get`]
+    pub fn get(&self, path: impl AsRef<str>, stat: Option<&mut Stat>) -> ZKResult<Vec<u8>> {
+        self.block_on(self.inner.get(path, stat))
+    }
+
+    /// 参考 [`crate::ZooKeeper::getw`]，回调在运行时的线程中执行
+    pub fn getw(
+        &self,
+        path: impl AsRef<str>,
+        watcher: Option<impl Watcher + 'static>,
+        stat: Option<&mut Stat>,
+    ) -> ZKResult<Vec<u8>> {
+        self.block_on(self.inner.getw(path, watcher, stat))
+    }
+
+    /// 参考 [`crate::ZooKeeper::create_as`]
+    #[cfg(feature = "json")]
+    pub fn create_as<T: Serialize + ?Sized>(
+        &self,
+        path: impl AsRef<str>,
+        value: &T,
+        acl_list: Vec<ACL>,
+        create_model: CreateMode,
+    ) -> ZKResult<String> {
+        self.block_on(self.inner.create_as(path, value, acl_list, create_model))
+    }
+
+    /// 参考 [`crate::ZooKeeper::create_as_with`]
+    pub fn create_as_with<T: Serialize + ?Sized>(
+        &self,
+        path: impl AsRef<str>,
+        value: &T,
+        acl_list: Vec<ACL>,
+        create_model: CreateMode,
+        codec: &impl Codec,
+    ) -> ZKResult<String> {
+        self.block_on(
+            self.inner
+                .create_as_with(path, value, acl_list, create_model, codec),
+        )
+    }
+
+    /// 参考 [`crate::ZooKeeper::set_as`]
+    #[cfg(feature = "json")]
+    pub fn set_as<T: Serialize + ?Sized>(
+        &self,
+        path: impl AsRef<str>,
+        value: &T,
+    ) -> ZKResult<Stat> {
+        self.block_on(self.inner.set_as(path, value))
+    }
+
+    /// 参考 [`crate::ZooKeeper::set_as_with`]
+    pub fn set_as_with<T: Serialize + ?Sized>(
+        &self,
+        path: impl AsRef<str>,
+        value: &T,
+        codec: &impl Codec,
+    ) -> ZKResult<Stat> {
+        self.block_on(self.inner.set_as_with(path, value, codec))
+    }
+
+    /// 参考 [`crate::ZooKeeper::get_as`]
+    #[cfg(feature = "json")]
+    pub fn get_as<T: DeserializeOwned>(&self, path: impl AsRef<str>) -> ZKResult<T> {
+        self.block_on(self.inner.get_as(path))
+    }
+
+    /// 参考 [`crate::ZooKeeper::get_as_with`]
+    pub fn get_as_with<T: DeserializeOwned>(
+        &self,
+        path: impl AsRef<str>,
+        codec: &impl Codec,
+        stat: Option<&mut Stat>,
+    ) -> ZKResult<T> {
+        self.block_on(self.inner.get_as_with(path, codec, stat))
+    }
+
+    /// 参考 [`crate::ZooKeeper::multi`]
+    pub fn multi(&self, ops: Vec<Op>) -> ZKResult<Vec<OpResult>> {
+        self.block_on(self.inner.multi(ops))
+    }
+
+    /// 参考 [`crate::ZooKeeper::exists`]
+    pub fn exists(&self, path: impl AsRef<str>) -> ZKResult<Option<Stat>> {
+        self.block_on(self.inner.exists(path))
+    }
+
+    /// 参考 [`crate::ZooKeeper::existsw`]，回调在运行时的线程中执行
+    pub fn existsw(
+        &self,
+        path: impl AsRef<str>,
+        watcher: Option<impl Watcher + 'static>,
+    ) -> ZKResult<Option<Stat>> {
+        self.block_on(self.inner.existsw(path, watcher))
+    }
+
+    /// 参考 [`crate::ZooKeeper::children`]
+    pub fn children(&self, path: impl AsRef<str>) -> ZKResult<Vec<String>> {
+        self.block_on(self.inner.children(path))
+    }
+
+    /// 参考 [`crate::ZooKeeper::childrenw`]，回调在运行时的线程中执行
+    pub fn childrenw(
+        &self,
+        path: impl AsRef<str>,
+        watcher: Option<impl Watcher + 'static>,
+    ) -> ZKResult<Vec<String>> {
+        self.block_on(self.inner.childrenw(path, watcher))
+    }
+
+    /// 参考 [`crate::ZooKeeper::childrens`]
+    pub fn childrens(&self, path: impl AsRef<str>, stat: &mut Stat) -> ZKResult<Vec<String>> {
+        self.block_on(self.inner.childrens(path, stat))
+    }
+
+    /// 参考 [`crate::ZooKeeper::childrensw`]，回调在运行时的线程中执行
+    pub fn childrensw(
+        &self,
+        path: impl AsRef<str>,
+        watcher: Option<impl Watcher + 'static>,
+        stat: &mut Stat,
+    ) -> ZKResult<Vec<String>> {
+        self.block_on(self.inner.childrensw(path, watcher, stat))
+    }
+
+    /// 参考 [`crate::ZooKeeper::children_count`]
+    pub fn children_count(&self, path: impl AsRef<str>) -> ZKResult<u32> {
+        self.block_on(self.inner.children_count(path))
+    }
+
+    /// 参考 [`crate::ZooKeeper::get_ephemerals`]
+    pub fn get_ephemerals(&self, path: impl AsRef<str>) -> ZKResult<Vec<String>> {
+        self.block_on(self.inner.get_ephemerals(path))
+    }
+
+    /// 参考 [`crate::ZooKeeper::get_acl`]
+    pub fn get_acl(&self, path: impl AsRef<str>, stat: Option<&mut Stat>) -> ZKResult<Vec<ACL>> {
+        self.block_on(self.inner.get_acl(path, stat))
+    }
+
+    /// 参考 [`crate::ZooKeeper::set_acl`]
+    pub fn set_acl(
+        &self,
+        path: impl AsRef<str>,
+        acl_list: Vec<ACL>,
+        version: i32,
+    ) -> ZKResult<Stat> {
+        self.block_on(self.inner.set_acl(path, acl_list, version))
+    }
+
+    /// 参考 [`crate::ZooKeeper::add_watch`]，回调在运行时的线程中执行
+    pub fn add_watch<W: Watcher + 'static>(
+        &self,
+        path: impl AsRef<str>,
+        watcher: W,
+        mode: AddWatchMode,
+    ) -> ZKResult<()> {
+        self.block_on(self.inner.add_watch(path, watcher, mode))
+    }
+
+    /// 参考 [`crate::ZooKeeper::remove_watches`]
+    pub fn remove_watches<W: Watcher + 'static>(
+        &self,
+        path: impl AsRef<str>,
+        watcher: W,
+        watcher_type: WatcherType,
+        local: bool,
+    ) -> ZKResult<()> {
+        self.block_on(
+            self.inner
+                .remove_watches(path, watcher, watcher_type, local),
+        )
+    }
+
+    /// 参考 [`crate::ZooKeeper::state`]
+    pub fn state(&self) -> ZKResult<States> {
+        self.inner.state()
+    }
+
+    /// 参考 [`crate::ZooKeeper::session_id`]
+    pub fn session_id(&self) -> ZKResult<i64> {
+        self.inner.session_id()
+    }
+
+    /// 参考 [`crate::ZooKeeper::session_timeout`]
+    pub fn session_timeout(&self) -> ZKResult<u32> {
+        self.inner.session_timeout()
+    }
+
+    /// 订阅客户端连接状态的变化，参考 [`crate::ZooKeeper::subscribe_state`]
+    /// # Examples
+    /// ```rust,ignore
+    /// let mut listener = zk.subscribe_state();
+    /// std::thread::spawn(move || {
+    ///     while let Some(event) = listener.recv() {
+    ///         println!("{:?}", event.state);
+    ///     }
+    /// });
+    /// ```
+    pub fn subscribe_state(&self) -> StateListener {
+        StateListener {
+            inner: self.inner.subscribe_state(),
+            runtime: self.runtime.clone(),
+        }
+    }
+
+    /// 参考 [`crate::ZooKeeper::wait_until_connected`]
+    pub fn wait_until_connected(&self, timeout: Duration) -> ZKResult<()> {
+        self.block_on(self.inner.wait_until_connected(timeout))
+    }
+
+    /// 按照客户端的重试策略执行操作，参考 [`crate::ZooKeeper::retry`]
+    /// # Examples
+    /// ```rust,ignore
+    /// let data = zk.retry(|zk| zk.get("/your/path", None))?;
+    /// ```
+    pub fn retry<T, F>(&self, operation: F) -> ZKResult<T>
+    where
+        F: FnMut(ZooKeeper) -> ZKResult<T>,
+    {
+        let policy = self.inner.client.retry_policy();
+        self.retry_with(policy.as_ref(), operation)
+    }
+
+    /// 按照指定的重试策略执行操作，参考 [`crate::ZooKeeper::retry_with`]
+    pub fn retry_with<T, F>(&self, policy: &dyn RetryPolicy, mut operation: F) -> ZKResult<T>
+    where
+        F: FnMut(ZooKeeper) -> ZKResult<T>,
+    {
+        self.block_on(retry::run(policy, || {
+            // 操作内部会再次调用 block_on，需要先让出当前的运行时上下文
+            let result = tokio::task::block_in_place(|| operation(self.clone()));
+            async move { result }
+        }))
+    }
+
+    /// 参考 [`crate::ZooKeeper::add_auth`]
+    pub fn add_auth(&self, scheme: impl Into<String>, auth: impl Into<Vec<u8>>) -> ZKResult<()> {
+        self.block_on(self.inner.add_auth(scheme, auth))
+    }
+
+    /// 参考 [`crate::ZooKeeper::close`]
+    pub fn close(&self) -> ZKResult<()> {
+        self.block_on(self.inner.close())
+    }
+
+    /// 参考 [`crate::ZooKeeper::put_blob`]
+    pub fn put_blob(&self, path: impl AsRef<str>, data: &[u8]) -> ZKResult<Stat> {
+        self.block_on(self.inner.put_blob(path, data))
+    }
+
+    /// 参考 [`crate::ZooKeeper::put_blob_with`]
+    pub fn put_blob_with(
+        &self,
+        path: impl AsRef<str>,
+        data: &[u8],
+        options: &BlobOptions,
+    ) -> ZKResult<Stat> {
+        self.block_on(self.inner.put_blob_with(path, data, options))
+    }
+
+    /// 参考 [`crate::ZooKeeper::get_blob`]
+    pub fn get_blob(&self, path: impl AsRef<str>, stat: Option<&mut Stat>) -> ZKResult<Vec<u8>> {
+        self.block_on(self.inner.get_blob(path, stat))
+    }
+
+    /// 参考 [`crate::ZooKeeper::delete_blob`]
+    pub fn delete_blob(&self, path: impl AsRef<str>) -> ZKResult<()> {
+        self.block_on(self.inner.delete_blob(path))
+    }
+
+    /// 参考 [`crate::ZooKeeper::create_all`]
+    pub fn create_all(
+        &self,
+        path: impl AsRef<str>,
+        data: Option<&[u8]>,
+        acl_list: Vec<ACL>,
+        create_model: CreateMode,
+    ) -> ZKResult<String> {
+        self.block_on(self.inner.create_all(path, data, acl_list, create_model))
+    }
+
+    /// 参考 [`crate::ZooKeeper::create_all_with`]
+    pub fn create_all_with(
+        &self,
+        path: impl AsRef<str>,
+        data: Option<&[u8]>,
+        acl_list: Vec<ACL>,
+        create_model: CreateMode,
+        parents: &ParentOptions,
+    ) -> ZKResult<String> {
+        self.block_on(
+            self.inner
+                .create_all_with(path, data, acl_list, create_model, parents),
+        )
+    }
+
+    /// 参考 [`crate::ZooKeeper::delete_all`]
+    pub fn delete_all(&self, path: impl AsRef<str>) -> ZKResult<Vec<String>> {
+        self.block_on(self.inner.delete_all(path))
+    }
+
+    /// 参考 [`crate::ZooKeeper::delete_all_with`]
+    pub fn delete_all_with(
+        &self,
+        path: impl AsRef<str>,
+        options: &DeleteOptions,
+    ) -> ZKResult<Vec<String>> {
+        self.block_on(self.inner.delete_all_with(path, options))
+    }
+
+    /// 参考 [`crate::ZooKeeper::create_or_set`]
+    pub fn create_or_set(&self, path: impl AsRef<str>, data: &[u8]) -> ZKResult<Stat> {
+        self.block_on(self.inner.create_or_set(path, data))
+    }
+
+    /// 参考 [`crate::ZooKeeper::create_or_set_with`]
+    pub fn create_or_set_with(
+        &self,
+        path: impl AsRef<str>,
+        data: &[u8],
+        options: &UpdateOptions,
+    ) -> ZKResult<Stat> {
+        self.block_on(self.inner.create_or_set_with(path, data, options))
+    }
+
+    /// 参考 [`crate::ZooKeeper::create_if_absent`]
+    pub fn create_if_absent(&self, path: impl AsRef<str>, data: &[u8]) -> ZKResult<bool> {
+        self.block_on(self.inner.create_if_absent(path, data))
+    }
+
+    /// 参考 [`crate::ZooKeeper::create_if_absent_with`]
+    pub fn create_if_absent_with(
+        &self,
+        path: impl AsRef<str>,
+        data: &[u8],
+        options: &UpdateOptions,
+    ) -> ZKResult<bool> {
+        self.block_on(self.inner.create_if_absent_with(path, data, options))
+    }
+
+    /// 参考 [`crate::ZooKeeper::update`]
+    pub fn update<F>(&self, path: impl AsRef<str>, f: F) -> ZKResult<Option<Stat>>
+    where
+        F: FnMut(Option<&[u8]>) -> Option<Vec<u8>>,
+    {
+        self.block_on(self.inner.update(path, f))
+    }
+
+    /// 参考 [`crate::ZooKeeper::update_with`]
+    pub fn update_with<F>(
+        &self,
+        path: impl AsRef<str>,
+        options: &UpdateOptions,
+        f: F,
+    ) -> ZKResult<Option<Stat>>
+    where
+        F: FnMut(Option<&[u8]>) -> Option<Vec<u8>>,
+    {
+        self.block_on(self.inner.update_with(path, options, f))
+    }
+
+    /// 使用默认配置广度优先遍历子树，参考 [`crate::ZooKeeper::walk`]
+    /// # Examples
+    /// ```rust,ignore
+    /// for entry in zk.walk("/app") {
+    ///     let entry = entry?;
+    ///     println!("{} {}", entry.path, entry.stat.num_children);
+    /// }
+    /// ```
+    pub fn walk(&self, path: impl AsRef<str>) -> Walk {
+        self.walk_with(path, WalkOptions::default())
+    }
+
+    /// 遍历以 `path` 为根的子树，参考 [`crate::ZooKeeper::walk_with`]
+    pub fn walk_with(&self, path: impl AsRef<str>, options: WalkOptions) -> Walk {
+        // 返回的 Stream 会捕获路径参数的类型，转换为 String 后才能满足 'static
+        let path = path.as_ref().to_string();
+        Walk {
+            stream: Box::pin(self.inner.walk_with(path, options)),
+            runtime: self.runtime.clone(),
+        }
+    }
+
+    /// 参考 [`crate::ZooKeeper::sequential_children`]
+    pub fn sequential_children(
+        &self,
+        path: impl AsRef<str>,
+        prefix: &str,
+    ) -> ZKResult<Vec<SequentialName>> {
+        self.block_on(self.inner.sequential_children(path, prefix))
+    }
+}
+
+/// 同步的状态变化订阅者，参考 [`crate::StateListener`]
+#[derive(Debug)]
+pub struct StateListener {
+    inner: crate::StateListener,
+    runtime: Arc<BlockingRuntime>,
+}
+
+impl StateListener {
+    /// 阻塞等待下一次状态变化，客户端被释放后返回 `None`
+    pub fn recv(&mut self) -> Option<StateEvent> {
+        self.runtime.block_on(self.inner.recv())
+    }
+}
+
+/// 子树遍历的迭代器，通过 [`ZooKeeper::walk`] 获取，按照遍历顺序输出节点
+pub struct Walk {
+    stream: std::pin::Pin<Box<dyn Stream<Item = ZKResult<WalkEntry>> + Send>>,
+    runtime: Arc<BlockingRuntime>,
+}
+
+impl Iterator for Walk {
+    type Item = ZKResult<WalkEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.runtime.block_on(self.stream.next())
+    }
+}
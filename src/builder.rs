@@ -0,0 +1,225 @@
+//! # 客户端构造器模块
+//! 通过 [`ZooKeeperBuilder`] 收集客户端的所有配置项，再创建 [`ZooKeeper`] 客户端
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::client::Client;
+use crate::compress::Compression;
+use crate::host::{self, HostProvider, StaticHostProvider};
+use crate::retry::{ExponentialBackoffRetry, RetryPolicy};
+#[cfg(feature = "tls")]
+use crate::TlsConfig;
+use crate::{SaslConfig, Watcher, ZKResult, ZooKeeper, ACL};
+
+/// 鉴权信息，建立连接后会自动发送给服务端
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub(crate) struct AuthInfo {
+    pub scheme: String,
+    pub auth: Vec<u8>,
+}
+
+/// 客户端的所有配置项
+#[derive(Debug, Clone)]
+pub(crate) struct ClientConfig {
+    pub session_timeout: Duration,
+    pub connection_timeout: Option<Duration>,
+    pub request_timeout: Option<Duration>,
+    pub read_only: bool,
+    pub auth_info: Vec<AuthInfo>,
+    pub sasl: Option<SaslConfig>,
+    #[cfg(feature = "tls")]
+    pub tls: Option<TlsConfig>,
+    pub default_acl: Vec<ACL>,
+    pub packet_capacity: usize,
+    pub event_capacity: usize,
+    pub state_capacity: usize,
+    pub auto_watch_reset: bool,
+    pub retry_policy: Arc<dyn RetryPolicy>,
+    pub compression: Option<Compression>,
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        ClientConfig {
+            session_timeout: Duration::from_secs(30),
+            connection_timeout: None,
+            request_timeout: None,
+            read_only: false,
+            auth_info: Vec::new(),
+            sasl: None,
+            #[cfg(feature = "tls")]
+            tls: None,
+            default_acl: ACL::world_acl(),
+            packet_capacity: 2017,
+            event_capacity: 2017,
+            state_capacity: 1002,
+            auto_watch_reset: true,
+            retry_policy: Arc::new(
+                ExponentialBackoffRetry::new(Duration::from_millis(100), 3)
+                    .max_sleep(Duration::from_secs(1)),
+            ),
+            compression: None,
+        }
+    }
+}
+
+/// ZooKeeper 客户端构造器
+/// # Examples
+///
+/// ```rust,ignore
+/// let zk = ZooKeeperBuilder::new("127.0.0.1:2181/chroot")
+///     .session_timeout(Duration::from_secs(10))
+///     .request_timeout(Duration::from_secs(3))
+///     .auth("digest", "user:password")
+///     .build()
+///     .await?;
+/// ```
+#[derive(Debug)]
+pub struct ZooKeeperBuilder {
+    connect_string: String,
+    config: ClientConfig,
+    host_provider: Option<Box<dyn HostProvider>>,
+    default_watcher: Option<Box<dyn Watcher>>,
+}
+
+impl ZooKeeperBuilder {
+    /// 创建构造器
+    /// # Args
+    /// - `connect_string`: 连接字符串格式为 "host1:port1,host2:port2,host3:port3.../chroot"，其中 chroot 为可选，
+    ///   host 可以是 IPv4 地址、主机名或者 "[ipv6]"，
+    ///   设置了 [`ZooKeeperBuilder::host_provider`] 时只会使用其中的 chroot
+    pub fn new(connect_string: impl Into<String>) -> ZooKeeperBuilder {
+        ZooKeeperBuilder {
+            connect_string: connect_string.into(),
+            config: ClientConfig::default(),
+            host_provider: None,
+            default_watcher: None,
+        }
+    }
+
+    /// 会话超时时间，默认 30 秒，最终以服务端协商的结果为准
+    pub fn session_timeout(mut self, session_timeout: Duration) -> Self {
+        self.config.session_timeout = session_timeout;
+        self
+    }
+
+    /// 连接单个服务端的超时时间，默认为会话超时时间除以服务端数量
+    pub fn connection_timeout(mut self, connection_timeout: Duration) -> Self {
+        self.config.connection_timeout = Some(connection_timeout);
+        self
+    }
+
+    /// 单个请求等待响应的超时时间，默认不超时。超时的请求返回 [`crate::ServerErrorCode::RequestTimeout`]，
+    /// 连接不会断开，可以按照重试策略重试
+    pub fn request_timeout(mut self, request_timeout: Duration) -> Self {
+        self.config.request_timeout = Some(request_timeout);
+        self
+    }
+
+    /// 是否允许连接到只读模式的服务端，默认不允许
+    pub fn read_only(mut self, read_only: bool) -> Self {
+        self.config.read_only = read_only;
+        self
+    }
+
+    /// 添加鉴权信息，每次建立连接后都会自动发送给服务端，可以多次调用
+    /// # Args
+    /// - `scheme`： 鉴权模式，例如 "digest"
+    /// - `auth`： 鉴权数据，例如 "user:password"
+    pub fn auth(mut self, scheme: impl Into<String>, auth: impl Into<Vec<u8>>) -> Self {
+        self.config.auth_info.push(AuthInfo {
+            scheme: scheme.into(),
+            auth: auth.into(),
+        });
+        self
+    }
+
+    /// 开启 SASL 鉴权，每次建立连接后都会先完成 SASL 交换再发送其他请求
+    ///
+    /// 鉴权成功时通过 [`ZooKeeper::subscribe_state`] 通知 [`crate::KeeperState::SaslAuthenticated`]，
+    /// 失败时客户端进入 [`crate::ConnectionState::AuthFailed`] 状态
+    pub fn sasl(mut self, sasl: SaslConfig) -> Self {
+        self.config.sasl = Some(sasl);
+        self
+    }
+
+    /// 使用 TLS 连接服务端的 `secureClientPort`，需要开启 `tls` feature
+    #[cfg(feature = "tls")]
+    pub fn tls(mut self, tls: TlsConfig) -> Self {
+        self.config.tls = Some(tls);
+        self
+    }
+
+    /// 默认的 ACL 列表，默认为 [`ACL::world_acl`]，可以通过 [`ZooKeeper::default_acl`] 获取
+    pub fn default_acl(mut self, acl_list: Vec<ACL>) -> Self {
+        self.config.default_acl = acl_list;
+        self
+    }
+
+    /// 待发送请求的队列长度，默认 2017
+    pub fn packet_channel_capacity(mut self, capacity: usize) -> Self {
+        self.config.packet_capacity = capacity;
+        self
+    }
+
+    /// 待处理回调事件的队列长度，默认 2017
+    pub fn event_channel_capacity(mut self, capacity: usize) -> Self {
+        self.config.event_capacity = capacity;
+        self
+    }
+
+    /// 每个状态订阅者最多缓存的状态变化数量，默认 1002
+    pub fn state_channel_capacity(mut self, capacity: usize) -> Self {
+        self.config.state_capacity = capacity;
+        self
+    }
+
+    /// 重连后是否自动向服务端重新注册回调，默认开启
+    pub fn auto_watch_reset(mut self, auto_watch_reset: bool) -> Self {
+        self.config.auto_watch_reset = auto_watch_reset;
+        self
+    }
+
+    /// [`ZooKeeper::retry`] 等方法使用的重试策略，默认为基础等待 100 毫秒、最多重试 3 次、
+    /// 每次最多等待 1 秒的 [`ExponentialBackoffRetry`]
+    pub fn retry_policy(mut self, retry_policy: impl RetryPolicy + 'static) -> Self {
+        self.config.retry_policy = Arc::new(retry_policy);
+        self
+    }
+
+    /// 开启节点数据压缩，`create`、`set` 时压缩数据，参考 [`Compression`]。`get` 总是根据头部自动解压，与该配置无关。
+    /// 需要开启 `gzip` 或者 `zstd` feature
+    pub fn compression(mut self, compression: Compression) -> Self {
+        self.config.compression = Some(compression);
+        self
+    }
+
+    /// 自定义服务端地址提供者，参考 [`HostProvider`]
+    pub fn host_provider(mut self, host_provider: impl HostProvider + 'static) -> Self {
+        self.host_provider = Some(Box::new(host_provider));
+        self
+    }
+
+    /// 默认回调，所有的连接状态变化都会通知该回调
+    pub fn default_watcher(mut self, watcher: impl Watcher + 'static) -> Self {
+        self.default_watcher = Some(Box::new(watcher));
+        self
+    }
+
+    /// 创建 ZooKeeper 客户端，第一次连接成功后才会返回
+    /// # Errors
+    ///
+    /// 无法连接服务端或者连接字符串格式有问题将会返回异常
+    pub async fn build(self) -> ZKResult<ZooKeeper> {
+        let (host_provider, chroot): (Box<dyn HostProvider>, String) = match self.host_provider {
+            Some(host_provider) => (host_provider, host::split_chroot(&self.connect_string)?.1),
+            None => {
+                let (server_list, chroot) = host::parse_connect_string(&self.connect_string)?;
+                (Box::new(StaticHostProvider::new(server_list)?), chroot)
+            }
+        };
+        let client = Client::new(self.config, host_provider, chroot, self.default_watcher).await?;
+        Ok(ZooKeeper { client })
+    }
+}
@@ -1,85 +1,128 @@
 extern crate chrono;
 
+use std::collections::{HashMap, VecDeque};
 use std::sync::atomic::AtomicI32;
 use std::sync::atomic::Ordering::Release;
 use std::sync::{Arc, Mutex};
 
-use bytes::{Buf, BufMut, BytesMut};
+use bytes::{BufMut, BytesMut};
 use chrono::prelude::*;
-use futures_timer::Delay;
-use tokio::io::{self, AsyncReadExt, AsyncWriteExt, ReadHalf, WriteHalf};
-use tokio::net::TcpStream;
+use tokio::io::{self, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 use tokio::sync::mpsc;
 use tokio::sync::mpsc::{Receiver, Sender};
-use tokio::time::Duration;
-
-use crate::constants::{OpCode, States, XidType};
-use crate::error::{ServerErrorCode, ServerInfo};
+use tokio::sync::oneshot;
+use tokio::time::{self, Duration, Instant};
+
+use crate::acl::PermissionEvaluator;
+use crate::builder::{AuthInfo, ClientConfig};
+use crate::compress;
+use crate::constants::{EventType, KeeperState, OpCode, States, XidType};
+use crate::error::ServerErrorCode;
+use crate::host::HostProvider;
 use crate::metric::Metrics;
-use crate::protocol::req::{ConnectRequest, ReqPacket, RequestHeader, DEATH_PTYPE};
-use crate::protocol::resp::{ConnectResponse, ReplyHeader, WatcherEvent};
+use crate::protocol::req::{
+    AuthPacket, ConnectRequest, GetSASLRequest, ReqPacket, RequestHeader, ACL, DEATH_PTYPE,
+};
+use crate::protocol::resp::{
+    ConnectResponse, IgnoreResponse, ReplyHeader, SetSASLResponse, WatcherEvent,
+};
 use crate::protocol::{Deserializer, Serializer};
+use crate::retry::RetryPolicy;
+use crate::sasl::{DigestMd5Client, SaslConfig};
+use crate::state::{ConnectionState, StateListener, StateManager};
+use crate::transport::{BoxedStream, Connector};
 use crate::watcher::WatcherManager;
-use crate::{WatchedEvent, Watcher, ZKError, ZKResult};
+use crate::{Scheme, WatchedEvent, Watcher, ZKError, ZKResult};
 
-struct SenderTask {
-    packet_rx: Receiver<ReqPacket>,
-    writer: WriteHalf<TcpStream>,
-    #[allow(unused)]
+/// 等待服务端响应的请求，key 为请求的 xid
+type PendingMap = Arc<Mutex<HashMap<i32, oneshot::Sender<(ReplyHeader, BytesMut)>>>>;
+
+/// 等待服务端响应的鉴权请求，服务端按顺序响应，重连时自动发送的鉴权请求没有等待者
+type AuthQueue = Arc<Mutex<VecDeque<Option<oneshot::Sender<i32>>>>>;
+
+/// 从连接中读取一个完整的数据包，去掉开头的长度字段
+async fn read_frame<R: AsyncRead + Unpin + ?Sized>(reader: &mut R) -> ZKResult<BytesMut> {
+    let len = reader.read_i32().await?;
+    if len < 0 {
+        return Err(ZKError::ProtocolParseError);
+    }
+    let mut buf = BytesMut::zeroed(len as usize);
+    reader.read_exact(&mut buf).await?;
+    Ok(buf)
+}
+
+struct SenderTask<'a, W> {
+    packet_rx: &'a mut Receiver<ReqPacket>,
+    writer: W,
     metrics: Arc<Mutex<Metrics>>,
+    ping_interval: Duration,
 }
 
-impl SenderTask {
+impl<'a, W: AsyncWrite + Unpin> SenderTask<'a, W> {
+    fn create_ping_request(&self) -> ReqPacket {
+        ReqPacket::new(
+            Some(RequestHeader::new_full(XidType::Ping.into(), OpCode::Ping)),
+            None,
+        )
+    }
+
+    fn next_ping(&self) -> Duration {
+        let idle_time =
+            Local::now().timestamp_millis() - self.metrics.lock().unwrap().last_send_timestamp;
+        self.ping_interval
+            .saturating_sub(Duration::from_millis(idle_time.max(0) as u64))
+    }
+
+    async fn write_packet(&mut self, packet: ReqPacket) -> ZKResult<()> {
+        let mut buf = BytesMut::new();
+        if let Some(rh) = packet.rh {
+            rh.write(&mut buf)?;
+        }
+        if let Some(req) = packet.req {
+            buf.extend(req);
+        }
+        // write_buf 只写入一次，大的数据包需要多次写入
+        self.writer.write_all(&Client::wrap_len_buf(buf)).await?;
+        self.writer.flush().await?;
+        self.metrics.lock().unwrap().send_done();
+        Ok(())
+    }
+
+    /// 正常退出（收到 DEATH 请求或者客户端已经被释放）时返回 `Ok`，连接异常时返回 `Err`
     pub(self) async fn run(&mut self) -> ZKResult<()> {
         loop {
-            let packet = match self.packet_rx.recv().await {
-                Some(packet) if packet.ptype != DEATH_PTYPE => packet,
-                Some(_death) => {
-                    info!("Received DEATH REQ quit!");
-                    return Ok(());
-                }
-                None => continue,
+            let next_ping = self.next_ping();
+            let packet = tokio::select! {
+                packet = self.packet_rx.recv() => match packet {
+                    Some(packet) if packet.ptype != DEATH_PTYPE => packet,
+                    _ => {
+                        info!("Received DEATH REQ quit!");
+                        return Ok(());
+                    }
+                },
+                _ = time::sleep(next_ping) => self.create_ping_request(),
             };
-            let mut buf = BytesMut::new();
-            if let Some(rh) = packet.rh {
-                rh.write(&mut buf)?;
-            }
-            if let Some(req) = packet.req {
-                buf.extend(req);
-            }
-            self.writer
-                .write_buf(&mut Client::wrap_len_buf(buf))
-                .await?;
-            self.writer.flush().await?;
+            self.write_packet(packet).await?;
         }
     }
 }
 
-struct ReceiverTask {
-    buf_tx: Sender<(ReplyHeader, BytesMut)>,
-    reader: ReadHalf<TcpStream>,
+struct ReceiverTask<R> {
+    reader: R,
     event_tx: Sender<WatchedEvent>,
-    // FIXME 用这个字段区分是否连接，是否可靠，之后的重连怎么处理
-    is_connected: bool,
+    pending: PendingMap,
+    auth_queue: AuthQueue,
+    session: Arc<Mutex<Session>>,
+    read_timeout: Duration,
 }
 
-impl ReceiverTask {
-    async fn read_origin_buf(&mut self) -> BytesMut {
-        // FIXME 1024 够吗
-        let mut buf = BytesMut::with_capacity(1024);
-        while let Ok(buf_size) = self.reader.read_buf(&mut buf).await {
-            if buf_size > 0 {
-                // skip first size
-                let len = buf.get_i32();
-                let bytes_mut = buf.split_to(len as usize);
-                return bytes_mut;
-            }
-        }
-        buf
-    }
-
-    async fn handle_reply(&self, mut reply_header: ReplyHeader, mut buf: BytesMut) -> ZKResult<()> {
+impl<R: AsyncRead + Unpin> ReceiverTask<R> {
+    async fn handle_reply(&self, mut buf: BytesMut) -> ZKResult<()> {
+        let mut reply_header = ReplyHeader::default();
         reply_header.read(&mut buf)?;
+        if reply_header.zxid > 0 {
+            self.session.lock().unwrap().last_zxid = reply_header.zxid;
+        }
         // 区分不同的 xid
         match XidType::from(reply_header.xid) {
             XidType::Notification => {
@@ -90,11 +133,28 @@ impl ReceiverTask {
             XidType::Ping => {
                 trace!("Received Ping from server");
             }
-            XidType::AuthPacket => {}
+            XidType::AuthPacket => {
+                if let Some(Some(tx)) = self.auth_queue.lock().unwrap().pop_front() {
+                    let _ = tx.send(reply_header.err);
+                }
+                if reply_header.err != 0 {
+                    // 鉴权失败后服务端会主动断开连接
+                    return Err(ZKError::ServerError(
+                        ServerErrorCode::from(reply_header.err),
+                        reply_header.err,
+                    ));
+                }
+            }
             XidType::SetWatches => {}
             XidType::Response => {
                 trace!("Received Response from server");
-                self.buf_tx.send((reply_header, buf)).await?;
+                match self.pending.lock().unwrap().remove(&reply_header.xid) {
+                    Some(tx) => {
+                        // 请求方已经放弃等待时发送会失败，忽略即可
+                        let _ = tx.send((reply_header, buf));
+                    }
+                    None => warn!("Received response with unknown xid {}", reply_header.xid),
+                }
             }
         }
         Ok(())
@@ -102,16 +162,16 @@ impl ReceiverTask {
 
     pub(self) async fn run(&mut self) -> ZKResult<()> {
         loop {
-            let buf = self.read_origin_buf().await;
-            let reply_header = ReplyHeader::default();
-
-            if !self.is_connected {
-                // connect response 没有 ReplyHeader
-                self.buf_tx.send((reply_header, buf)).await?;
-                self.is_connected = true;
-            } else {
-                self.handle_reply(reply_header, buf).await?;
-            }
+            let buf = match time::timeout(self.read_timeout, read_frame(&mut self.reader)).await {
+                Ok(buf) => buf?,
+                Err(_) => {
+                    return Err(ZKError::NetworkError(format!(
+                        "No response from server in {:?}",
+                        self.read_timeout
+                    )))
+                }
+            };
+            self.handle_reply(buf).await?;
         }
     }
 }
@@ -129,154 +189,449 @@ impl EventTask {
     }
 
     pub(self) async fn run(&mut self) -> Result<(), io::Error> {
-        loop {
-            let event = match self.event_rx.recv().await {
-                Some(event) => event,
-                None => continue,
-            };
+        while let Some(event) = self.event_rx.recv().await {
             let watchers = self.watcher_manager.find_need_triggered_watchers(&event);
             self.process_event(event, watchers).await;
         }
+        Ok(())
     }
 }
 
-struct PingTask {
-    packet_tx: Sender<ReqPacket>,
+/// 会话信息，重连时需要携带
+#[derive(Debug, Default)]
+pub(crate) struct Session {
+    pub session_id: i64,
+    pub password: Option<Vec<u8>>,
+    pub session_timeout: u32,
+    pub last_zxid: i64,
+}
+
+/// 负责建立连接、握手以及断线重连，每一个连接上都会运行一组 Sender/Receiver task
+struct ConnectionTask {
+    host_provider: Box<dyn HostProvider>,
+    connector: Connector,
+    connect_timeout: Duration,
+    read_only: bool,
+    auth_info: Arc<Mutex<Vec<AuthInfo>>>,
+    sasl: Option<SaslConfig>,
+    xid: Arc<AtomicI32>,
+    watcher_manager: Arc<WatcherManager>,
+    packet_rx: Receiver<ReqPacket>,
+    event_tx: Sender<WatchedEvent>,
+    pending: PendingMap,
+    auth_queue: AuthQueue,
+    session: Arc<Mutex<Session>>,
+    state_manager: Arc<StateManager>,
     metrics: Arc<Mutex<Metrics>>,
-    read_timeout: i64,
 }
 
-impl PingTask {
-    fn create_ping_request(&self) -> ReqPacket {
-        ReqPacket::new(
-            Some(RequestHeader::new_full(XidType::Ping.into(), OpCode::Ping)),
-            None,
-        )
+impl ConnectionTask {
+    async fn handshake(&self, host: &str) -> ZKResult<(BoxedStream, ConnectResponse)> {
+        let mut socket =
+            match time::timeout(self.connect_timeout, self.connector.connect(host)).await {
+                Ok(Ok(socket)) => socket,
+                Ok(Err(e)) => return Err(e),
+                Err(_) => {
+                    return Err(ZKError::NetworkError(
+                        "Connect to ZooKeeper server timeout!".into(),
+                    ))
+                }
+            };
+        let mut buf = BytesMut::new();
+        let connect_request = {
+            let session = self.session.lock().unwrap();
+            ConnectRequest::new_full(
+                session.last_zxid,
+                session.session_timeout,
+                session.session_id,
+                session.password.clone(),
+                self.read_only,
+            )
+        };
+        connect_request.write(&mut buf)?;
+        socket.write_all(&Client::wrap_len_buf(buf)).await?;
+        socket.flush().await?;
+        // connect response 没有 ReplyHeader
+        let mut buf = match time::timeout(self.connect_timeout, read_frame(&mut socket)).await {
+            Ok(buf) => buf?,
+            Err(_) => {
+                return Err(ZKError::NetworkError(
+                    "Handshake with ZooKeeper server timeout!".into(),
+                ))
+            }
+        };
+        let mut response = ConnectResponse::default();
+        response.read(&mut buf)?;
+        Ok((socket, response))
     }
 
-    pub(self) async fn run(&mut self) -> ZKResult<()> {
-        let max_idle = 10000;
+    /// 在发送其他请求之前完成 SASL 交换
+    async fn sasl_authenticate(&self, socket: &mut BoxedStream, sasl: &SaslConfig) -> ZKResult<()> {
+        let mut client = DigestMd5Client::new(sasl.clone());
+        let mut token = client.initial_response();
         loop {
-            let idle_time =
-                Local::now().timestamp_millis() - self.metrics.lock().unwrap().last_send_timestamp;
-            let next_ping = if idle_time > 1000 {
-                self.read_timeout / 2 - idle_time - 1000
-            } else {
-                self.read_timeout / 2 - idle_time
+            let xid = self.xid.fetch_add(1, Release);
+            let mut packet = BytesMut::new();
+            RequestHeader::new_full(xid, OpCode::Sasl).write(&mut packet)?;
+            GetSASLRequest::new(token).write(&mut packet)?;
+            socket.write_all(&Client::wrap_len_buf(packet)).await?;
+            socket.flush().await?;
+            let (reply_header, mut buf) = loop {
+                let mut buf = match time::timeout(self.connect_timeout, read_frame(socket)).await {
+                    Ok(buf) => buf?,
+                    Err(_) => {
+                        return Err(ZKError::NetworkError(
+                            "SASL authentication with ZooKeeper server timeout!".into(),
+                        ))
+                    }
+                };
+                let mut reply_header = ReplyHeader::default();
+                reply_header.read(&mut buf)?;
+                if reply_header.xid == xid {
+                    break (reply_header, buf);
+                }
+                trace!(
+                    "Ignore reply {} during SASL authentication",
+                    reply_header.xid
+                );
             };
-            if next_ping <= 0 || idle_time > max_idle {
-                // send_ping
-                self.packet_tx.send(self.create_ping_request()).await?;
-                self.metrics.lock().unwrap().send_done();
-            } else {
-                Delay::new(Duration::from_millis(self.read_timeout as u64)).await;
+            if reply_header.err != 0 {
+                return Err(ZKError::ServerError(
+                    ServerErrorCode::from(reply_header.err),
+                    reply_header.err,
+                ));
+            }
+            let mut response = SetSASLResponse::default();
+            response.read(&mut buf)?;
+            match client.evaluate_challenge(&response.token)? {
+                Some(next) => token = next,
+                None => return Ok(()),
             }
         }
     }
-}
-
-#[derive(Debug)]
-struct HostProvider {
-    server_list: Vec<String>,
-    current_index: usize,
-    server_len: usize,
-}
 
-impl HostProvider {
-    fn validate_host(host: &str) -> ZKResult<String> {
-        let ip_port = host.split(':').collect::<Vec<&str>>();
-        if ip_port.len() != 2 {
-            return Err(ZKError::ServerInfoError(
-                ServerInfo::Host,
-                "Host Address format must be 'ip:port'".into(),
-            ));
+    /// 在启动 Sender/Receiver 之前重新注册回调并发送鉴权信息
+    async fn prime_connection(&self, socket: &mut BoxedStream) -> ZKResult<()> {
+        let mut buf = BytesMut::new();
+        let last_zxid = self.session.lock().unwrap().last_zxid;
+        if let Some(request) = self.watcher_manager.set_watches_request(last_zxid) {
+            let mut packet = BytesMut::new();
+            RequestHeader::new_full(XidType::SetWatches.into(), request.op_code())
+                .write(&mut packet)?;
+            request.write(&mut packet)?;
+            buf.extend(Client::wrap_len_buf(packet));
         }
-        match ip_port.get(1).unwrap().parse::<usize>() {
-            Ok(port) if port <= 65535 => port,
-            _ => {
-                return Err(ZKError::ServerInfoError(
-                    ServerInfo::Port,
-                    "Port must be number and less than 65535".into(),
-                ));
-            }
+        let auth_info = self.auth_info.lock().unwrap().clone();
+        for auth in auth_info {
+            let mut packet = BytesMut::new();
+            RequestHeader::new_full(XidType::AuthPacket.into(), OpCode::Auth).write(&mut packet)?;
+            AuthPacket::new(auth.scheme, auth.auth).write(&mut packet)?;
+            buf.extend(Client::wrap_len_buf(packet));
+            self.auth_queue.lock().unwrap().push_back(None);
+        }
+        if !buf.is_empty() {
+            socket.write_all(&buf).await?;
+            socket.flush().await?;
+        }
+        Ok(())
+    }
+
+    /// 连接断开，等待中的请求全部失败
+    fn fail_pending(&self) {
+        self.pending.lock().unwrap().clear();
+        self.auth_queue.lock().unwrap().clear();
+    }
+
+    async fn change_state(
+        &self,
+        state: ConnectionState,
+        keeper_state: KeeperState,
+        states: States,
+    ) {
+        self.state_manager.set_states(states);
+        self.notify(state, keeper_state).await;
+    }
+
+    /// 通知状态订阅者以及默认回调
+    async fn notify(&self, state: ConnectionState, keeper_state: KeeperState) {
+        self.state_manager.publish(state, keeper_state);
+        let event = WatchedEvent {
+            keep_state: keeper_state,
+            event_type: EventType::None,
+            path: String::new(),
         };
+        // EventTask 已经退出时忽略
+        let _ = self.event_tx.send(event).await;
+    }
 
-        for ip in ip_port.get(0).unwrap().split('.') {
-            match ip.parse::<usize>() {
-                Ok(i) if i > 255 => {
-                    return Err(ZKError::ServerInfoError(
-                        ServerInfo::Ip,
-                        "ip address must between 0 and 255".into(),
-                    ));
-                }
-                Err(_) => {
-                    return Err(ZKError::ServerInfoError(
-                        ServerInfo::Ip,
-                        "Invalid ip, must be number".into(),
-                    ))
+    /// 丢弃已经进入队列但是还没有发送的请求，它们属于断开的连接，不能在新的连接上重放。
+    /// 返回 `true` 表示客户端已经被关闭
+    fn fail_queued(&mut self) -> bool {
+        loop {
+            match self.packet_rx.try_recv() {
+                Ok(packet) if packet.ptype != DEATH_PTYPE => {
+                    if let Some(rh) = packet.rh {
+                        if rh.xid == i32::from(XidType::AuthPacket) {
+                            self.drop_queued_auth();
+                        } else {
+                            self.pending.lock().unwrap().remove(&rh.xid);
+                        }
+                    }
                 }
-                _ => (),
+                Ok(_) | Err(mpsc::error::TryRecvError::Disconnected) => return true,
+                Err(mpsc::error::TryRecvError::Empty) => return false,
             }
         }
-        Ok(host.to_string())
     }
 
-    pub(self) fn new(connect_string: &str) -> ZKResult<(HostProvider, String)> {
-        let split_chroot = connect_string.split('/').collect::<Vec<&str>>();
-        if split_chroot.len() > 2 {
-            return Err(ZKError::ServerInfoError(
-                ServerInfo::Chroot,
-                "chroot format must be like 'ip:port/chroot'".into(),
-            ));
-        }
-        let mut server_list = Vec::new();
-        for add in split_chroot.get(0).unwrap().split(',') {
-            match HostProvider::validate_host(add) {
-                Ok(s) => server_list.push(s),
-                Err(e) => return Err(e),
-            }
+    /// `add_auth` 的回调和请求按相同的顺序入队，没有发送的鉴权请求对应第一个等待中的回调
+    fn drop_queued_auth(&self) {
+        let mut auth_queue = self.auth_queue.lock().unwrap();
+        if let Some(index) = auth_queue.iter().position(|tx| tx.is_some()) {
+            auth_queue.remove(index);
         }
+    }
 
-        let mut chroot = String::from('/');
-        if split_chroot.len() == 2 {
-            chroot = '/'.to_string() + *split_chroot.get(1).unwrap();
-        }
-        let server_len = server_list.len();
-        Ok((
-            HostProvider {
-                server_list,
-                current_index: 0,
-                server_len,
-            },
-            chroot,
-        ))
+    /// 退出之后不再接收新的请求，已经进入队列的请求全部失败
+    fn shutdown(&mut self) {
+        self.packet_rx.close();
+        self.fail_queued();
+        self.fail_pending();
     }
 
-    pub(self) fn pick_host(&mut self) -> &str {
-        let address = self.server_list.get(self.current_index).unwrap();
-        let mut new_index = self.current_index + 1;
-        if new_index == self.server_len {
-            new_index = 0;
+    /// 断线之后距离会话过期的剩余时间，服务端在会话超时时间内收不到心跳就会让会话过期
+    fn session_remaining(&self, disconnected_at: Instant) -> Duration {
+        let session_timeout = self.session.lock().unwrap().session_timeout;
+        Duration::from_millis(session_timeout as u64).saturating_sub(disconnected_at.elapsed())
+    }
+
+    /// 断线期间等待下一次重连，返回 `true` 表示客户端已经被关闭
+    async fn wait_for_retry(&mut self, delay: Duration) -> bool {
+        let sleep = time::sleep(delay);
+        tokio::pin!(sleep);
+        loop {
+            tokio::select! {
+                _ = &mut sleep => return false,
+                packet = self.packet_rx.recv() => match packet {
+                    Some(packet) if packet.ptype != DEATH_PTYPE => {
+                        // 断线期间的请求直接以 ConnectionLoss 失败
+                        if let Some(rh) = packet.rh {
+                            self.pending.lock().unwrap().remove(&rh.xid);
+                        }
+                    }
+                    _ => return true,
+                },
+            }
         }
-        self.current_index = new_index;
+    }
+
+    pub(self) async fn run(&mut self, ready_tx: oneshot::Sender<ZKResult<()>>) {
+        let mut ready_tx = Some(ready_tx);
+        let mut failures = 0;
+        let mut disconnected_at = None;
+        loop {
+            if let Some(disconnected_at) = disconnected_at {
+                if self.session_remaining(disconnected_at).is_zero() {
+                    // 不等服务端确认，与 Curator 一致在本地判定会话已经过期
+                    warn!("Disconnected longer than session timeout, session expired");
+                    self.change_state(ConnectionState::Lost, KeeperState::Expired, States::Closed)
+                        .await;
+                    self.fail_pending();
+                    return;
+                }
+            }
+            let host = self.host_provider.next();
+            let (mut socket, response) = match self.handshake(&host).await {
+                Ok(r) => r,
+                Err(e) => {
+                    warn!("Unable to connect to {}: {}", host, e);
+                    failures += 1;
+                    if failures < self.host_provider.size() {
+                        continue;
+                    }
+                    // 所有服务端都尝试过一遍
+                    failures = 0;
+                    if let Some(tx) = ready_tx.take() {
+                        let _ = tx.send(Err(e));
+                        return;
+                    }
+                    let delay = match disconnected_at {
+                        Some(disconnected_at) => {
+                            Duration::from_secs(1).min(self.session_remaining(disconnected_at))
+                        }
+                        None => Duration::from_secs(1),
+                    };
+                    if self.wait_for_retry(delay).await {
+                        self.change_state(
+                            ConnectionState::Closed,
+                            KeeperState::Closed,
+                            States::Closed,
+                        )
+                        .await;
+                        return;
+                    }
+                    continue;
+                }
+            };
+            failures = 0;
+            self.host_provider.on_connected(&host);
+            if response.time_out <= 0 {
+                // 服务端认为会话已经过期
+                self.change_state(ConnectionState::Lost, KeeperState::Expired, States::Closed)
+                    .await;
+                self.fail_pending();
+                if let Some(tx) = ready_tx.take() {
+                    let _ = tx.send(Err(self.state_manager.loss_error()));
+                }
+                return;
+            }
+            {
+                let mut session = self.session.lock().unwrap();
+                session.session_id = response.session_id;
+                session.password = Some(response.password.clone());
+                session.session_timeout = response.time_out as u32;
+            }
+            let (state, keeper_state, states) = if response.read_only {
+                (
+                    ConnectionState::ReadOnly,
+                    KeeperState::ConnectedReadOnly,
+                    States::ConnectedReadOnly,
+                )
+            } else if ready_tx.is_some() {
+                (
+                    ConnectionState::Connected,
+                    KeeperState::SyncConnected,
+                    States::Connected,
+                )
+            } else {
+                (
+                    ConnectionState::Reconnected,
+                    KeeperState::SyncConnected,
+                    States::Connected,
+                )
+            };
+            if let Some(sasl) = &self.sasl {
+                match self.sasl_authenticate(&mut socket, sasl).await {
+                    Ok(()) => {}
+                    Err(e @ ZKError::SaslError(_))
+                    | Err(e @ ZKError::ServerError(ServerErrorCode::AuthFailed, _)) => {
+                        warn!("SASL authentication with {} failed: {}", host, e);
+                        // 与 Java 客户端一致，鉴权失败后客户端不再可用
+                        self.change_state(
+                            ConnectionState::AuthFailed,
+                            KeeperState::AuthFailed,
+                            States::AuthFailed,
+                        )
+                        .await;
+                        self.fail_pending();
+                        if let Some(tx) = ready_tx.take() {
+                            let _ = tx.send(Err(e));
+                        }
+                        return;
+                    }
+                    Err(e) => {
+                        warn!("SASL authentication with {} error: {}", host, e);
+                        self.fail_pending();
+                        continue;
+                    }
+                }
+            }
+            if let Err(e) = self.prime_connection(&mut socket).await {
+                warn!("Prime connection to {} error: {}", host, e);
+                self.fail_pending();
+                continue;
+            }
+            // 握手也算一次发送，避免连接建立后立刻发送心跳
+            self.metrics.lock().unwrap().send_done();
+            // 断线前进入队列的请求在握手期间仍然可能留在队列中
+            if self.fail_queued() {
+                self.change_state(ConnectionState::Closed, KeeperState::Closed, States::Closed)
+                    .await;
+                self.fail_pending();
+                return;
+            }
+            self.change_state(state, keeper_state, states).await;
+            if self.sasl.is_some() {
+                self.notify(state, KeeperState::SaslAuthenticated).await;
+            }
+            if let Some(tx) = ready_tx.take() {
+                let _ = tx.send(Ok(()));
+            }
 
-        address
+            let read_timeout = Duration::from_millis(response.time_out as u64 * 2 / 3);
+            let (reader, writer) = io::split(socket);
+            let mut sender_task = SenderTask {
+                packet_rx: &mut self.packet_rx,
+                writer,
+                metrics: self.metrics.clone(),
+                ping_interval: read_timeout / 2,
+            };
+            let mut receiver_task = ReceiverTask {
+                reader,
+                event_tx: self.event_tx.clone(),
+                pending: self.pending.clone(),
+                auth_queue: self.auth_queue.clone(),
+                session: self.session.clone(),
+                read_timeout,
+            };
+            let result = tokio::select! {
+                r = sender_task.run() => r,
+                r = receiver_task.run() => r,
+            };
+            drop(sender_task);
+
+            if result.is_ok() || self.state_manager.is_closing() {
+                self.change_state(ConnectionState::Closed, KeeperState::Closed, States::Closed)
+                    .await;
+                self.fail_pending();
+                return;
+            }
+            if let Err(ZKError::ServerError(ServerErrorCode::AuthFailed, _)) = result {
+                // 与 Java 客户端一致，鉴权失败后客户端不再可用
+                self.change_state(
+                    ConnectionState::AuthFailed,
+                    KeeperState::AuthFailed,
+                    States::AuthFailed,
+                )
+                .await;
+                self.fail_pending();
+                return;
+            }
+            if let Err(e) = result {
+                warn!("Connection to {} lost: {}", host, e);
+            }
+            self.change_state(
+                ConnectionState::Suspended,
+                KeeperState::Disconnected,
+                States::Connecting,
+            )
+            .await;
+            // 等待中和排队中的请求全部以 ConnectionLoss 失败
+            self.fail_pending();
+            if self.fail_queued() {
+                self.change_state(ConnectionState::Closed, KeeperState::Closed, States::Closed)
+                    .await;
+                return;
+            }
+            disconnected_at = Some(Instant::now());
+        }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub(crate) struct Client {
-    #[allow(dead_code)]
-    host_provider: HostProvider,
-    pub session_timeout: u32,
+    config: Arc<ClientConfig>,
     packet_tx: Sender<ReqPacket>,
-    buf_rx: Receiver<(ReplyHeader, BytesMut)>,
-    pub state: States,
-    pub session_id: i64,
-    password: Option<Vec<u8>>,
+    pending: PendingMap,
+    auth_info: Arc<Mutex<Vec<AuthInfo>>>,
+    auth_queue: AuthQueue,
+    /// 保证鉴权回调入队和请求发送的顺序一致
+    auth_sending: Arc<tokio::sync::Mutex<()>>,
+    session: Arc<Mutex<Session>>,
+    state_manager: Arc<StateManager>,
     chroot: String,
     watcher_manager: Arc<WatcherManager>,
-    xid: AtomicI32,
+    xid: Arc<AtomicI32>,
 }
 
 impl Client {
@@ -331,35 +686,28 @@ impl Client {
         path
     }
 
-    pub(crate) async fn new(connect_string: &str, session_timeout: u32) -> ZKResult<Client> {
-        let (mut host_provider, chroot) = HostProvider::new(connect_string)?;
-        let socket = match TcpStream::connect(host_provider.pick_host()).await {
-            Ok(socket) => socket,
-            Err(_e) => {
-                return Err(ZKError::NetworkError(
-                    "Connect to ZooKeeper server error!".into(),
-                ))
-            }
-        };
-
-        let (reader, writer) = io::split(socket);
-        let metrics = Arc::new(Mutex::new(Metrics::default()));
-        // start send thread
-        let (packet_tx, packet_rx): (Sender<ReqPacket>, Receiver<ReqPacket>) = mpsc::channel(2017);
-        let mut sender_task = SenderTask {
-            packet_rx,
-            writer,
-            metrics: metrics.clone(),
-        };
-        tokio::spawn(async move {
-            sender_task.run().await?;
-            Ok::<_, ZKError>(())
+    pub(crate) async fn new(
+        config: ClientConfig,
+        host_provider: Box<dyn HostProvider>,
+        chroot: String,
+        default_watcher: Option<Box<dyn Watcher>>,
+    ) -> ZKResult<Client> {
+        let session_timeout = config.session_timeout.as_millis() as u32;
+        let connect_timeout = config.connection_timeout.unwrap_or_else(|| {
+            Duration::from_millis((session_timeout as usize / host_provider.size().max(1)) as u64)
         });
+        let connector = Connector::new(&config)?;
+        let metrics = Arc::new(Mutex::new(Metrics::default()));
+        let (packet_tx, packet_rx): (Sender<ReqPacket>, Receiver<ReqPacket>) =
+            mpsc::channel(config.packet_capacity);
 
-        let watcher_manager = Arc::new(WatcherManager::new(false));
+        let watcher_manager = Arc::new(WatcherManager::new(
+            !config.auto_watch_reset,
+            default_watcher,
+        ));
 
         // start event thread
-        let (event_tx, event_rx) = mpsc::channel(2017);
+        let (event_tx, event_rx) = mpsc::channel(config.event_capacity);
         let mut event_task = EventTask {
             event_rx,
             watcher_manager: watcher_manager.clone(),
@@ -369,136 +717,226 @@ impl Client {
             Ok::<_, ZKError>(())
         });
 
-        // start receive thread
-        let (buf_tx, buf_rx): (
-            Sender<(ReplyHeader, BytesMut)>,
-            Receiver<(ReplyHeader, BytesMut)>,
-        ) = mpsc::channel(1002);
-        let mut receiver_task = ReceiverTask {
-            buf_tx,
-            reader,
+        let state_manager = Arc::new(StateManager::new(config.state_capacity));
+        let pending: PendingMap = Arc::new(Mutex::new(HashMap::new()));
+        let session = Arc::new(Mutex::new(Session {
+            session_timeout,
+            ..Session::default()
+        }));
+        let auth_info = Arc::new(Mutex::new(config.auth_info.clone()));
+        let auth_queue: AuthQueue = Arc::new(Mutex::new(VecDeque::new()));
+        let xid = Arc::new(AtomicI32::new(0));
+
+        // start connection thread，Sender 和 Receiver 都由它负责启动
+        let mut connection_task = ConnectionTask {
+            host_provider,
+            connector,
+            connect_timeout,
+            read_only: config.read_only,
+            auth_info: auth_info.clone(),
+            sasl: config.sasl.clone(),
+            xid: xid.clone(),
+            watcher_manager: watcher_manager.clone(),
+            packet_rx,
             event_tx,
-            is_connected: false,
+            pending: pending.clone(),
+            auth_queue: auth_queue.clone(),
+            session: session.clone(),
+            state_manager: state_manager.clone(),
+            metrics,
         };
+        let (ready_tx, ready_rx) = oneshot::channel();
         tokio::spawn(async move {
-            receiver_task.run().await?;
-            Ok::<_, ZKError>(())
+            connection_task.run(ready_tx).await;
+            connection_task.shutdown();
         });
+        match ready_rx.await {
+            Ok(result) => result?,
+            Err(_) => return Err(ZKError::UnknownError),
+        }
 
-        let packet_tx_ping = Sender::clone(&packet_tx);
-
-        let mut client = Client {
-            host_provider,
-            session_timeout,
+        Ok(Client {
+            config: Arc::new(config),
             packet_tx,
-            buf_rx,
-            state: States::NotConnected,
-            session_id: 0,
-            password: None,
+            pending,
+            auth_info,
+            auth_queue,
+            auth_sending: Arc::new(tokio::sync::Mutex::new(())),
+            session,
+            state_manager,
             chroot,
-            watcher_manager: watcher_manager.clone(),
-            xid: AtomicI32::new(0),
-        };
-        client.start_connect(session_timeout).await?;
-        client.state = States::Connected;
-        // start ping task
-        let mut ping_task = PingTask {
-            packet_tx: packet_tx_ping,
-            metrics: metrics.clone(),
-            read_timeout: (session_timeout / 3 * 2) as i64,
-        };
+            watcher_manager,
+            xid,
+        })
+    }
 
-        tokio::spawn(async move {
-            ping_task.run().await?;
-            Ok::<_, ZKError>(())
-        });
-        Ok(client)
+    pub(crate) fn default_acl(&self) -> Vec<ACL> {
+        self.config.default_acl.clone()
     }
 
-    fn create_connect_request(&self, session_timeout: u32) -> ZKResult<BytesMut> {
-        let mut buf = BytesMut::new();
-        let connect_request = ConnectRequest::new(session_timeout);
-        connect_request.write(&mut buf)?;
-        Ok(buf)
+    pub(crate) fn retry_policy(&self) -> Arc<dyn RetryPolicy> {
+        self.config.retry_policy.clone()
     }
 
-    async fn read_buf(&mut self, xid: Option<i32>) -> ZKResult<BytesMut> {
-        let buf = match self.buf_rx.recv().await {
-            Some((reply_header, buf)) => {
-                if reply_header.err != 0 {
-                    return Err(ZKError::ServerError(
-                        ServerErrorCode::from(reply_header.err),
-                        reply_header.err,
-                    ));
-                }
-                if let Some(xid) = xid {
-                    if reply_header.xid != xid {
-                        return Err(ZKError::ServerError(
-                            ServerErrorCode::ConnectionLoss,
-                            reply_header.err,
-                        ));
-                    }
-                }
-                buf
-            }
-            _ => return Err(ZKError::UnknownError),
-        };
-        Ok(buf)
+    /// 开启压缩时压缩节点数据
+    pub(crate) fn compress(&self, path: &str, data: &[u8]) -> ZKResult<Vec<u8>> {
+        match &self.config.compression {
+            Some(compression) => compression.compress(path, data),
+            None => Ok(data.to_vec()),
+        }
     }
 
-    async fn write_buf(
-        &mut self,
-        mut rh: Option<RequestHeader>,
-        req: BytesMut,
-    ) -> ZKResult<Option<i32>> {
-        let xid = match rh.as_mut() {
-            Some(mut header) => {
-                header.xid = self.xid.fetch_add(1, Release);
-                Some(header.xid)
-            }
-            _ => None,
-        };
-        let packet = ReqPacket::new(rh, Some(req));
-        self.packet_tx.send(packet).await?;
-        Ok(xid)
+    /// 根据头部解压节点数据，与是否开启压缩无关，其他客户端写入的压缩数据同样可以读取
+    pub(crate) fn decompress(&self, path: &str, data: Vec<u8>) -> ZKResult<Vec<u8>> {
+        compress::decompress(path, data)
     }
 
-    async fn start_connect(&mut self, session_timeout: u32) -> ZKResult<()> {
-        if self.state.is_connected() {
-            return Ok(());
+    /// 使用当前会话的鉴权信息创建权限计算器
+    pub(crate) fn permission_evaluator(&self) -> PermissionEvaluator {
+        let mut evaluator = PermissionEvaluator::new();
+        for auth in self.auth_info.lock().unwrap().iter() {
+            evaluator = evaluator.auth(&auth.scheme, &auth.auth);
         }
-        self.state = States::Connecting;
+        if let Some(sasl) = &self.config.sasl {
+            evaluator = evaluator.identity(Scheme::Sasl(sasl.username.clone()));
+        }
+        evaluator
+    }
 
-        // 创建 connect request
-        let req = self.create_connect_request(session_timeout)?;
+    pub(crate) fn state(&self) -> States {
+        self.state_manager.states()
+    }
 
-        self.write_buf(None, req).await?;
+    pub(crate) fn session_id(&self) -> i64 {
+        self.session.lock().unwrap().session_id
+    }
 
-        let mut buf = self.read_buf(None).await?;
+    pub(crate) fn session_timeout(&self) -> u32 {
+        self.session.lock().unwrap().session_timeout
+    }
 
-        let mut response = ConnectResponse::default();
-        response.read(&mut buf)?;
-        self.session_id = response.session_id;
-        self.password = Some(response.password);
+    pub(crate) fn subscribe_state(&self) -> StateListener {
+        self.state_manager.subscribe()
+    }
+
+    pub(crate) fn loss_error(&self) -> ZKError {
+        self.state_manager.loss_error()
+    }
+
+    pub(crate) async fn wait_until_connected(&self, timeout: Duration) -> ZKResult<()> {
+        self.state_manager.wait_until_connected(timeout).await
+    }
+
+    pub(crate) async fn add_auth(&self, scheme: String, auth: Vec<u8>) -> ZKResult<()> {
+        if !self.state_manager.is_connected() {
+            return Err(self.state_manager.loss_error());
+        }
+        let auth_info = AuthInfo { scheme, auth };
+        let rh = RequestHeader::new_full(XidType::AuthPacket.into(), OpCode::Auth);
+        let mut req = BytesMut::new();
+        AuthPacket::new(auth_info.scheme.clone(), auth_info.auth.clone()).write(&mut req)?;
+        let (tx, rx) = oneshot::channel();
+        {
+            // 服务端按顺序回复鉴权请求，入队和发送必须是一个整体，否则并发时会拿到其他请求的结果
+            let _sending = self.auth_sending.lock().await;
+            self.auth_queue.lock().unwrap().push_back(Some(tx));
+            self.packet_tx
+                .send(ReqPacket::new(Some(rh), Some(req)))
+                .await?;
+        }
+        match rx.await {
+            Ok(0) => {
+                // 服务端接受之后才保存鉴权信息，之后每次重连都会自动发送
+                let mut guard = self.auth_info.lock().unwrap();
+                if !guard.contains(&auth_info) {
+                    guard.push(auth_info);
+                }
+                Ok(())
+            }
+            Ok(err) => Err(ZKError::ServerError(ServerErrorCode::from(err), err)),
+            Err(_) => Err(self.state_manager.loss_error()),
+        }
+    }
+
+    pub(crate) async fn close(&self) -> ZKResult<()> {
+        if !self.state().is_alive() {
+            return Ok(());
+        }
+        self.state_manager.set_closing();
+        if self.state_manager.is_connected() {
+            let rh = RequestHeader::new(OpCode::CloseSession);
+            if let Err(e) = self
+                .submit_request(rh, "/", BytesMut::new(), IgnoreResponse::default())
+                .await
+            {
+                warn!("Close session error: {}", e);
+            }
+        }
+        // ConnectionTask 已经退出时发送会失败，忽略即可
+        let _ = self.packet_tx.send(ReqPacket::death_request()).await;
+        let timeout = Duration::from_millis(self.session_timeout() as u64);
+        self.state_manager.wait_until_closed(timeout).await;
         Ok(())
     }
 
+    /// 发送请求并等待响应，失败时错误会携带操作类型和 `path`
     pub async fn submit_request<D>(
-        &mut self,
-        rh: Option<RequestHeader>,
+        &self,
+        rh: RequestHeader,
+        path: &str,
+        req: BytesMut,
+        resp: D,
+    ) -> ZKResult<D>
+    where
+        D: Deserializer,
+    {
+        let op = OpCode::from(rh.rtype);
+        self.send_request(rh, req, resp)
+            .await
+            .map_err(|e| e.with_operation(op, path))
+    }
+
+    async fn send_request<D>(
+        &self,
+        mut rh: RequestHeader,
         req: BytesMut,
         mut resp: D,
     ) -> ZKResult<D>
     where
         D: Deserializer,
     {
-        if !self.state.is_connected() {
-            return Err(ZKError::NetworkError(
-                "Client connection is not ready!".into(),
+        if !self.state_manager.is_connected() {
+            return Err(self.state_manager.loss_error());
+        }
+        rh.xid = self.xid.fetch_add(1, Release);
+        let xid = rh.xid;
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().unwrap().insert(xid, tx);
+        self.packet_tx
+            .send(ReqPacket::new(Some(rh), Some(req)))
+            .await?;
+        let reply = match self.config.request_timeout {
+            Some(timeout) => match time::timeout(timeout, rx).await {
+                Ok(reply) => reply,
+                Err(_) => {
+                    // 之后到达的响应找不到等待者，直接丢弃
+                    self.pending.lock().unwrap().remove(&xid);
+                    return Err(ZKError::ServerError(ServerErrorCode::RequestTimeout, -122));
+                }
+            },
+            None => rx.await,
+        };
+        let (reply_header, mut buf) = match reply {
+            Ok(reply) => reply,
+            // 连接断开，等待中的请求被丢弃
+            Err(_) => return Err(self.state_manager.loss_error()),
+        };
+        if reply_header.err != 0 {
+            return Err(ZKError::ServerError(
+                ServerErrorCode::from(reply_header.err),
+                reply_header.err,
             ));
         }
-        let xid = self.write_buf(rh, req).await?;
-        let mut buf = self.read_buf(xid).await?;
         resp.read(&mut buf)?;
         Ok(resp)
     }
@@ -0,0 +1,152 @@
+//! # 编解码模块
+//! 定义 [`Codec`]，配合 [`ZooKeeper::get_as_with`]、[`ZooKeeper::set_as_with`]、[`ZooKeeper::create_as_with`]
+//! 直接读写具体类型的节点数据。基于 serde 的 JSON、bincode、TOML 实现分别需要开启
+//! `json`、`bincode`、`toml` feature，开启 `json` 后不带 `_with` 的 `get_as`、`set_as`、`create_as` 默认使用 JSON
+//!
+//! [`ZooKeeper::get_as_with`]: crate::ZooKeeper::get_as_with
+//! [`ZooKeeper::set_as_with`]: crate::ZooKeeper::set_as_with
+//! [`ZooKeeper::create_as_with`]: crate::ZooKeeper::create_as_with
+
+use std::error::Error;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// 编解码失败的原因
+pub type CodecError = Box<dyn Error + Send + Sync>;
+
+/// 节点数据的编解码规则
+/// # Examples
+///
+/// ```rust,ignore
+/// #[derive(Debug)]
+/// struct Utf8Codec;
+///
+/// impl Codec for Utf8Codec {
+///     fn name(&self) -> &'static str {
+///         "utf8"
+///     }
+///
+///     fn encode<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>, CodecError> {
+///         Ok(serde_plain::to_string(value)?.into_bytes())
+///     }
+///
+///     fn decode<T: DeserializeOwned>(&self, data: &[u8]) -> Result<T, CodecError> {
+///         Ok(serde_plain::from_str(std::str::from_utf8(data)?)?)
+///     }
+/// }
+/// ```
+pub trait Codec {
+    /// 编解码规则的名称，会出现在错误信息中
+    fn name(&self) -> &'static str;
+
+    /// 将对象编码为节点数据
+    fn encode<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>, CodecError>;
+
+    /// 将节点数据解码为对象
+    fn decode<T: DeserializeOwned>(&self, data: &[u8]) -> Result<T, CodecError>;
+}
+
+/// JSON 编解码，需要开启 `json` feature
+#[cfg(feature = "json")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonCodec;
+
+#[cfg(feature = "json")]
+impl Codec for JsonCodec {
+    fn name(&self) -> &'static str {
+        "json"
+    }
+
+    fn encode<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>, CodecError> {
+        Ok(serde_json::to_vec(value)?)
+    }
+
+    fn decode<T: DeserializeOwned>(&self, data: &[u8]) -> Result<T, CodecError> {
+        Ok(serde_json::from_slice(data)?)
+    }
+}
+
+/// bincode 编解码，需要开启 `bincode` feature
+#[cfg(feature = "bincode")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BincodeCodec;
+
+#[cfg(feature = "bincode")]
+impl Codec for BincodeCodec {
+    fn name(&self) -> &'static str {
+        "bincode"
+    }
+
+    fn encode<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>, CodecError> {
+        Ok(bincode::serialize(value)?)
+    }
+
+    fn decode<T: DeserializeOwned>(&self, data: &[u8]) -> Result<T, CodecError> {
+        Ok(bincode::deserialize(data)?)
+    }
+}
+
+/// TOML 编解码，需要开启 `toml` feature。TOML 的顶层只能是表，因此只支持结构体和 map
+#[cfg(feature = "toml")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TomlCodec;
+
+#[cfg(feature = "toml")]
+impl Codec for TomlCodec {
+    fn name(&self) -> &'static str {
+        "toml"
+    }
+
+    fn encode<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>, CodecError> {
+        Ok(toml::to_string(value)?.into_bytes())
+    }
+
+    fn decode<T: DeserializeOwned>(&self, data: &[u8]) -> Result<T, CodecError> {
+        Ok(toml::from_str(std::str::from_utf8(data)?)?)
+    }
+}
+
+#[cfg(test)]
+#[cfg(all(feature = "json", feature = "bincode", feature = "toml"))]
+mod test {
+    use std::collections::BTreeMap;
+
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Route {
+        name: String,
+        weight: u32,
+        tags: BTreeMap<String, String>,
+    }
+
+    fn route() -> Route {
+        Route {
+            name: "shard-1".to_string(),
+            weight: 7,
+            tags: BTreeMap::from([("zone".to_string(), "a".to_string())]),
+        }
+    }
+
+    fn round_trip(codec: impl Codec) {
+        let data = codec.encode(&route()).unwrap();
+        assert_eq!(codec.decode::<Route>(&data).unwrap(), route());
+        assert!(codec.decode::<Route>(b"\xff\x00").is_err());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        round_trip(JsonCodec);
+        round_trip(BincodeCodec);
+        round_trip(TomlCodec);
+        assert_eq!(
+            JsonCodec.encode(&route()).unwrap(),
+            br#"{"name":"shard-1","weight":7,"tags":{"zone":"a"}}"#
+        );
+        // TOML 的顶层只能是表
+        assert!(TomlCodec.encode(&1).is_err());
+    }
+}
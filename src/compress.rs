@@ -0,0 +1,248 @@
+//! # 压缩模块
+//! 节点数据最大约 1 MB，开启压缩后 `create`、`set` 写入的数据会被压缩并添加一个 4 字节的头部，
+//! `get` 读取时根据头部自动解压，没有头部的旧数据原样返回。gzip 和 zstd 分别需要开启 `gzip`、`zstd` feature
+//!
+//! 头部格式为 `0xF0 'Z' 'C'` 加上 1 字节的算法编号
+
+use std::error::Error;
+#[cfg(any(feature = "gzip", feature = "zstd"))]
+use std::io::{Read, Write};
+
+use crate::{ZKError, ZKResult};
+
+const MAGIC: [u8; 3] = [0xF0, b'Z', b'C'];
+const HEADER_LEN: usize = MAGIC.len() + 1;
+
+/// 压缩算法
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Algorithm {
+    Gzip,
+    Zstd,
+}
+
+impl Algorithm {
+    fn id(&self) -> u8 {
+        match self {
+            Algorithm::Gzip => 1,
+            Algorithm::Zstd => 2,
+        }
+    }
+
+    fn from_id(id: u8) -> Option<Algorithm> {
+        match id {
+            1 => Some(Algorithm::Gzip),
+            2 => Some(Algorithm::Zstd),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Algorithm::Gzip => "gzip",
+            Algorithm::Zstd => "zstd",
+        }
+    }
+}
+
+/// 压缩配置，只影响 `create`、`set` 写入的数据。读取时总是根据头部自动解压，读取的客户端不需要配置压缩，
+/// 但是需要开启对应的 feature
+/// # Examples
+///
+/// ```rust,ignore
+/// let zk = ZooKeeper::builder("127.0.0.1:2181")
+///     .compression(Compression::zstd().level(9).min_size(4096))
+///     .build()
+///     .await?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct Compression {
+    algorithm: Algorithm,
+    level: Option<i32>,
+    min_size: usize,
+}
+
+impl Compression {
+    /// 使用 gzip 压缩，需要开启 `gzip` feature
+    #[cfg(feature = "gzip")]
+    pub fn gzip() -> Compression {
+        Compression::new(Algorithm::Gzip)
+    }
+
+    /// 使用 zstd 压缩，需要开启 `zstd` feature
+    #[cfg(feature = "zstd")]
+    pub fn zstd() -> Compression {
+        Compression::new(Algorithm::Zstd)
+    }
+
+    #[cfg(any(feature = "gzip", feature = "zstd"))]
+    fn new(algorithm: Algorithm) -> Compression {
+        Compression {
+            algorithm,
+            level: None,
+            min_size: 1024,
+        }
+    }
+
+    /// 压缩级别，gzip 为 0 到 9，zstd 为 1 到 22，默认使用算法的默认级别
+    pub fn level(mut self, level: i32) -> Self {
+        self.level = Some(level);
+        self
+    }
+
+    /// 小于该字节数的数据不压缩，默认 1024
+    pub fn min_size(mut self, min_size: usize) -> Self {
+        self.min_size = min_size;
+        self
+    }
+
+    /// 压缩数据并添加头部，数据太小或者压缩后没有变小时原样返回
+    pub(crate) fn compress(&self, path: &str, data: &[u8]) -> ZKResult<Vec<u8>> {
+        if data.len() < self.min_size {
+            return Ok(data.to_vec());
+        }
+        let mut header = Vec::with_capacity(data.len() / 2);
+        header.extend_from_slice(&MAGIC);
+        header.push(self.algorithm.id());
+        let compressed = compress(self.algorithm, self.level, data, header).map_err(|source| {
+            ZKError::EncodeError {
+                path: path.to_string(),
+                codec: self.algorithm.name(),
+                source,
+            }
+        })?;
+        if compressed.len() >= data.len() {
+            return Ok(data.to_vec());
+        }
+        Ok(compressed)
+    }
+}
+
+/// 根据头部解压数据，没有头部时原样返回
+pub(crate) fn decompress(path: &str, data: Vec<u8>) -> ZKResult<Vec<u8>> {
+    if data.len() < HEADER_LEN || data[..MAGIC.len()] != MAGIC {
+        return Ok(data);
+    }
+    let algorithm = match Algorithm::from_id(data[MAGIC.len()]) {
+        Some(algorithm) => algorithm,
+        // 只是碰巧以相同的字节开头
+        None => return Ok(data),
+    };
+    decompress_payload(algorithm, &data[HEADER_LEN..]).map_err(|source| ZKError::DecodeError {
+        path: path.to_string(),
+        codec: algorithm.name(),
+        source,
+    })
+}
+
+/// 压缩后的数据追加到 `out` 之后
+#[cfg(any(feature = "gzip", feature = "zstd"))]
+fn compress(
+    algorithm: Algorithm,
+    level: Option<i32>,
+    data: &[u8],
+    out: Vec<u8>,
+) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+    match algorithm {
+        #[cfg(feature = "gzip")]
+        Algorithm::Gzip => {
+            let level = match level {
+                Some(level) => flate2::Compression::new(level.clamp(0, 9) as u32),
+                None => flate2::Compression::default(),
+            };
+            let mut encoder = flate2::write::GzEncoder::new(out, level);
+            encoder.write_all(data)?;
+            Ok(encoder.finish()?)
+        }
+        #[cfg(feature = "zstd")]
+        Algorithm::Zstd => {
+            let level = level.unwrap_or(zstd::DEFAULT_COMPRESSION_LEVEL);
+            let mut encoder = zstd::Encoder::new(out, level)?;
+            encoder.write_all(data)?;
+            Ok(encoder.finish()?)
+        }
+        #[allow(unreachable_patterns)]
+        _ => Err(feature_disabled(algorithm)),
+    }
+}
+
+#[cfg(any(feature = "gzip", feature = "zstd"))]
+fn decompress_payload(
+    algorithm: Algorithm,
+    data: &[u8],
+) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+    let mut out: Vec<u8> = Vec::with_capacity(data.len() * 2);
+    match algorithm {
+        #[cfg(feature = "gzip")]
+        Algorithm::Gzip => {
+            flate2::read::GzDecoder::new(data).read_to_end(&mut out)?;
+            Ok(out)
+        }
+        #[cfg(feature = "zstd")]
+        Algorithm::Zstd => {
+            zstd::Decoder::new(data)?.read_to_end(&mut out)?;
+            Ok(out)
+        }
+        #[allow(unreachable_patterns)]
+        _ => Err(feature_disabled(algorithm)),
+    }
+}
+
+/// 没有开启任何压缩 feature 时无法构造 [`Compression`]，读取到压缩数据时返回错误
+#[cfg(not(any(feature = "gzip", feature = "zstd")))]
+fn compress(
+    algorithm: Algorithm,
+    _level: Option<i32>,
+    _data: &[u8],
+    _out: Vec<u8>,
+) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+    Err(feature_disabled(algorithm))
+}
+
+#[cfg(not(any(feature = "gzip", feature = "zstd")))]
+fn decompress_payload(
+    algorithm: Algorithm,
+    _data: &[u8],
+) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+    Err(feature_disabled(algorithm))
+}
+
+fn feature_disabled(algorithm: Algorithm) -> Box<dyn Error + Send + Sync> {
+    format!("`{}` feature is not enabled", algorithm.name()).into()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_legacy_data() {
+        assert_eq!(decompress("/a", b"plain".to_vec()).unwrap(), b"plain");
+        assert_eq!(decompress("/a", vec![0xF0, b'Z']).unwrap(), [0xF0, b'Z']);
+        // 未知的算法编号视为普通数据
+        let data = vec![0xF0, b'Z', b'C', 9, 1, 2];
+        assert_eq!(decompress("/a", data.clone()).unwrap(), data);
+    }
+
+    #[cfg(all(feature = "gzip", feature = "zstd"))]
+    #[test]
+    fn test_round_trip() {
+        let data = "route=10.0.0.1;".repeat(1000).into_bytes();
+        for compression in [Compression::gzip(), Compression::zstd().level(19)] {
+            let compressed = compression.compress("/a", &data).unwrap();
+            assert_eq!(compressed[..3], MAGIC);
+            assert!(compressed.len() < data.len() / 10);
+            assert_eq!(decompress("/a", compressed).unwrap(), data);
+        }
+        // 小数据不压缩
+        let compression = Compression::gzip().min_size(64);
+        assert_eq!(compression.compress("/a", b"small").unwrap(), b"small");
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_corrupted_data() {
+        let e = decompress("/a", vec![0xF0, b'Z', b'C', 1, 0, 0, 0]).unwrap_err();
+        assert_eq!(e.path(), Some("/a"));
+        assert!(matches!(e, ZKError::DecodeError { codec: "gzip", .. }));
+    }
+}
@@ -1,52 +1,182 @@
 #![allow(unused)]
 
-use std::fmt::{Display, Formatter};
-
-/// ZooKeeper 定义的 5 种权限
-/// `Read`： 节点可读        0 0 0 0 1
-/// `Write`： 节点可写       0 0 0 1 0
-/// `Create`： 可创建子节点   0 0 1 0 0
-/// `Delete`： 可删除子节点   0 1 0 0 0
-/// `ACL`： 可读写 ACL 数据  1 0 0 0 0
-/// `All`： 所有以上权限     1 1 1 1 1
-#[derive(Debug)]
-pub enum Perms {
-    Read,
-    Write,
-    Create,
-    Delete,
-    ACL,
-    All,
+use std::fmt::{Debug, Display, Formatter};
+use std::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, Not, Sub};
+use std::str::FromStr;
+
+use crate::ZKError;
+
+/// ZooKeeper 定义的 5 种权限，可以通过 `|` 组合，通过 [`Perms::contains`] 判断
+/// `READ`： 节点可读        0 0 0 0 1
+/// `WRITE`： 节点可写       0 0 0 1 0
+/// `CREATE`： 可创建子节点   0 0 1 0 0
+/// `DELETE`： 可删除子节点   0 1 0 0 0
+/// `ADMIN`： 可读写 ACL 数据 1 0 0 0 0
+/// `ALL`： 所有以上权限      1 1 1 1 1
+///
+/// 与 zkCli 一致，显示为 `cdrwa` 格式的字符串，也可以从该格式解析
+/// # Examples
+///
+/// ```rust,ignore
+/// let perms = Perms::READ | Perms::WRITE;
+/// assert!(perms.contains(Perms::READ));
+/// assert_eq!(perms.to_string(), "rw");
+/// assert_eq!("rw".parse::<Perms>()?, perms);
+/// ```
+#[derive(Clone, Copy, Default, Eq, PartialEq, Hash)]
+pub struct Perms(i32);
+
+impl Perms {
+    pub const NONE: Perms = Perms(0);
+    pub const READ: Perms = Perms(1);
+    pub const WRITE: Perms = Perms(1 << 1);
+    pub const CREATE: Perms = Perms(1 << 2);
+    pub const DELETE: Perms = Perms(1 << 3);
+    pub const ADMIN: Perms = Perms(1 << 4);
+    pub const ALL: Perms = Perms(31);
+
+    /// zkCli 显示权限的顺序
+    const CHARS: [(char, Perms); 5] = [
+        ('c', Perms::CREATE),
+        ('d', Perms::DELETE),
+        ('r', Perms::READ),
+        ('w', Perms::WRITE),
+        ('a', Perms::ADMIN),
+    ];
+
+    /// 原始的权限值
+    pub const fn bits(self) -> i32 {
+        self.0
+    }
+
+    /// 是否包含 `other` 中的所有权限
+    pub const fn contains(self, other: Perms) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// 是否包含 `other` 中的任意一个权限
+    pub const fn intersects(self, other: Perms) -> bool {
+        self.0 & other.0 != 0
+    }
+
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn insert(&mut self, other: Perms) {
+        self.0 |= other.0;
+    }
+
+    pub fn remove(&mut self, other: Perms) {
+        self.0 &= !other.0;
+    }
+
+    /// 逐个返回包含的单个权限，顺序与 `cdrwa` 一致
+    pub fn iter(self) -> impl Iterator<Item = Perms> {
+        Perms::CHARS
+            .into_iter()
+            .map(|(_, perm)| perm)
+            .filter(move |perm| self.contains(*perm))
+    }
 }
 
 impl From<Perms> for i32 {
     fn from(perms: Perms) -> Self {
-        match perms {
-            Perms::Read => 1,
-            Perms::Write => 2,
-            Perms::Create => 4,
-            Perms::Delete => 8,
-            Perms::ACL => 16,
-            Perms::All => 31,
-        }
+        perms.0
+    }
+}
+
+impl From<i32> for Perms {
+    fn from(bits: i32) -> Self {
+        Perms(bits)
+    }
+}
+
+impl BitOr for Perms {
+    type Output = Perms;
+
+    fn bitor(self, rhs: Perms) -> Perms {
+        Perms(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for Perms {
+    fn bitor_assign(&mut self, rhs: Perms) {
+        self.0 |= rhs.0;
+    }
+}
+
+impl BitAnd for Perms {
+    type Output = Perms;
+
+    fn bitand(self, rhs: Perms) -> Perms {
+        Perms(self.0 & rhs.0)
+    }
+}
+
+impl BitAndAssign for Perms {
+    fn bitand_assign(&mut self, rhs: Perms) {
+        self.0 &= rhs.0;
+    }
+}
+
+impl Sub for Perms {
+    type Output = Perms;
+
+    fn sub(self, rhs: Perms) -> Perms {
+        Perms(self.0 & !rhs.0)
+    }
+}
+
+impl Not for Perms {
+    type Output = Perms;
+
+    fn not(self) -> Perms {
+        Perms(!self.0 & Perms::ALL.0)
     }
 }
 
 impl Display for Perms {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        let s = match self {
-            Perms::Read => "R",
-            Perms::Write => "W",
-            Perms::Create => "C",
-            Perms::Delete => "D",
-            Perms::ACL => "A",
-            Perms::All => "RWCDA",
-        };
-        f.write_str(s);
+        for (c, perm) in Perms::CHARS {
+            if self.contains(perm) {
+                write!(f, "{}", c)?;
+            }
+        }
         Ok(())
     }
 }
 
+impl Debug for Perms {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Perms({})", self)
+    }
+}
+
+impl FromStr for Perms {
+    type Err = ZKError;
+
+    /// 解析 `cdrwa` 格式的权限，不区分大小写，顺序任意
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut perms = Perms::NONE;
+        for c in s.chars() {
+            match Perms::CHARS
+                .iter()
+                .find(|(perm_char, _)| c.eq_ignore_ascii_case(perm_char))
+            {
+                Some((_, perm)) => perms |= *perm,
+                None => {
+                    return Err(ZKError::ACLError(
+                        s.to_string(),
+                        format!("Unknown perm type '{}'", c),
+                    ))
+                }
+            }
+        }
+        Ok(perms)
+    }
+}
+
 /// world scheme
 pub const WORLD: &str = "world";
 /// ip scheme
@@ -55,6 +185,12 @@ pub const IP: &str = "ip";
 pub const DIGEST: &str = "digest";
 /// super scheme
 pub const SUPER: &str = "super";
+/// auth scheme，表示创建者鉴权过的所有身份
+pub const AUTH: &str = "auth";
+/// x509 scheme，id 为客户端证书的 DN
+pub const X509: &str = "x509";
+/// sasl scheme，id 为 SASL 鉴权的用户名
+pub const SASL: &str = "sasl";
 /// World scheme 固定的 id
 pub const ANYONE: &str = "anyone";
 /// 忽略版本号，一般用于 set_data 或 delete
@@ -148,7 +284,7 @@ impl From<isize> for EventType {
 }
 
 /// 服务端的状态
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub enum KeeperState {
     /// 未连接
     Disconnected,
@@ -237,7 +373,9 @@ impl States {
     }
 }
 
-pub(crate) enum OpCode {
+/// 请求的操作类型，出错时会记录在 [`ZKError::OperationError`](crate::ZKError::OperationError) 中
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum OpCode {
     Notification,
     Create,
     Delete,
@@ -311,13 +449,52 @@ impl From<OpCode> for i32 {
     }
 }
 
+impl From<i32> for OpCode {
+    fn from(code: i32) -> Self {
+        match code {
+            0 => OpCode::Notification,
+            1 => OpCode::Create,
+            2 => OpCode::Delete,
+            3 => OpCode::Exists,
+            4 => OpCode::GetData,
+            5 => OpCode::SetData,
+            6 => OpCode::GetACL,
+            7 => OpCode::SetACL,
+            8 => OpCode::GetChildren,
+            9 => OpCode::Sync,
+            11 => OpCode::Ping,
+            12 => OpCode::GetChildren2,
+            13 => OpCode::Check,
+            14 => OpCode::Multi,
+            15 => OpCode::Create2,
+            16 => OpCode::ReConfig,
+            17 => OpCode::CheckWatches,
+            18 => OpCode::RemoveWatches,
+            19 => OpCode::CreateContainer,
+            20 => OpCode::DeleteContainer,
+            21 => OpCode::CreateTTL,
+            22 => OpCode::MultiRead,
+            100 => OpCode::Auth,
+            101 => OpCode::SetWatches,
+            102 => OpCode::Sasl,
+            103 => OpCode::GetEphemerals,
+            104 => OpCode::GetAllChildrenNumber,
+            105 => OpCode::SetWatches2,
+            106 => OpCode::AddWatch,
+            -10 => OpCode::CreateSession,
+            -11 => OpCode::CloseSession,
+            _ => OpCode::Error,
+        }
+    }
+}
+
 pub enum OpKind {
     Transaction,
     Read,
 }
 
 /// 创建的节点类型
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub enum CreateMode {
     /// 持久节点
     Persistent,
@@ -339,6 +516,24 @@ impl CreateMode {
     pub fn is_container(&self) -> bool {
         self.eq(&CreateMode::Container)
     }
+
+    /// 是否为顺序节点，服务端会在节点名称后添加自增序号
+    pub fn is_sequential(&self) -> bool {
+        matches!(
+            self,
+            CreateMode::PersistentSequential
+                | CreateMode::EphemeralSequential
+                | CreateMode::PersistentSequentialWithTTL
+        )
+    }
+
+    /// 是否为临时节点
+    pub fn is_ephemeral(&self) -> bool {
+        matches!(
+            self,
+            CreateMode::Ephemeral | CreateMode::EphemeralSequential
+        )
+    }
 }
 
 impl From<CreateMode> for i32 {
@@ -403,6 +598,45 @@ impl From<isize> for XidType {
 mod test {
     use super::*;
 
+    #[test]
+    fn test_perms() {
+        let mut perms = Perms::READ | Perms::WRITE;
+        assert!(perms.contains(Perms::READ));
+        assert!(!perms.contains(Perms::READ | Perms::ADMIN));
+        assert!(perms.intersects(Perms::READ | Perms::ADMIN));
+        perms.insert(Perms::CREATE);
+        perms.remove(Perms::WRITE);
+        assert_eq!(perms, Perms::CREATE | Perms::READ);
+        assert_eq!(i32::from(perms), 5);
+        assert_eq!(Perms::ALL - Perms::ADMIN, Perms::from(15));
+        assert_eq!(!Perms::READ, Perms::from(30));
+        assert_eq!(
+            Perms::ALL.iter().collect::<Vec<_>>(),
+            vec![
+                Perms::CREATE,
+                Perms::DELETE,
+                Perms::READ,
+                Perms::WRITE,
+                Perms::ADMIN
+            ]
+        );
+    }
+
+    #[test]
+    fn test_perms_string() {
+        assert_eq!(Perms::ALL.to_string(), "cdrwa");
+        assert_eq!((Perms::ADMIN | Perms::READ).to_string(), "ra");
+        assert_eq!(Perms::NONE.to_string(), "");
+        assert_eq!("awrdc".parse::<Perms>().unwrap(), Perms::ALL);
+        assert_eq!("RW".parse::<Perms>().unwrap(), Perms::READ | Perms::WRITE);
+        assert_eq!("".parse::<Perms>().unwrap(), Perms::NONE);
+        assert!("rx".parse::<Perms>().is_err());
+        for bits in 0..32 {
+            let perms = Perms::from(bits);
+            assert_eq!(perms.to_string().parse::<Perms>().unwrap(), perms);
+        }
+    }
+
     #[test]
     fn test_as() {
         let code: i32 = WatcherType::Any.into();
@@ -1,9 +1,10 @@
 use std::fmt::{Display, Formatter};
-use std::io::Error;
 
-use cmd_lib::log::SetLoggerError;
 use thiserror::Error;
 
+use crate::codec::CodecError;
+use crate::constants::OpCode;
+
 #[derive(Debug)]
 pub enum ServerInfo {
     Host,
@@ -23,6 +24,21 @@ impl Display for ServerInfo {
     }
 }
 
+/// 客户端的错误类型
+///
+/// 所有发给服务端的请求失败时都会被包装成 [`ZKError::OperationError`]，其中记录了操作类型和路径，
+/// 原始错误可以通过 [`std::error::Error::source`] 获取。判断错误种类时请使用 [`ZKError::code`]、
+/// [`ZKError::is_retryable`] 等方法，它们会穿透 `OperationError` 检查原始错误
+/// # Examples
+///
+/// ```rust,ignore
+/// match zk.delete("/your/path").await {
+///     Ok(()) => {}
+///     Err(e) if e.code() == Some(ServerErrorCode::NoNode) => {}
+///     Err(e) if e.is_retryable() => { /* 稍后重试 */ }
+///     Err(e) => return Err(e),
+/// }
+/// ```
 #[derive(Error, Debug)]
 pub enum ZKError {
     /// ArgumentError
@@ -33,36 +49,172 @@ pub enum ZKError {
     #[error("NetworkError detail : {0}")]
     NetworkError(String),
 
+    #[error("The ACL `{0}` is not correct [{1}]")]
+    ACLError(String, String),
+    #[error("SASL authentication failed : {0}")]
+    SaslError(String),
+    #[error("TLS error : {0}")]
+    TlsError(String),
+
+    /// 节点数据编码失败，参考 [`crate::Codec`]
+    #[error("Encode data of `{path}` with {codec} failed : {source}")]
+    EncodeError {
+        path: String,
+        codec: &'static str,
+        source: CodecError,
+    },
+    /// 节点数据解码失败，参考 [`crate::Codec`]
+    #[error("Decode data of `{path}` with {codec} failed : {source}")]
+    DecodeError {
+        path: String,
+        codec: &'static str,
+        source: CodecError,
+    },
+
+    /// 递归删除时子树的节点数超过限制，参考 [`crate::DeleteOptions::max_nodes`]
+    #[error("Subtree of `{path}` has more than {limit} nodes")]
+    TooManyNodes { path: String, limit: usize },
+
+    /// 释放当前对象没有持有的锁，参考 [`crate::recipes::lock::InterProcessMutex::release`]
+    #[error("`{path}` is not held by this handle")]
+    NotHeld { path: String },
+
+    /// 共享计数节点的数据不是非负的十进制整数，参考 [`crate::recipes::semaphore::InterProcessSemaphore::with_shared_count`]
+    #[error("Data of `{path}` is not a valid count [{data}]")]
+    InvalidCount { path: String, data: String },
+
+    /// 一次获取的租约数量为 0 或者超过信号量的租约上限，参考 [`crate::recipes::semaphore::InterProcessSemaphore::acquire_many`]
+    #[error(
+        "Can't acquire {count} leases of `{path}`, count must be between 1 and the max leases"
+    )]
+    InvalidLeaseCount { path: String, count: usize },
+
     #[error("Parse protocol occur error")]
     ProtocolParseError,
 
     #[error("UnknownError")]
     UnknownError,
 
+    #[error("IO error : {0}")]
+    IoError(#[from] std::io::Error),
+
     #[error("Received error from ZooKeeper server message is {0}, error code is {1}")]
     ServerError(ServerErrorCode, i32),
+
+    /// 请求失败时携带的上下文，`path` 为用户传入的路径，不包含 chroot
+    #[error("Operation {op:?} on `{path}` failed : {source}")]
+    OperationError {
+        op: OpCode,
+        path: String,
+        source: Box<ZKError>,
+    },
 }
 
-impl<T> From<tokio::sync::mpsc::error::SendError<T>> for ZKError {
-    fn from(e: tokio::sync::mpsc::error::SendError<T>) -> Self {
-        ZKError::NetworkError(e.to_string())
+impl ZKError {
+    /// 为错误附加操作类型和路径，已经包装过的错误不会重复包装
+    pub(crate) fn with_operation(self, op: OpCode, path: &str) -> ZKError {
+        match self {
+            e @ ZKError::OperationError { .. } => e,
+            e => ZKError::OperationError {
+                op,
+                path: path.to_string(),
+                source: Box::new(e),
+            },
+        }
+    }
+
+    /// 去掉 [`ZKError::OperationError`] 包装后的原始错误
+    pub fn root(&self) -> &ZKError {
+        match self {
+            ZKError::OperationError { source, .. } => source.root(),
+            e => e,
+        }
+    }
+
+    /// 服务端返回的错误码，非服务端错误时返回 `None`
+    pub fn code(&self) -> Option<ServerErrorCode> {
+        match self.root() {
+            ZKError::ServerError(code, _) => Some(*code),
+            _ => None,
+        }
+    }
+
+    /// 失败的操作类型
+    pub fn op(&self) -> Option<OpCode> {
+        match self {
+            ZKError::OperationError { op, .. } => Some(*op),
+            _ => None,
+        }
     }
-}
 
-impl From<log::SetLoggerError> for ZKError {
-    fn from(_: SetLoggerError) -> Self {
-        ZKError::UnknownError
+    /// 失败操作的目标路径
+    pub fn path(&self) -> Option<&str> {
+        match self {
+            ZKError::OperationError { path, .. }
+            | ZKError::EncodeError { path, .. }
+            | ZKError::DecodeError { path, .. }
+            | ZKError::TooManyNodes { path, .. }
+            | ZKError::NotHeld { path }
+            | ZKError::InvalidCount { path, .. }
+            | ZKError::InvalidLeaseCount { path, .. }
+            | ZKError::PathError(path, _) => Some(path),
+            _ => None,
+        }
+    }
+
+    /// 与服务端的连接断开或者建立连接失败，会话可能仍然有效
+    pub fn is_connection_loss(&self) -> bool {
+        match self.root() {
+            ZKError::ServerError(code, _) => *code == ServerErrorCode::ConnectionLoss,
+            ZKError::NetworkError(_) | ZKError::IoError(_) => true,
+            _ => false,
+        }
+    }
+
+    /// 会话已经过期或者客户端已经关闭，需要重新创建客户端
+    pub fn is_session_expired(&self) -> bool {
+        self.code() == Some(ServerErrorCode::SessionExpired)
+    }
+
+    /// 节点状态与请求不符，例如节点不存在、已存在、版本不匹配等，重试不会改变结果
+    pub fn is_node_error(&self) -> bool {
+        matches!(
+            self.code(),
+            Some(
+                ServerErrorCode::NoNode
+                    | ServerErrorCode::NodeExists
+                    | ServerErrorCode::NotEmpty
+                    | ServerErrorCode::BadVersion
+                    | ServerErrorCode::NoChildrenForEphemerals
+            )
+        )
+    }
+
+    /// 暂时性的错误，稍后重试可能成功
+    pub fn is_retryable(&self) -> bool {
+        self.is_connection_loss()
+            || matches!(
+                self.code(),
+                Some(
+                    ServerErrorCode::OperationTimeout
+                        | ServerErrorCode::RequestTimeout
+                        | ServerErrorCode::SessionMoved
+                        | ServerErrorCode::ReConfigInProgress
+                        | ServerErrorCode::NewConfigNoQuorum
+                        | ServerErrorCode::Throttled
+                )
+            )
     }
 }
 
-impl From<std::io::Error> for ZKError {
-    fn from(_: Error) -> Self {
-        ZKError::UnknownError
+impl<T> From<tokio::sync::mpsc::error::SendError<T>> for ZKError {
+    fn from(e: tokio::sync::mpsc::error::SendError<T>) -> Self {
+        ZKError::NetworkError(e.to_string())
     }
 }
 
 /// repeekooz 常见错误
-#[derive(Error, Debug, Eq, PartialEq)]
+#[derive(Error, Debug, Clone, Copy, Eq, PartialEq, Hash)]
 pub enum ServerErrorCode {
     SystemError,
     RuntimeInconsistency,
@@ -93,6 +245,8 @@ pub enum ServerErrorCode {
     RequestTimeout,
     ReConfigDisabled,
     SessionClosedRequireSASLAuth,
+    QuotaExceeded,
+    Throttled,
 }
 
 impl Display for ServerErrorCode {
@@ -127,6 +281,8 @@ impl Display for ServerErrorCode {
             ServerErrorCode::RequestTimeout => "RequestTimeout",
             ServerErrorCode::ReConfigDisabled => "ReConfigDisabled",
             ServerErrorCode::SessionClosedRequireSASLAuth => "SessionClosedRequireSASLAuth",
+            ServerErrorCode::QuotaExceeded => "QuotaExceeded",
+            ServerErrorCode::Throttled => "Throttled",
         })
     }
 }
@@ -169,6 +325,8 @@ impl From<isize> for ServerErrorCode {
             -122 => ServerErrorCode::RequestTimeout,
             -123 => ServerErrorCode::ReConfigDisabled,
             -124 => ServerErrorCode::SessionClosedRequireSASLAuth,
+            -125 => ServerErrorCode::QuotaExceeded,
+            -127 => ServerErrorCode::Throttled,
             _ => ServerErrorCode::SystemError,
         }
     }
@@ -176,6 +334,55 @@ impl From<isize> for ServerErrorCode {
 
 #[cfg(test)]
 mod test {
+    use std::error::Error;
+    use std::io;
+
+    use super::*;
+
+    fn server_error(code: i32) -> ZKError {
+        ZKError::ServerError(ServerErrorCode::from(code), code)
+    }
+
     #[test]
-    fn test_error() {}
+    fn test_error() {
+        let e = server_error(-101).with_operation(OpCode::Delete, "/a");
+        assert_eq!(e.code(), Some(ServerErrorCode::NoNode));
+        assert_eq!(e.op(), Some(OpCode::Delete));
+        assert_eq!(e.path(), Some("/a"));
+        assert!(e.is_node_error());
+        assert!(!e.is_retryable());
+        assert_eq!(
+            e.to_string(),
+            "Operation Delete on `/a` failed : Received error from ZooKeeper server message is NoNode, error code is -101"
+        );
+        // 不会重复包装
+        let e = e.with_operation(OpCode::GetData, "/b");
+        assert_eq!(e.op(), Some(OpCode::Delete));
+        assert!(matches!(e.root(), ZKError::ServerError(_, -101)));
+    }
+
+    #[test]
+    fn test_io_source() {
+        let e = ZKError::from(io::Error::new(io::ErrorKind::ConnectionReset, "reset"))
+            .with_operation(OpCode::SetData, "/a");
+        assert!(e.is_connection_loss());
+        assert!(e.is_retryable());
+        assert_eq!(e.code(), None);
+        let source = e.source().unwrap().source().unwrap();
+        let io = source.downcast_ref::<io::Error>().unwrap();
+        assert_eq!(io.kind(), io::ErrorKind::ConnectionReset);
+    }
+
+    #[test]
+    fn test_classification() {
+        assert!(server_error(-4).is_connection_loss());
+        assert!(server_error(-112).is_session_expired());
+        assert!(!server_error(-112).is_retryable());
+        assert!(server_error(-7).is_retryable());
+        assert!(server_error(-127).is_retryable());
+        assert!(server_error(-103).is_node_error());
+        assert!(!server_error(-102).is_retryable());
+        assert!(!ZKError::PathError("a".into(), "b".into()).is_retryable());
+        assert_eq!(ServerErrorCode::from(-125), ServerErrorCode::QuotaExceeded);
+    }
 }
@@ -0,0 +1,201 @@
+//! # 服务端地址模块
+//! 解析连接字符串，并决定每一次（重新）连接时使用哪一个服务端
+
+use std::fmt::Debug;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use crate::error::ServerInfo;
+use crate::{ZKError, ZKResult};
+
+/// 服务端地址提供者，实现该 trait 即可自定义选择服务端的策略
+pub trait HostProvider: Debug + Send + Sync {
+    /// 服务端的数量
+    fn size(&self) -> usize;
+
+    /// 下一次连接使用的服务端地址，格式为 "host:port"，主机名在连接时解析
+    fn next(&mut self) -> String;
+
+    /// 成功连接到某个服务端后回调
+    fn on_connected(&mut self, _host: &str) {}
+}
+
+/// 默认的服务端地址提供者，按顺序轮流使用连接字符串中的服务端
+#[derive(Debug)]
+pub struct StaticHostProvider {
+    server_list: Vec<String>,
+    current_index: usize,
+}
+
+impl StaticHostProvider {
+    /// 创建服务端地址提供者
+    /// # Args
+    /// - `server_list`： 服务端地址列表，每个地址的格式为 "ip:port"、"hostname:port" 或者 "[ipv6]:port"
+    /// # Errors
+    ///
+    /// 地址列表为空或者地址格式有问题将会返回异常
+    pub fn new(server_list: Vec<String>) -> ZKResult<StaticHostProvider> {
+        if server_list.is_empty() {
+            return Err(ZKError::ServerInfoError(
+                ServerInfo::Host,
+                "Host list can't be empty".into(),
+            ));
+        }
+        for host in server_list.iter() {
+            validate_host(host)?;
+        }
+        Ok(StaticHostProvider {
+            server_list,
+            current_index: 0,
+        })
+    }
+}
+
+impl HostProvider for StaticHostProvider {
+    fn size(&self) -> usize {
+        self.server_list.len()
+    }
+
+    fn next(&mut self) -> String {
+        let address = self.server_list.get(self.current_index).unwrap().clone();
+        let mut new_index = self.current_index + 1;
+        if new_index == self.server_list.len() {
+            new_index = 0;
+        }
+        self.current_index = new_index;
+
+        address
+    }
+}
+
+/// 校验服务端地址，支持 "ip:port"、"hostname:port" 和 "[ipv6]:port"，主机名在每次连接时解析
+fn validate_host(host: &str) -> ZKResult<()> {
+    let (name, port) = match host.rsplit_once(':') {
+        Some(name_port) => name_port,
+        None => {
+            return Err(ZKError::ServerInfoError(
+                ServerInfo::Host,
+                "Host Address format must be 'host:port'".into(),
+            ))
+        }
+    };
+    if port.parse::<u16>().is_err() {
+        return Err(ZKError::ServerInfoError(
+            ServerInfo::Port,
+            "Port must be number and less than 65535".into(),
+        ));
+    }
+    if let Some(ipv6) = name
+        .strip_prefix('[')
+        .and_then(|name| name.strip_suffix(']'))
+    {
+        return match ipv6.parse::<Ipv6Addr>() {
+            Ok(_) => Ok(()),
+            Err(_) => Err(ZKError::ServerInfoError(
+                ServerInfo::Ip,
+                "Invalid ipv6 address".into(),
+            )),
+        };
+    }
+    // 只由数字和点组成时按照 IPv4 地址校验
+    if name.chars().all(|c| c.is_ascii_digit() || c == '.') {
+        return match name.parse::<Ipv4Addr>() {
+            Ok(_) => Ok(()),
+            Err(_) => Err(ZKError::ServerInfoError(
+                ServerInfo::Ip,
+                "ip address must be 4 numbers between 0 and 255".into(),
+            )),
+        };
+    }
+    if !is_hostname(name) {
+        return Err(ZKError::ServerInfoError(
+            ServerInfo::Host,
+            format!(
+                "Invalid hostname '{}', ipv6 address must be like '[::1]:port'",
+                name
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// 主机名由若干个 1 到 63 位的标签组成，标签只能包含字母、数字和 '-'，并且不能以 '-' 开头或结尾
+fn is_hostname(name: &str) -> bool {
+    let name = name.strip_suffix('.').unwrap_or(name);
+    !name.is_empty()
+        && name.len() <= 253
+        && name.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+}
+
+/// 解析连接字符串 "host1:port1,host2:port2,host3:port3.../chroot"，返回服务端列表和 chroot
+pub(crate) fn parse_connect_string(connect_string: &str) -> ZKResult<(Vec<String>, String)> {
+    let (hosts, chroot) = split_chroot(connect_string)?;
+    let mut server_list = Vec::new();
+    for add in hosts.split(',') {
+        validate_host(add)?;
+        server_list.push(add.to_string());
+    }
+    Ok((server_list, chroot))
+}
+
+/// 拆分连接字符串中的服务端部分和 chroot，不校验服务端地址
+pub(crate) fn split_chroot(connect_string: &str) -> ZKResult<(&str, String)> {
+    let split_chroot = connect_string.split('/').collect::<Vec<&str>>();
+    if split_chroot.len() > 2 {
+        return Err(ZKError::ServerInfoError(
+            ServerInfo::Chroot,
+            "chroot format must be like 'ip:port/chroot'".into(),
+        ));
+    }
+    let mut chroot = String::from('/');
+    if split_chroot.len() == 2 {
+        chroot = '/'.to_string() + *split_chroot.get(1).unwrap();
+    }
+    Ok((split_chroot.first().unwrap(), chroot))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_connect_string() {
+        let (server_list, chroot) =
+            parse_connect_string("127.0.0.1:2181,127.0.0.1:2182/app").unwrap();
+        assert_eq!(server_list, vec!["127.0.0.1:2181", "127.0.0.1:2182"]);
+        assert_eq!(chroot, "/app");
+
+        let mut provider = StaticHostProvider::new(server_list).unwrap();
+        assert_eq!(provider.next(), "127.0.0.1:2181");
+        assert_eq!(provider.next(), "127.0.0.1:2182");
+        assert_eq!(provider.next(), "127.0.0.1:2181");
+
+        assert!(parse_connect_string("127.0.0.1").is_err());
+        assert!(parse_connect_string("127.0.0.1:65536").is_err());
+        assert!(StaticHostProvider::new(vec![]).is_err());
+    }
+
+    #[test]
+    fn test_validate_host() {
+        let (server_list, chroot) =
+            parse_connect_string("zk1.example.com:2281,[::1]:2181/app").unwrap();
+        assert_eq!(server_list, vec!["zk1.example.com:2281", "[::1]:2181"]);
+        assert_eq!(chroot, "/app");
+        assert!(validate_host("localhost:2181").is_ok());
+        assert!(validate_host("zk-1.internal.:2181").is_ok());
+
+        assert!(validate_host("256.0.0.1:2181").is_err());
+        assert!(validate_host("127.0.0:2181").is_err());
+        assert!(validate_host("::1:2181").is_err());
+        assert!(validate_host("[::g]:2181").is_err());
+        assert!(validate_host("-zk.example.com:2181").is_err());
+        assert!(validate_host("zk_1:2181").is_err());
+        assert!(validate_host(":2181").is_err());
+        assert!(validate_host("zk1.example.com").is_err());
+    }
+}
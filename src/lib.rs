@@ -48,23 +48,67 @@ extern crate lazy_static;
 extern crate log;
 
 // re-export, 常用的结构体全部都要导出，使得用户可以直接通过 repeekooz 直接引用
+pub use acl::{EffectivePerms, PermDecision, PermissionEvaluator};
 pub use api::ZooKeeper;
-pub use constants::{AddWatchMode, CreateMode, EventType, KeeperState, States, WatcherType};
-pub use error::ZKError;
-pub use protocol::req::{Scheme, ACL};
-pub use protocol::resp::Stat;
+pub use blob::BlobOptions;
+pub use builder::ZooKeeperBuilder;
+#[cfg(feature = "bincode")]
+pub use codec::BincodeCodec;
+#[cfg(feature = "json")]
+pub use codec::JsonCodec;
+#[cfg(feature = "toml")]
+pub use codec::TomlCodec;
+pub use codec::{Codec, CodecError};
+pub use compress::Compression;
+pub use constants::{
+    AddWatchMode, CreateMode, EventType, KeeperState, OpCode, Perms, States, WatcherType,
+};
+pub use error::{ServerErrorCode, ZKError};
+pub use host::{HostProvider, StaticHostProvider};
+pub use paths::ZkPath;
+pub use protocol::req::{Op, Scheme, ACL, CREATOR_ALL_ACL, OPEN_ACL_UNSAFE, READ_ACL_UNSAFE};
+pub use protocol::resp::{OpResult, Stat};
+pub use recursive::{DeleteOptions, ParentOptions};
+pub use retry::{
+    ExponentialBackoffRetry, RetryForever, RetryNTimes, RetryPolicy, RetryUntilElapsed,
+};
+pub use sasl::SaslConfig;
+pub use sequential::SequentialName;
+pub use state::{ConnectionState, StateEvent, StateListener};
+#[cfg(feature = "tls")]
+pub use tls::TlsConfig;
+pub use update::UpdateOptions;
+pub use walk::{WalkEntry, WalkOptions, WalkOrder};
 pub use watcher::{WatchedEvent, Watcher};
 
 use anyhow::Result;
 
+mod acl;
 mod api;
+mod blob;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+mod builder;
 mod client;
+mod codec;
+mod compress;
 mod constants;
 mod error;
+mod host;
 mod metric;
 mod paths;
 mod protocol;
-mod recipes;
+pub mod recipes;
+mod recursive;
+mod retry;
+mod sasl;
+mod sequential;
+mod state;
+#[cfg(feature = "tls")]
+mod tls;
+mod transport;
+mod update;
+mod walk;
 mod watcher;
 
 pub type ZKResult<T> = Result<T, ZKError>;
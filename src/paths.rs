@@ -1,53 +1,284 @@
+//! # 路径模块
+//! 校验规则与 Java 客户端的 `PathUtils` 一致，[`ZkPath`] 是校验过的路径，并提供常用的路径操作
+
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
 use crate::{ZKError, ZKResult};
 
+fn path_error(client_path: &str, reason: impl Into<String>) -> ZKError {
+    ZKError::PathError(client_path.into(), reason.into())
+}
+
+/// 非法字符：控制字符、私有区字符和非字符
+fn is_invalid_char(c: char) -> bool {
+    matches!(c,
+        '\u{1}'..='\u{1f}'
+        | '\u{7f}'..='\u{9f}'
+        | '\u{e000}'..='\u{f8ff}'
+        | '\u{fff0}'..='\u{ffff}')
+}
+
 pub(crate) fn validate_path(client_path: &str) -> ZKResult<()> {
     if client_path.is_empty() {
-        return Err(ZKError::PathError(
-            client_path.into(),
-            "Path can't be empty".into(),
-        ));
+        return Err(path_error(client_path, "Path can't be empty"));
     }
     if !client_path.starts_with('/') {
-        return Err(ZKError::PathError(
-            client_path.into(),
-            "Path must start with '/'".into(),
-        ));
+        return Err(path_error(client_path, "Path must start with '/'"));
     }
     if client_path == "/" {
         return Ok(());
     }
     if client_path.ends_with('/') {
-        return Err(ZKError::PathError(
-            client_path.into(),
-            "Path must not end with '/'".into(),
-        ));
+        return Err(path_error(client_path, "Path must not end with '/'"));
+    }
+    for (i, segment) in client_path[1..].split('/').enumerate() {
+        match segment {
+            "" => {
+                return Err(path_error(
+                    client_path,
+                    format!("Empty node name specified at segment {}", i),
+                ))
+            }
+            "." | ".." => {
+                return Err(path_error(client_path, "Relative paths not allowed"));
+            }
+            _ => {}
+        }
+    }
+    if let Some((i, c)) = client_path
+        .char_indices()
+        .find(|(_, c)| *c == '\u{0}' || is_invalid_char(*c))
+    {
+        let reason = if c == '\u{0}' {
+            format!("Null character not allowed @{}", i)
+        } else {
+            format!("Invalid character @{}", i)
+        };
+        return Err(path_error(client_path, reason));
     }
+    Ok(())
+}
 
-    // TODO 具体的非法字符
-    // if (c > 'U+0000' && c <= '\u001f'
-    //     || c >= '\u007f' && c <= '\u009F'
-    //     || c >= '\ud800' && c <= '\uf8ff'
-    //     || c >= '\ufff0' && c <= '\uffff') {
-    //     reason = "invalid character @" + i;
-    //     break;
+/// 拆分出父节点路径和节点名称，根节点的父节点为 "/"
+pub(crate) fn split(path: &str) -> (&str, &str) {
+    match path.rsplit_once('/') {
+        Some(("", name)) => ("/", name),
+        Some((parent, name)) => (parent, name),
+        None => ("/", path),
+    }
+}
 
-    Ok(())
+/// 拼接父节点路径和子节点名称
+pub(crate) fn join(parent: &str, child: &str) -> String {
+    if parent.ends_with('/') {
+        format!("{}{}", parent, child)
+    } else {
+        format!("{}/{}", parent, child)
+    }
+}
+
+/// 校验过的 ZooKeeper 节点路径，所有接受路径的 API 都可以直接传入
+/// # Examples
+///
+/// ```rust,ignore
+/// let app = ZkPath::new("/app")?;
+/// let config = app.join("config/db")?;
+/// assert_eq!(config.as_str(), "/app/config/db");
+/// assert_eq!(config.parent().unwrap().as_str(), "/app/config");
+/// assert_eq!(config.file_name(), Some("db"));
+/// assert_eq!(config.strip_prefix(&app), Some("config/db"));
+/// assert!(app.is_ancestor_of(&config));
+/// let data = zk.get(&config, None).await?;
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct ZkPath(String);
+
+impl ZkPath {
+    /// 校验并创建路径
+    /// # Errors
+    ///
+    /// 路径不合法时返回 [`ZKError::PathError`]
+    pub fn new(path: impl Into<String>) -> ZKResult<ZkPath> {
+        let path = path.into();
+        validate_path(&path)?;
+        Ok(ZkPath(path))
+    }
+
+    /// 根节点 "/"
+    pub fn root() -> ZkPath {
+        ZkPath("/".to_string())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.0 == "/"
+    }
+
+    /// 拼接相对路径，`child` 可以包含多级，例如 "a/b"
+    /// # Errors
+    ///
+    /// 拼接后的路径不合法时返回 [`ZKError::PathError`]，例如 `child` 以 "/" 开头
+    pub fn join(&self, child: &str) -> ZKResult<ZkPath> {
+        ZkPath::new(join(&self.0, child))
+    }
+
+    /// 父节点路径，根节点没有父节点
+    pub fn parent(&self) -> Option<ZkPath> {
+        if self.is_root() {
+            return None;
+        }
+        Some(ZkPath(split(&self.0).0.to_string()))
+    }
+
+    /// 节点名称，根节点没有名称
+    pub fn file_name(&self) -> Option<&str> {
+        if self.is_root() {
+            return None;
+        }
+        Some(split(&self.0).1)
+    }
+
+    /// 从自身开始依次返回自身、父节点、祖父节点，直到根节点
+    pub fn ancestors(&self) -> impl Iterator<Item = ZkPath> {
+        std::iter::successors(Some(self.clone()), ZkPath::parent)
+    }
+
+    /// 去掉 `base` 前缀后的相对路径，`base` 不是自身或者祖先节点时返回 `None`
+    pub fn strip_prefix(&self, base: &ZkPath) -> Option<&str> {
+        if base.is_root() {
+            return Some(&self.0[1..]);
+        }
+        match self.0.strip_prefix(base.as_str())? {
+            "" => Some(""),
+            rest => rest.strip_prefix('/'),
+        }
+    }
+
+    /// 是否为 `other` 的祖先节点，节点不是自身的祖先
+    pub fn is_ancestor_of(&self, other: &ZkPath) -> bool {
+        self != other && other.strip_prefix(self).is_some()
+    }
+}
+
+impl Display for ZkPath {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl AsRef<str> for ZkPath {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl FromStr for ZkPath {
+    type Err = ZKError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ZkPath::new(s)
+    }
+}
+
+impl TryFrom<&str> for ZkPath {
+    type Error = ZKError;
+
+    fn try_from(path: &str) -> Result<Self, Self::Error> {
+        ZkPath::new(path)
+    }
+}
+
+impl TryFrom<String> for ZkPath {
+    type Error = ZKError;
+
+    fn try_from(path: String) -> Result<Self, Self::Error> {
+        ZkPath::new(path)
+    }
+}
+
+impl From<ZkPath> for String {
+    fn from(path: ZkPath) -> Self {
+        path.0
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::paths::validate_path;
+    use crate::paths::{join, split, validate_path, ZkPath};
+
+    #[test]
+    fn test_split_and_join() {
+        assert_eq!(split("/a/b"), ("/a", "b"));
+        assert_eq!(split("/a"), ("/", "a"));
+        assert_eq!(join("/", "a"), "/a");
+        assert_eq!(join("/a", "b"), "/a/b");
+    }
 
     #[test]
     fn test_validate_path() {
-        if let Err(e) = validate_path("") {
-            println!("{}", e);
-        }
-        if let Err(e) = validate_path("123") {
-            println!("{}", e);
+        for path in ["/", "/a", "/a/b.c", "/a/..b", "/a/.b/c", "/中文", "/a b"] {
+            assert!(validate_path(path).is_ok(), "{}", path);
         }
-        if let Err(e) = validate_path("/123/") {
-            println!("{}", e);
+        for path in [
+            "",
+            "123",
+            "/123/",
+            "//",
+            "/a//b",
+            "/a/.",
+            "/a/./b",
+            "/..",
+            "/a/../b",
+            "/a\u{0}",
+            "/a\u{1f}",
+            "/a\u{85}",
+            "/a\u{e000}",
+            "/a\u{fffe}",
+        ] {
+            assert!(validate_path(path).is_err(), "{:?}", path);
         }
+        let e = validate_path("/a\u{7f}b").unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "The path `/a\u{7f}b` that you provide is not legitimate [Invalid character @2]"
+        );
+    }
+
+    #[test]
+    fn test_zk_path() {
+        let root = ZkPath::root();
+        let app = ZkPath::new("/app").unwrap();
+        let db = app.join("config/db").unwrap();
+        assert_eq!(db.as_str(), "/app/config/db");
+        assert!(app.join("/config").is_err());
+        assert!(app.join("..").is_err());
+
+        assert_eq!(db.parent(), Some(ZkPath::new("/app/config").unwrap()));
+        assert_eq!(app.parent(), Some(root.clone()));
+        assert_eq!(root.parent(), None);
+        assert_eq!(db.file_name(), Some("db"));
+        assert_eq!(root.file_name(), None);
+        let ancestors: Vec<String> = db.ancestors().map(String::from).collect();
+        assert_eq!(ancestors, ["/app/config/db", "/app/config", "/app", "/"]);
+
+        assert_eq!(db.strip_prefix(&app), Some("config/db"));
+        assert_eq!(db.strip_prefix(&root), Some("app/config/db"));
+        assert_eq!(app.strip_prefix(&app), Some(""));
+        assert_eq!(ZkPath::new("/apple").unwrap().strip_prefix(&app), None);
+        assert!(app.is_ancestor_of(&db));
+        assert!(root.is_ancestor_of(&app));
+        assert!(!app.is_ancestor_of(&app));
+        assert!(!db.is_ancestor_of(&app));
+
+        assert_eq!("/app".parse::<ZkPath>().unwrap(), app);
+        assert!(ZkPath::try_from("app").is_err());
+        assert_eq!(
+            ZkPath::try_from("/app".to_string()).unwrap().to_string(),
+            "/app"
+        );
     }
 }
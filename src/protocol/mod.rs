@@ -42,6 +42,14 @@ pub trait Serializer: Send {
         b.put_slice(s.as_bytes());
     }
 
+    /// 与 `write_vec` 不同，空列表写入长度 0 而不是 -1，服务端不接受 null 的路径列表
+    fn write_string_vec(&self, v: &[String], b: &mut BytesMut) {
+        self.write_len(v.len(), b);
+        for s in v.iter() {
+            self.write_string(s, b);
+        }
+    }
+
     fn write_len(&self, i: usize, b: &mut BytesMut) {
         b.put_u32(i as u32);
     }
@@ -1,13 +1,18 @@
-use std::net::{IpAddr, Ipv4Addr};
+use std::fmt::{Display, Formatter};
+use std::net::IpAddr;
 use std::str::FromStr;
 
+use base64::prelude::*;
 use bytes::BytesMut;
+use lazy_static::lazy_static;
+use sha1::{Digest, Sha1};
 
 use crate::constants::{
-    AddWatchMode, CreateMode, OpCode, Perms, VersionType, ANYONE, DIGEST, IP, WORLD,
+    AddWatchMode, CreateMode, OpCode, Perms, VersionType, ANYONE, AUTH, DIGEST, IP, SASL, WORLD,
+    X509,
 };
 use crate::protocol::{Deserializer, Serializer};
-use crate::{WatcherType, ZKResult};
+use crate::{WatcherType, ZKError, ZKResult};
 
 #[derive(Debug, Default)]
 pub(crate) struct RequestHeader {
@@ -60,6 +65,23 @@ impl ConnectRequest {
             read_only: false,
         }
     }
+
+    pub(crate) fn new_full(
+        last_zxid_seen: i64,
+        session_timeout: u32,
+        session_id: i64,
+        passwd: Option<Vec<u8>>,
+        read_only: bool,
+    ) -> Self {
+        ConnectRequest {
+            protocol_version: 0,
+            last_zxid_seen,
+            time_out: session_timeout,
+            session_id,
+            passwd,
+            read_only,
+        }
+    }
 }
 
 impl Serializer for ConnectRequest {
@@ -73,64 +95,136 @@ impl Serializer for ConnectRequest {
         Ok(())
     }
 }
-/// ZK 内置的 3 种 scheme
-/// 第 4 种 Super 其实就是特殊的 Digest
-#[derive(Debug, Eq, PartialEq)]
+/// ZK 内置的 scheme
+/// 第 7 种 Super 其实就是特殊的 Digest
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub enum Scheme {
+    /// 任何人，id 固定为 `anyone`
     World,
-    IP(IpAddr),
-    // TODO 拆分成加密前的用户名密码，两个字段
+    /// 创建者鉴权过的所有身份，服务端在创建节点时替换为具体的身份，id 一般为空
+    Auth(String),
+    /// 用户名以及加密后的密码，格式为 `user:base64(sha1(user:password))`，可以通过 [`Scheme::digest`] 生成
     Digest(String),
-}
+    /// 客户端 IP，可以带上 CIDR 前缀长度，例如 `10.0.0.0/8`、`2001:db8::/32`
+    IP(IpAddr, Option<u8>),
+    /// 客户端证书的 DN，需要使用 TLS 双向认证
+    X509(String),
+    /// SASL 鉴权的用户名
+    Sasl(String),
+    /// 服务端自定义的鉴权插件，依次为 scheme 和 id
+    Other(String, String),
+}
+
+impl Scheme {
+    /// 根据 scheme 和 id 创建，IP 格式有误时返回异常
+    /// # Examples
+    ///
+    /// ```rust,ignore
+    /// let scheme = Scheme::new("ip", "10.0.0.0/8")?;
+    /// assert_eq!(scheme, Scheme::IP("10.0.0.0".parse().unwrap(), Some(8)));
+    /// ```
+    pub fn new(scheme: &str, id: &str) -> ZKResult<Scheme> {
+        Ok(match scheme {
+            WORLD if id == ANYONE => Scheme::World,
+            AUTH => Scheme::Auth(id.to_string()),
+            DIGEST => Scheme::Digest(id.to_string()),
+            IP => {
+                let (addr, prefix) = parse_ip(id).ok_or_else(|| {
+                    ZKError::ACLError(format!("{}:{}", scheme, id), "Invalid ip".into())
+                })?;
+                Scheme::IP(addr, prefix)
+            }
+            X509 => Scheme::X509(id.to_string()),
+            SASL => Scheme::Sasl(id.to_string()),
+            _ => Scheme::Other(scheme.to_string(), id.to_string()),
+        })
+    }
 
-impl From<(String, String)> for Scheme {
-    fn from((scheme, id): (String, String)) -> Self {
-        match scheme.as_str() {
-            WORLD => Scheme::World,
-            IP => Scheme::IP(IpAddr::V4(Ipv4Addr::from_str(id.as_str()).unwrap())),
-            DIGEST => Scheme::Digest(id),
-            _ => panic!("Unknown Scheme : '{}' from server", scheme),
+    /// 使用用户名和明文密码生成 digest scheme
+    pub fn digest(user: &str, password: &str) -> Scheme {
+        Scheme::Digest(Scheme::generate_digest(&format!("{}:{}", user, password)))
+    }
+
+    /// 与 Java 的 `DigestAuthenticationProvider.generateDigest` 一致，
+    /// 把 `user:password` 转换成 `user:base64(sha1(user:password))`
+    pub fn generate_digest(id_password: &str) -> String {
+        let user = id_password.split(':').next().unwrap_or_default();
+        let digest = Sha1::digest(id_password.as_bytes());
+        format!("{}:{}", user, BASE64_STANDARD.encode(digest))
+    }
+
+    /// scheme 名称
+    pub fn scheme(&self) -> &str {
+        match self {
+            Scheme::World => WORLD,
+            Scheme::Auth(_) => AUTH,
+            Scheme::Digest(_) => DIGEST,
+            Scheme::IP(..) => IP,
+            Scheme::X509(_) => X509,
+            Scheme::Sasl(_) => SASL,
+            Scheme::Other(scheme, _) => scheme,
         }
     }
+
+    /// scheme 对应的 id
+    pub fn id(&self) -> String {
+        match self {
+            Scheme::World => ANYONE.to_string(),
+            Scheme::IP(addr, Some(prefix)) => format!("{}/{}", addr, prefix),
+            Scheme::IP(addr, None) => addr.to_string(),
+            Scheme::Auth(id)
+            | Scheme::Digest(id)
+            | Scheme::X509(id)
+            | Scheme::Sasl(id)
+            | Scheme::Other(_, id) => id.clone(),
+        }
+    }
+}
+
+impl Display for Scheme {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.scheme(), self.id())
+    }
+}
+
+/// 解析 `addr` 或者 `addr/prefix`，前缀长度不能超过地址的位数
+fn parse_ip(id: &str) -> Option<(IpAddr, Option<u8>)> {
+    let (addr, prefix) = match id.split_once('/') {
+        Some((addr, prefix)) => (addr, Some(prefix.parse::<u8>().ok()?)),
+        None => (id, None),
+    };
+    let addr = IpAddr::from_str(addr).ok()?;
+    let max = if addr.is_ipv4() { 32 } else { 128 };
+    match prefix {
+        Some(prefix) if prefix > max => None,
+        _ => Some((addr, prefix)),
+    }
 }
 
 /// ZooKeeper 权限对象
 /// - `perms`：权限
 /// - `scheme`：鉴权模式，详情可见 [`Scheme`]
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq)]
 pub struct ACL {
-    // TODO 该字段应该也是枚举对象或者其他有意义的类型，而不是 i32
-    pub perms: i32,
+    pub perms: Perms,
     pub scheme: Scheme,
 }
 
 impl Serializer for ACL {
     fn write(&self, b: &mut BytesMut) -> ZKResult<()> {
-        self.write_i32(self.perms, b);
-        match &self.scheme {
-            Scheme::World => {
-                self.write_string(WORLD, b);
-                self.write_string(ANYONE, b);
-            }
-            Scheme::IP(addr) => {
-                self.write_string(IP, b);
-                self.write_string(addr.to_string().as_str(), b);
-            }
-            Scheme::Digest(digest_info) => {
-                self.write_string(DIGEST, b);
-                self.write_string(digest_info, b);
-            }
-        };
+        self.write_i32(self.perms.bits(), b);
+        self.write_string(self.scheme.scheme(), b);
+        self.write_string(self.scheme.id().as_str(), b);
         Ok(())
     }
 }
 
 impl Deserializer for ACL {
     fn read(&mut self, b: &mut BytesMut) -> ZKResult<()> {
-        self.perms = self.read_i32(b);
+        self.perms = Perms::from(self.read_i32(b));
         let scheme = self.read_string(b);
         let id = self.read_string(b);
-        self.scheme = Scheme::from((scheme, id));
+        self.scheme = Scheme::new(&scheme, &id)?;
         Ok(())
     }
 }
@@ -138,17 +232,84 @@ impl Deserializer for ACL {
 impl Default for ACL {
     fn default() -> Self {
         ACL {
-            perms: Perms::All.into(),
+            perms: Perms::ALL,
             scheme: Scheme::World,
         }
     }
 }
 
+/// zkCli 格式，例如 `digest:bob:xyz=:r`
+impl Display for ACL {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.scheme, self.perms)
+    }
+}
+
+impl FromStr for ACL {
+    type Err = ZKError;
+
+    /// 解析 zkCli 格式的单个 ACL，scheme 和权限分别取第一个和最后一个 `:` 之外的部分，中间的都是 id
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || ZKError::ACLError(s.to_string(), "Expect scheme:id:perms".into());
+        let (scheme, rest) = s.split_once(':').ok_or_else(invalid)?;
+        let (id, perms) = rest.rsplit_once(':').ok_or_else(invalid)?;
+        Ok(ACL {
+            perms: perms.parse()?,
+            scheme: Scheme::new(scheme, id)?,
+        })
+    }
+}
+
+lazy_static! {
+    /// 任何人都拥有所有权限，对应 Java 的 `Ids.OPEN_ACL_UNSAFE`
+    pub static ref OPEN_ACL_UNSAFE: Vec<ACL> = vec![ACL::new(Perms::ALL, Scheme::World)];
+    /// 只有创建者拥有所有权限，创建前需要先通过 `add_auth` 鉴权，对应 Java 的 `Ids.CREATOR_ALL_ACL`
+    pub static ref CREATOR_ALL_ACL: Vec<ACL> = vec![ACL::new(Perms::ALL, Scheme::Auth(String::new()))];
+    /// 任何人都只能读，对应 Java 的 `Ids.READ_ACL_UNSAFE`
+    pub static ref READ_ACL_UNSAFE: Vec<ACL> = vec![ACL::new(Perms::READ, Scheme::World)];
+}
+
 impl ACL {
-    /// world 权限固定写法
+    /// 创建 ACL
+    /// # Examples
+    ///
+    /// ```rust,ignore
+    /// let acl = ACL::new(Perms::ALL, Scheme::digest("user", "password"));
+    /// ```
+    pub fn new(perms: Perms, scheme: Scheme) -> ACL {
+        ACL { perms, scheme }
+    }
+
+    /// 解析 zkCli 格式的 ACL 列表，多个 ACL 之间用 `,` 分隔
+    /// # Examples
+    ///
+    /// ```rust,ignore
+    /// let acl_list = ACL::parse_list("world:anyone:cdrwa,digest:bob:xyz=:r,ip:10.0.0.0/8:rw")?;
+    /// assert_eq!(ACL::join_list(&acl_list), "world:anyone:cdrwa,digest:bob:xyz=:r,ip:10.0.0.0/8:rw");
+    /// ```
+    /// # Errors
+    ///
+    /// 任意一个 ACL 格式有误时返回异常
+    pub fn parse_list(s: &str) -> ZKResult<Vec<ACL>> {
+        s.split(',')
+            .map(str::trim)
+            .filter(|acl| !acl.is_empty())
+            .map(ACL::from_str)
+            .collect()
+    }
+
+    /// 把 ACL 列表转换成 zkCli 格式，与 [`ACL::parse_list`] 互逆
+    pub fn join_list(acl_list: &[ACL]) -> String {
+        acl_list
+            .iter()
+            .map(ACL::to_string)
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    /// world 权限固定写法，与 [`struct@OPEN_ACL_UNSAFE`] 相同
     pub fn world_acl() -> Vec<ACL> {
-        // TODO 缓存
-        vec![ACL::default()]
+        OPEN_ACL_UNSAFE.clone()
     }
 }
 
@@ -375,3 +536,430 @@ impl CheckWatchesRequest {
         }
     }
 }
+
+#[derive(Debug, Default)]
+pub(crate) struct AuthPacket {
+    auth_type: i32,
+    scheme: String,
+    auth: Vec<u8>,
+}
+
+impl Serializer for AuthPacket {
+    fn write(&self, b: &mut BytesMut) -> ZKResult<()> {
+        self.write_i32(self.auth_type, b);
+        self.write_string(self.scheme.as_str(), b);
+        self.write_slice(self.auth.clone(), b);
+        Ok(())
+    }
+}
+
+impl AuthPacket {
+    pub(crate) fn new(scheme: String, auth: Vec<u8>) -> Self {
+        AuthPacket {
+            auth_type: 0,
+            scheme,
+            auth,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct SetWatchesRequest {
+    relative_zxid: i64,
+    data_watches: Vec<String>,
+    exist_watches: Vec<String>,
+    child_watches: Vec<String>,
+    persistent_watches: Vec<String>,
+    persistent_recursive_watches: Vec<String>,
+}
+
+impl Serializer for SetWatchesRequest {
+    fn write(&self, b: &mut BytesMut) -> ZKResult<()> {
+        self.write_i64(self.relative_zxid, b);
+        self.write_string_vec(&self.data_watches, b);
+        self.write_string_vec(&self.exist_watches, b);
+        self.write_string_vec(&self.child_watches, b);
+        if self.op_code() == OpCode::SetWatches2 {
+            self.write_string_vec(&self.persistent_watches, b);
+            self.write_string_vec(&self.persistent_recursive_watches, b);
+        }
+        Ok(())
+    }
+}
+
+impl SetWatchesRequest {
+    pub(crate) fn new(
+        relative_zxid: i64,
+        data_watches: Vec<String>,
+        exist_watches: Vec<String>,
+        child_watches: Vec<String>,
+        persistent_watches: Vec<String>,
+        persistent_recursive_watches: Vec<String>,
+    ) -> Self {
+        SetWatchesRequest {
+            relative_zxid,
+            data_watches,
+            exist_watches,
+            child_watches,
+            persistent_watches,
+            persistent_recursive_watches,
+        }
+    }
+
+    /// 只有持久回调需要 3.6 之后才支持的 SetWatches2
+    pub(crate) fn op_code(&self) -> OpCode {
+        if self.persistent_watches.is_empty() && self.persistent_recursive_watches.is_empty() {
+            OpCode::SetWatches
+        } else {
+            OpCode::SetWatches2
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.data_watches.is_empty()
+            && self.exist_watches.is_empty()
+            && self.child_watches.is_empty()
+            && self.op_code() == OpCode::SetWatches
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct GetSASLRequest {
+    token: Vec<u8>,
+}
+
+impl Serializer for GetSASLRequest {
+    fn write(&self, b: &mut BytesMut) -> ZKResult<()> {
+        self.write_slice(self.token.clone(), b);
+        Ok(())
+    }
+}
+
+impl GetSASLRequest {
+    pub(crate) fn new(token: Vec<u8>) -> Self {
+        GetSASLRequest { token }
+    }
+}
+
+/// 事务中的单个操作，多个操作通过 [`ZooKeeper::multi`] 原子地提交，全部成功或者全部失败
+/// # Examples
+///
+/// ```rust,ignore
+/// let results = zk
+///     .multi(vec![
+///         Op::check("/config", 3),
+///         Op::create("/config/v4", Some(b"data"), ACL::world_acl(), CreateMode::Persistent),
+///         Op::set_data("/config", b"v4", 3),
+///     ])
+///     .await?;
+/// ```
+///
+/// 版本号为 -1 时不校验版本
+///
+/// [`ZooKeeper::multi`]: crate::ZooKeeper::multi
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Op {
+    Create {
+        path: String,
+        data: Option<Vec<u8>>,
+        acl_list: Vec<ACL>,
+        create_mode: CreateMode,
+    },
+    Delete {
+        path: String,
+        version: i32,
+    },
+    SetData {
+        path: String,
+        data: Vec<u8>,
+        version: i32,
+    },
+    Check {
+        path: String,
+        version: i32,
+    },
+}
+
+impl Op {
+    /// 创建节点，参考 [`ZooKeeper::create`](crate::ZooKeeper::create)
+    pub fn create(
+        path: impl Into<String>,
+        data: Option<&[u8]>,
+        acl_list: Vec<ACL>,
+        create_mode: CreateMode,
+    ) -> Op {
+        Op::Create {
+            path: path.into(),
+            data: data.map(Vec::from),
+            acl_list,
+            create_mode,
+        }
+    }
+
+    /// 删除节点，参考 [`ZooKeeper::deletev`](crate::ZooKeeper::deletev)
+    pub fn delete(path: impl Into<String>, version: i32) -> Op {
+        Op::Delete {
+            path: path.into(),
+            version,
+        }
+    }
+
+    /// 设置节点数据，参考 [`ZooKeeper::setv`](crate::ZooKeeper::setv)
+    pub fn set_data(path: impl Into<String>, data: &[u8], version: i32) -> Op {
+        Op::SetData {
+            path: path.into(),
+            data: Vec::from(data),
+            version,
+        }
+    }
+
+    /// 校验节点的版本号，不满足时整个事务失败
+    pub fn check(path: impl Into<String>, version: i32) -> Op {
+        Op::Check {
+            path: path.into(),
+            version,
+        }
+    }
+
+    /// 操作的目标路径
+    pub fn path(&self) -> &str {
+        match self {
+            Op::Create { path, .. }
+            | Op::Delete { path, .. }
+            | Op::SetData { path, .. }
+            | Op::Check { path, .. } => path,
+        }
+    }
+
+    pub(crate) fn op_code(&self) -> OpCode {
+        match self {
+            Op::Create {
+                create_mode: CreateMode::Container,
+                ..
+            } => OpCode::CreateContainer,
+            Op::Create { .. } => OpCode::Create,
+            Op::Delete { .. } => OpCode::Delete,
+            Op::SetData { .. } => OpCode::SetData,
+            Op::Check { .. } => OpCode::Check,
+        }
+    }
+
+    pub(crate) fn path_mut(&mut self) -> &mut String {
+        match self {
+            Op::Create { path, .. }
+            | Op::Delete { path, .. }
+            | Op::SetData { path, .. }
+            | Op::Check { path, .. } => path,
+        }
+    }
+}
+
+impl Serializer for Op {
+    fn write(&self, b: &mut BytesMut) -> ZKResult<()> {
+        match self {
+            Op::Create {
+                path,
+                data,
+                acl_list,
+                create_mode,
+            } => {
+                self.write_string(path, b);
+                self.write_slice_option(data.clone(), b);
+                self.write_vec(acl_list, b);
+                self.write_i32((*create_mode).into(), b);
+            }
+            Op::Delete { path, version } | Op::Check { path, version } => {
+                self.write_string(path, b);
+                self.write_i32(*version, b);
+            }
+            Op::SetData {
+                path,
+                data,
+                version,
+            } => {
+                self.write_string(path, b);
+                self.write_slice(data.clone(), b);
+                self.write_i32(*version, b);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 事务中每个操作之前的头部，列表以 `done` 为 true 的头部结束
+#[derive(Debug, Default)]
+pub(crate) struct MultiHeader {
+    pub op_type: i32,
+    pub done: bool,
+    pub err: i32,
+}
+
+impl Serializer for MultiHeader {
+    fn write(&self, b: &mut BytesMut) -> ZKResult<()> {
+        self.write_i32(self.op_type, b);
+        self.write_bool(self.done, b);
+        self.write_i32(self.err, b);
+        Ok(())
+    }
+}
+
+impl Deserializer for MultiHeader {
+    fn read(&mut self, b: &mut BytesMut) -> ZKResult<()> {
+        self.op_type = self.read_i32(b);
+        self.done = self.read_bool(b);
+        self.err = self.read_i32(b);
+        Ok(())
+    }
+}
+
+/// `ops` 中的路径需要已经拼接了 chroot
+#[derive(Debug, Default)]
+pub(crate) struct MultiRequest {
+    ops: Vec<Op>,
+}
+
+impl Serializer for MultiRequest {
+    fn write(&self, b: &mut BytesMut) -> ZKResult<()> {
+        for op in self.ops.iter() {
+            let header = MultiHeader {
+                op_type: op.op_code().into(),
+                done: false,
+                err: -1,
+            };
+            header.write(b)?;
+            op.write(b)?;
+        }
+        let end = MultiHeader {
+            op_type: -1,
+            done: true,
+            err: -1,
+        };
+        end.write(b)
+    }
+}
+
+impl MultiRequest {
+    pub(crate) fn new(ops: Vec<Op>) -> Self {
+        MultiRequest { ops }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    use super::*;
+
+    #[test]
+    fn test_generate_digest() {
+        // ZooKeeper 管理员文档中的示例
+        assert_eq!(
+            Scheme::generate_digest("super:superpw"),
+            "super:g9oN2HttPfn8MMWJZ2r45Np/LIA="
+        );
+        assert_eq!(
+            Scheme::digest("super", "superpw"),
+            Scheme::Digest("super:g9oN2HttPfn8MMWJZ2r45Np/LIA=".into())
+        );
+    }
+
+    #[test]
+    fn test_scheme_round_trip() {
+        let cases = [
+            ("world", "anyone", Scheme::World),
+            ("auth", "", Scheme::Auth("".into())),
+            ("digest", "bob:xyz=", Scheme::Digest("bob:xyz=".into())),
+            (
+                "ip",
+                "10.0.0.1",
+                Scheme::IP(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)), None),
+            ),
+            (
+                "ip",
+                "10.0.0.0/8",
+                Scheme::IP(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 0)), Some(8)),
+            ),
+            (
+                "ip",
+                "2001:db8::/32",
+                Scheme::IP(
+                    IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 0)),
+                    Some(32),
+                ),
+            ),
+            ("x509", "CN=client", Scheme::X509("CN=client".into())),
+            ("sasl", "bob", Scheme::Sasl("bob".into())),
+            (
+                "custom",
+                "token",
+                Scheme::Other("custom".into(), "token".into()),
+            ),
+        ];
+        for (scheme, id, expected) in cases {
+            let parsed = Scheme::new(scheme, id).unwrap();
+            assert_eq!(parsed, expected);
+            assert_eq!(parsed.scheme(), scheme);
+            assert_eq!(parsed.id(), id);
+            assert_eq!(parsed.to_string(), format!("{}:{}", scheme, id));
+        }
+    }
+
+    #[test]
+    fn test_invalid_ip() {
+        assert!(Scheme::new("ip", "10.0.0.256").is_err());
+        assert!(Scheme::new("ip", "10.0.0.0/33").is_err());
+        assert!(Scheme::new("ip", "::1/129").is_err());
+        assert!(Scheme::new("ip", "10.0.0.0/").is_err());
+        assert!(Scheme::new("ip", "::1/128").is_ok());
+    }
+
+    #[test]
+    fn test_acl_serialize() {
+        let acl = ACL::new(Perms::READ, Scheme::new("ip", "10.0.0.0/8").unwrap());
+        let mut buf = BytesMut::new();
+        acl.write(&mut buf).unwrap();
+        let mut read = ACL::default();
+        read.read(&mut buf).unwrap();
+        assert_eq!(read, acl);
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn test_predefined_acl() {
+        assert_eq!(ACL::world_acl(), *OPEN_ACL_UNSAFE);
+        assert_eq!(CREATOR_ALL_ACL[0].scheme, Scheme::Auth(String::new()));
+        assert_eq!(CREATOR_ALL_ACL[0].perms, Perms::ALL);
+        assert_eq!(READ_ACL_UNSAFE[0].perms, Perms::READ);
+    }
+
+    #[test]
+    fn test_acl_string_round_trip() {
+        let s = "world:anyone:cdrwa,digest:bob:xyz=:r,ip:10.0.0.0/8:rw,ip:2001:db8::/32:a,auth::cd";
+        let acl_list = ACL::parse_list(s).unwrap();
+        assert_eq!(
+            acl_list,
+            vec![
+                ACL::new(Perms::ALL, Scheme::World),
+                ACL::new(Perms::READ, Scheme::Digest("bob:xyz=".into())),
+                ACL::new(
+                    Perms::READ | Perms::WRITE,
+                    Scheme::new("ip", "10.0.0.0/8").unwrap()
+                ),
+                ACL::new(Perms::ADMIN, Scheme::new("ip", "2001:db8::/32").unwrap()),
+                ACL::new(Perms::CREATE | Perms::DELETE, Scheme::Auth("".into())),
+            ]
+        );
+        assert_eq!(ACL::join_list(&acl_list), s);
+        assert_eq!(ACL::parse_list(" world:anyone:rwa , ").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_invalid_acl_string() {
+        assert!(ACL::from_str("world").is_err());
+        assert!(ACL::from_str("world:anyone").is_err());
+        assert!(ACL::from_str("world:anyone:").is_ok());
+        assert!(ACL::from_str("world:anyone:rx").is_err());
+        assert!(ACL::from_str("ip:10.0.0.0/40:r").is_err());
+        assert!(ACL::parse_list("world:anyone:r,bad").is_err());
+    }
+}
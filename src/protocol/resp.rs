@@ -1,7 +1,10 @@
 use bytes::BytesMut;
 
+use crate::constants::OpCode;
+use crate::error::ServerErrorCode;
+use crate::protocol::req::MultiHeader;
 use crate::protocol::Deserializer;
-use crate::{ZKResult, ACL};
+use crate::{ZKError, ZKResult, ACL};
 
 #[derive(Debug, Default)]
 pub(crate) struct ReplyHeader {
@@ -22,10 +25,10 @@ impl Deserializer for ReplyHeader {
 #[derive(Debug, Default)]
 pub(crate) struct ConnectResponse {
     protocol_version: i32,
-    time_out: i32,
+    pub time_out: i32,
     pub session_id: i64,
     pub password: Vec<u8>,
-    read_only: bool,
+    pub read_only: bool,
 }
 
 impl Deserializer for ConnectResponse {
@@ -34,7 +37,10 @@ impl Deserializer for ConnectResponse {
         self.time_out = self.read_i32(b);
         self.session_id = self.read_i64(b);
         self.password = self.read_slice_unchecked(b);
-        self.read_only = self.read_bool(b);
+        // 3.4 之前的服务端不会返回 read_only 字段
+        if !b.is_empty() {
+            self.read_only = self.read_bool(b);
+        }
         Ok(())
     }
 }
@@ -51,6 +57,31 @@ impl Deserializer for CreateResponse {
     }
 }
 
+#[derive(Debug, Default)]
+pub(crate) struct Create2Response {
+    pub path: String,
+    pub stat: Stat,
+}
+
+impl Deserializer for Create2Response {
+    fn read(&mut self, b: &mut BytesMut) -> ZKResult<()> {
+        self.path = self.read_string(b);
+        self.stat.read(b)
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct SetSASLResponse {
+    pub token: Vec<u8>,
+}
+
+impl Deserializer for SetSASLResponse {
+    fn read(&mut self, b: &mut BytesMut) -> ZKResult<()> {
+        self.token = self.read_slice_unchecked(b);
+        Ok(())
+    }
+}
+
 #[derive(Debug, Default)]
 pub(crate) struct IgnoreResponse {}
 
@@ -72,7 +103,7 @@ impl Deserializer for IgnoreResponse {
 /// - `data_length`： 数据的长度
 /// - `num_children`：子节点（不含孙子节点）数量
 /// - `pzxid`：
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
 pub struct Stat {
     pub czxid: i64,
     pub mzxid: i64,
@@ -225,3 +256,60 @@ impl Deserializer for WatcherEvent {
         Ok(())
     }
 }
+
+/// 事务中单个操作的结果，与提交的 [`Op`](crate::Op) 一一对应
+/// - `Create`：实际创建的路径，顺序节点会带上序号
+/// - `SetData`：设置后节点的统计数据
+/// - `Error`：事务失败时每个操作的错误码，成功的操作为 0，失败操作之后的操作为 -2（RuntimeInconsistency）
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum OpResult {
+    Create { path: String },
+    Delete,
+    SetData { stat: Stat },
+    Check,
+    Error { code: ServerErrorCode, err: i32 },
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct MultiResponse {
+    pub results: Vec<OpResult>,
+}
+
+impl Deserializer for MultiResponse {
+    fn read(&mut self, b: &mut BytesMut) -> ZKResult<()> {
+        loop {
+            let mut header = MultiHeader::default();
+            header.read(b)?;
+            if header.done {
+                return Ok(());
+            }
+            let result = match OpCode::from(header.op_type) {
+                OpCode::Create | OpCode::CreateContainer => OpResult::Create {
+                    path: self.read_string(b),
+                },
+                OpCode::Create2 | OpCode::CreateTTL => {
+                    let path = self.read_string(b);
+                    Stat::default().read(b)?;
+                    OpResult::Create { path }
+                }
+                OpCode::Delete => OpResult::Delete,
+                OpCode::SetData => {
+                    let mut stat = Stat::default();
+                    stat.read(b)?;
+                    OpResult::SetData { stat }
+                }
+                OpCode::Check => OpResult::Check,
+                OpCode::Error if header.op_type == -1 => {
+                    let err = self.read_i32(b);
+                    OpResult::Error {
+                        code: ServerErrorCode::from(err),
+                        err,
+                    }
+                }
+                // 未知的操作类型无法确定结果的长度
+                _ => return Err(ZKError::ProtocolParseError),
+            };
+            self.results.push(result);
+        }
+    }
+}
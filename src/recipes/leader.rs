@@ -0,0 +1,594 @@
+//! # 领导者选举模块
+//! [`LeaderLatch`] 的参与者在选举路径下创建数据为自身 ID 的 `latch-` 临时顺序节点，序号最小的参与者成为领导者，
+//! 其他参与者只监听排在自己前面的节点。[`LeaderSelector`] 以互斥锁的方式排队，持有锁期间执行调用方提供的任务。
+//!
+//! 连接挂起（[`ConnectionState::Suspended`]）时无法确认节点是否仍然排在第一位，领导者会立即放弃领导权，
+//! 重连成功后重新检查；会话过期时节点已经被删除，选举随之结束
+//!
+//! [`ConnectionState::Suspended`]: crate::ConnectionState::Suspended
+
+use std::fmt::{Debug, Formatter};
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures_util::future::BoxFuture;
+use futures_util::stream::{self, Stream};
+use futures_util::FutureExt;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{broadcast, watch, Notify};
+use tokio::task::JoinHandle;
+
+use crate::error::ServerErrorCode;
+use crate::recipes::lock::{mutex_driver, LockInternals, LOCK_NAME};
+use crate::recipes::{create_sequential, delete_quietly, EphemeralGuard, NotifyWatcher};
+use crate::{paths, ZKError, ZKResult, ZooKeeper};
+
+const LATCH_NAME: &str = "latch-";
+/// 检查失败（例如连接断开）之后再次检查的间隔
+const RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 领导权变化的通知，通过 [`LeaderLatch::events`] 获取
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum LeadershipEvent {
+    /// 成为领导者
+    Gained,
+    /// 失去领导权，包括连接挂起、会话过期以及主动关闭
+    Lost,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Status {
+    Latent,
+    Follower,
+    Leader,
+    Closed,
+}
+
+#[derive(Debug)]
+struct LatchCore {
+    zk: ZooKeeper,
+    path: String,
+    id: String,
+    status: watch::Sender<Status>,
+    events: broadcast::Sender<LeadershipEvent>,
+    node: Mutex<Option<String>>,
+}
+
+impl LatchCore {
+    fn set_status(&self, status: Status) {
+        let old = self.status.send_replace(status);
+        let event = match (old == Status::Leader, status == Status::Leader) {
+            (false, true) => LeadershipEvent::Gained,
+            (true, false) => LeadershipEvent::Lost,
+            _ => return,
+        };
+        info!("Leadership of `{}` {:?} by `{}`", self.path, event, self.id);
+        let _ = self.events.send(event);
+    }
+
+    async fn run(self: Arc<LatchCore>) {
+        let mut listener = self.zk.subscribe_state();
+        let mut guard = None;
+        loop {
+            let state = self.zk.client.state();
+            if !state.is_alive() {
+                break;
+            }
+            if !state.is_connected() {
+                self.set_status(Status::Follower);
+                match listener.recv().await {
+                    Some(event) if !event.state.is_terminal() => continue,
+                    _ => break,
+                }
+            }
+            let watcher = NotifyWatcher::default();
+            let retry = match self.check(&mut guard, &watcher).await {
+                Ok(leader) => {
+                    self.set_status(if leader {
+                        Status::Leader
+                    } else {
+                        Status::Follower
+                    });
+                    None
+                }
+                Err(e) => {
+                    warn!("Check leadership of `{}` failed: {}", self.path, e);
+                    self.set_status(Status::Follower);
+                    Some(tokio::time::sleep(RETRY_INTERVAL))
+                }
+            };
+            let retry = async {
+                match retry {
+                    Some(retry) => retry.await,
+                    None => std::future::pending().await,
+                }
+            };
+            tokio::select! {
+                _ = watcher.notified() => {}
+                _ = retry => {}
+                event = listener.recv() => match event {
+                    Some(event) if event.state.is_terminal() => break,
+                    // 连接挂起时立即放弃领导权，重连之后重新检查
+                    Some(event) if !event.state.is_connected() => {
+                        self.set_status(Status::Follower)
+                    }
+                    Some(_) => {}
+                    None => break,
+                },
+            }
+        }
+        // 会话已经失效，节点由服务端删除
+        if let Some(guard) = guard {
+            EphemeralGuard::keep(guard);
+        }
+        *self.node.lock().unwrap() = None;
+        self.set_status(Status::Closed);
+    }
+
+    /// 必要时创建节点，返回是否是领导者，同时在前一个节点（领导者监听自己的节点）上注册 `watcher`
+    async fn check(
+        &self,
+        guard: &mut Option<EphemeralGuard>,
+        watcher: &NotifyWatcher,
+    ) -> ZKResult<bool> {
+        loop {
+            if guard.is_none() {
+                let node =
+                    create_sequential(&self.zk, &self.path, LATCH_NAME, Some(self.id.as_bytes()))
+                        .await?;
+                *self.node.lock().unwrap() = Some(node.clone());
+                *guard = Some(EphemeralGuard::new(&self.zk, node));
+            }
+            let node = guard.as_ref().unwrap().path().to_string();
+            let (_, name) = paths::split(&node);
+            let nodes = self.zk.sequential_children(&self.path, LATCH_NAME).await?;
+            let index = match nodes.iter().position(|n| n.name() == name) {
+                Some(index) => index,
+                None => {
+                    // 节点被其他客户端删除，重新参与选举
+                    guard.take().unwrap().keep();
+                    continue;
+                }
+            };
+            let target = match index {
+                0 => node.clone(),
+                _ => paths::join(&self.path, nodes[index - 1].name()),
+            };
+            match self
+                .zk
+                .getw(target.as_str(), Some(watcher.clone()), None)
+                .await
+            {
+                Ok(_) => return Ok(index == 0),
+                Err(e) if e.code() == Some(ServerErrorCode::NoNode) => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+/// 领导者选举，调用 [`LeaderLatch::start`] 之后在后台参与选举，直到 [`LeaderLatch::close`] 或者会话过期。
+/// 对象被释放时退出选举，在后台删除节点
+/// # Examples
+///
+/// ```rust,ignore
+/// use futures_util::StreamExt;
+///
+/// let latch = LeaderLatch::new(&zk, "/election/scheduler", "node-1")?;
+/// latch.start()?;
+/// let mut events = Box::pin(latch.events());
+/// latch.await_leadership().await?;
+/// while let Some(event) = events.next().await {
+///     if event == LeadershipEvent::Lost {
+///         stop_scheduling();
+///     }
+/// }
+/// ```
+#[derive(Debug)]
+pub struct LeaderLatch {
+    core: Arc<LatchCore>,
+    task: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl LeaderLatch {
+    /// 创建选举对象，此时不会访问服务端
+    /// # Args
+    /// - `zk`： 客户端，选举节点属于该客户端的会话
+    /// - `path`： 选举路径，必须以 "/" 开头，所有参与者必须使用相同的路径
+    /// - `id`： 参与者的 ID，保存在节点数据中，参考 [`LeaderLatch::leader_id`]
+    /// # Errors
+    ///
+    /// 路径不合法时返回 [`ZKError::PathError`]
+    pub fn new(
+        zk: &ZooKeeper,
+        path: impl AsRef<str>,
+        id: impl Into<String>,
+    ) -> ZKResult<LeaderLatch> {
+        let path = path.as_ref();
+        paths::validate_path(path)?;
+        let (events, _) = broadcast::channel(16);
+        let core = LatchCore {
+            zk: zk.clone(),
+            path: path.to_string(),
+            id: id.into(),
+            status: watch::Sender::new(Status::Latent),
+            events,
+            node: Mutex::new(None),
+        };
+        Ok(LeaderLatch {
+            core: Arc::new(core),
+            task: Mutex::new(None),
+        })
+    }
+
+    /// 在后台开始参与选举，已经开始时不做任何操作，必须在 tokio 运行时中调用
+    /// # Errors
+    ///
+    /// 会话已经过期或者客户端已经关闭时返回异常
+    pub fn start(&self) -> ZKResult<()> {
+        let mut task = self.task.lock().unwrap();
+        if task.as_ref().is_some_and(|task| !task.is_finished()) {
+            return Ok(());
+        }
+        if !self.core.zk.client.state().is_alive() {
+            return Err(self.core.zk.client.loss_error());
+        }
+        self.core.set_status(Status::Latent);
+        *task = Some(tokio::spawn(self.core.clone().run()));
+        Ok(())
+    }
+
+    /// 退出选举并删除节点，之后可以再次调用 [`LeaderLatch::start`]
+    /// # Errors
+    ///
+    /// 删除节点失败时返回异常，节点会在会话过期后由服务端删除
+    pub async fn close(&self) -> ZKResult<()> {
+        let task = self.task.lock().unwrap().take();
+        if let Some(task) = task {
+            task.abort();
+            let _ = task.await;
+        }
+        self.core.set_status(Status::Closed);
+        let node = self.core.node.lock().unwrap().take();
+        match node {
+            Some(node) if self.core.zk.client.state().is_alive() => {
+                delete_quietly(&self.core.zk, &node).await
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// 当前是否是领导者，连接挂起期间返回 false
+    pub fn has_leadership(&self) -> bool {
+        *self.core.status.borrow() == Status::Leader
+    }
+
+    /// 一直等待直到成为领导者，已经是领导者时立即返回
+    /// # Errors
+    ///
+    /// 等待过程中选举被关闭时返回 [`ZKError::NotHeld`]，会话过期时返回会话过期的异常
+    pub async fn await_leadership(&self) -> ZKResult<()> {
+        let mut status = self.core.status.subscribe();
+        let result = status
+            .wait_for(|status| matches!(status, Status::Leader | Status::Closed))
+            .await
+            .map(|status| *status);
+        match result {
+            Ok(Status::Leader) => Ok(()),
+            _ => Err(self.closed_error()),
+        }
+    }
+
+    /// 最多等待 `timeout` 直到成为领导者，超时返回 false，参考 [`LeaderLatch::await_leadership`]
+    pub async fn await_leadership_timeout(&self, timeout: Duration) -> ZKResult<bool> {
+        match tokio::time::timeout(timeout, self.await_leadership()).await {
+            Ok(result) => result.map(|_| true),
+            Err(_) => Ok(false),
+        }
+    }
+
+    /// 领导权变化的通知，只包含订阅之后发生的变化，消费过慢时会跳过部分通知，
+    /// 当前状态请以 [`LeaderLatch::has_leadership`] 为准
+    pub fn events(&self) -> impl Stream<Item = LeadershipEvent> {
+        stream::unfold(self.core.events.subscribe(), |mut rx| async move {
+            loop {
+                match rx.recv().await {
+                    Ok(event) => return Some((event, rx)),
+                    Err(RecvError::Lagged(n)) => {
+                        warn!("Leadership events lagged, skipped {} events", n);
+                    }
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        })
+    }
+
+    /// 参与者的 ID
+    pub fn id(&self) -> &str {
+        &self.core.id
+    }
+
+    /// 当前对象的选举节点路径，没有参与选举时为 `None`
+    pub fn our_node(&self) -> Option<String> {
+        self.core.node.lock().unwrap().clone()
+    }
+
+    /// 当前领导者的 ID，没有参与者时为 `None`
+    pub async fn leader_id(&self) -> ZKResult<Option<String>> {
+        Ok(self.participant_ids().await?.into_iter().next())
+    }
+
+    /// 所有参与者的 ID，第一个为领导者
+    pub async fn participant_ids(&self) -> ZKResult<Vec<String>> {
+        let path = self.core.path.as_str();
+        let nodes = match self.core.zk.sequential_children(path, LATCH_NAME).await {
+            Ok(nodes) => nodes,
+            Err(e) if e.code() == Some(ServerErrorCode::NoNode) => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        let nodes = nodes.iter().map(|node| paths::join(path, node.name()));
+        read_ids(&self.core.zk, nodes).await
+    }
+
+    fn closed_error(&self) -> ZKError {
+        if self.core.zk.client.state().is_alive() {
+            ZKError::NotHeld {
+                path: self.core.path.clone(),
+            }
+        } else {
+            self.core.zk.client.loss_error()
+        }
+    }
+}
+
+/// 按顺序读取参与者节点中保存的 ID，跳过刚刚退出的参与者
+async fn read_ids(zk: &ZooKeeper, nodes: impl Iterator<Item = String>) -> ZKResult<Vec<String>> {
+    let mut ids = Vec::new();
+    for node in nodes {
+        match zk.get(&node, None).await {
+            Ok(data) => ids.push(String::from_utf8_lossy(&data).into_owned()),
+            Err(e) if e.code() == Some(ServerErrorCode::NoNode) => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(ids)
+}
+
+impl Drop for LeaderLatch {
+    fn drop(&mut self) {
+        // 任务中的节点守卫被释放时在后台删除节点
+        if let Some(task) = self.task.get_mut().unwrap().take() {
+            task.abort();
+        }
+    }
+}
+
+/// 成为领导者之后执行的任务，参考 [`LeaderSelector::new`]
+type TakeLeadership = dyn Fn(ZooKeeper) -> BoxFuture<'static, ()> + Send + Sync;
+
+struct SelectorCore {
+    zk: ZooKeeper,
+    path: String,
+    id: String,
+    lock: LockInternals,
+    take_leadership: Box<TakeLeadership>,
+    auto_requeue: AtomicBool,
+    queued: AtomicBool,
+    requeue: Notify,
+    leading: AtomicBool,
+}
+
+impl Debug for SelectorCore {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SelectorCore")
+            .field("path", &self.path)
+            .field("id", &self.id)
+            .field("lock", &self.lock)
+            .field("auto_requeue", &self.auto_requeue)
+            .field("queued", &self.queued)
+            .field("leading", &self.leading)
+            .finish()
+    }
+}
+
+impl SelectorCore {
+    async fn run(self: Arc<SelectorCore>) {
+        loop {
+            // `Notify` 会保存一次唤醒，排队请求不会丢失
+            while !self.queued.swap(false, Ordering::AcqRel) {
+                self.requeue.notified().await;
+            }
+            if let Err(e) = self.lock.acquire(None).await {
+                if !self.zk.client.state().is_alive() {
+                    break;
+                }
+                warn!("Wait for leadership of `{}` failed: {}", self.path, e);
+                self.queued.store(true, Ordering::Release);
+                tokio::time::sleep(RETRY_INTERVAL).await;
+                continue;
+            }
+            self.lead().await;
+            if let Err(e) = self.lock.release().await {
+                warn!("Release leadership of `{}` failed: {}", self.path, e);
+            }
+            if !self.zk.client.state().is_alive() {
+                break;
+            }
+            if self.auto_requeue.load(Ordering::Acquire) {
+                self.queued.store(true, Ordering::Release);
+            }
+        }
+        debug!("Selector of `{}` stopped", self.path);
+    }
+
+    /// 执行任务直到任务结束或者会话过期，会话过期时任务被取消
+    async fn lead(&self) {
+        info!("Leadership of `{}` taken by `{}`", self.path, self.id);
+        self.leading.store(true, Ordering::Release);
+        let mut listener = self.zk.subscribe_state();
+        let lost = async {
+            while self.zk.client.state().is_alive() {
+                match listener.recv().await {
+                    Some(event) if event.state.is_terminal() => break,
+                    Some(_) => {}
+                    None => break,
+                }
+            }
+        };
+        let task = AssertUnwindSafe((self.take_leadership)(self.zk.clone())).catch_unwind();
+        tokio::select! {
+            result = task => {
+                if result.is_err() {
+                    error!("Leadership task of `{}` panicked", self.path);
+                }
+            }
+            _ = lost => warn!("Session lost, leadership task of `{}` cancelled", self.path),
+        }
+        self.leading.store(false, Ordering::Release);
+        info!("Leadership of `{}` released by `{}`", self.path, self.id);
+    }
+}
+
+/// 领导者选举，成为领导者之后执行调用方提供的任务，任务结束即放弃领导权。
+/// 内部使用 [`crate::recipes::lock::InterProcessMutex`] 相同的方式排队，
+/// 默认只参与一次选举，可以通过 [`LeaderSelector::auto_requeue`] 或者 [`LeaderSelector::requeue`] 再次排队。
+/// 会话过期或者调用 [`LeaderSelector::close`] 时任务被取消，对象被释放时同样会取消任务
+/// # Examples
+///
+/// ```rust,ignore
+/// let selector = LeaderSelector::new(&zk, "/election/compaction", "node-1", |zk| async move {
+///     // 返回即放弃领导权
+///     run_compaction(&zk).await;
+/// })?
+/// .auto_requeue(true);
+/// selector.start()?;
+/// println!("current leader: {:?}", selector.leader_id().await?);
+/// ```
+#[derive(Debug)]
+pub struct LeaderSelector {
+    core: Arc<SelectorCore>,
+    task: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl LeaderSelector {
+    /// 创建选举对象，此时不会访问服务端
+    /// # Args
+    /// - `zk`： 客户端，选举节点属于该客户端的会话
+    /// - `path`： 选举路径，必须以 "/" 开头，所有参与者必须使用相同的路径
+    /// - `id`： 参与者的 ID，保存在节点数据中，参考 [`LeaderSelector::leader_id`]
+    /// - `take_leadership`： 成为领导者之后执行的任务，参数为当前客户端，任务执行期间保持领导权
+    /// # Errors
+    ///
+    /// 路径不合法时返回 [`ZKError::PathError`]
+    pub fn new<F, Fut>(
+        zk: &ZooKeeper,
+        path: impl AsRef<str>,
+        id: impl Into<String>,
+        take_leadership: F,
+    ) -> ZKResult<LeaderSelector>
+    where
+        F: Fn(ZooKeeper) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let path = path.as_ref();
+        let id = id.into();
+        let lock = LockInternals::new(zk, path, LOCK_NAME, mutex_driver)?
+            .with_data(id.clone().into_bytes());
+        let core = SelectorCore {
+            zk: zk.clone(),
+            path: path.to_string(),
+            id,
+            lock,
+            take_leadership: Box::new(move |zk| take_leadership(zk).boxed()),
+            auto_requeue: AtomicBool::new(false),
+            queued: AtomicBool::new(false),
+            requeue: Notify::new(),
+            leading: AtomicBool::new(false),
+        };
+        Ok(LeaderSelector {
+            core: Arc::new(core),
+            task: Mutex::new(None),
+        })
+    }
+
+    /// 任务结束之后是否自动重新排队，默认为 false
+    pub fn auto_requeue(self, auto_requeue: bool) -> Self {
+        self.core
+            .auto_requeue
+            .store(auto_requeue, Ordering::Release);
+        self
+    }
+
+    /// 在后台开始排队，已经开始时等同于 [`LeaderSelector::requeue`]，必须在 tokio 运行时中调用
+    /// # Errors
+    ///
+    /// 会话已经过期或者客户端已经关闭时返回异常
+    pub fn start(&self) -> ZKResult<()> {
+        let mut task = self.task.lock().unwrap();
+        if !self.core.zk.client.state().is_alive() {
+            return Err(self.core.zk.client.loss_error());
+        }
+        if task.as_ref().is_none_or(|task| task.is_finished()) {
+            *task = Some(tokio::spawn(self.core.clone().run()));
+        }
+        self.requeue();
+        Ok(())
+    }
+
+    /// 任务结束之后再次排队，正在排队或者已经请求过排队时不会重复排队
+    pub fn requeue(&self) {
+        self.core.queued.store(true, Ordering::Release);
+        self.core.requeue.notify_one();
+    }
+
+    /// 退出选举，正在执行的任务被取消，节点被删除
+    /// # Errors
+    ///
+    /// 删除节点失败时返回异常，节点会在会话过期后由服务端删除
+    pub async fn close(&self) -> ZKResult<()> {
+        let task = self.task.lock().unwrap().take();
+        if let Some(task) = task {
+            task.abort();
+            let _ = task.await;
+        }
+        self.core.queued.store(false, Ordering::Release);
+        self.core.leading.store(false, Ordering::Release);
+        match self.core.lock.release().await {
+            Err(ZKError::NotHeld { .. }) => Ok(()),
+            result => result,
+        }
+    }
+
+    /// 当前是否在执行任务
+    pub fn has_leadership(&self) -> bool {
+        self.core.leading.load(Ordering::Acquire)
+    }
+
+    /// 参与者的 ID
+    pub fn id(&self) -> &str {
+        &self.core.id
+    }
+
+    /// 当前领导者的 ID，没有参与者时为 `None`
+    pub async fn leader_id(&self) -> ZKResult<Option<String>> {
+        Ok(self.participant_ids().await?.into_iter().next())
+    }
+
+    /// 所有参与者的 ID，第一个为领导者，其余按照排队顺序排列
+    pub async fn participant_ids(&self) -> ZKResult<Vec<String>> {
+        let nodes = self.core.lock.participant_nodes().await?;
+        read_ids(&self.core.zk, nodes.into_iter()).await
+    }
+}
+
+impl Drop for LeaderSelector {
+    fn drop(&mut self) {
+        // 任务被取消，锁节点在后台删除
+        if let Some(task) = self.task.get_mut().unwrap().take() {
+            task.abort();
+        }
+    }
+}
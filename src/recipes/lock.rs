@@ -0,0 +1,364 @@
+//! # 分布式锁模块
+//! 每个参与者在锁路径下创建一个临时顺序节点，按照序号排队。轮不到自己时只监听排在自己前面的节点，
+//! 前一个节点删除后只有一个等待者被唤醒，避免所有等待者同时请求服务端（羊群效应）
+//!
+//! ```text
+//! /locks/job                          锁路径，不存在时创建为容器节点
+//! /locks/job/_c_1f0e...-lock-0000000000   持有锁
+//! /locks/job/_c_8a2b...-lock-0000000001   监听 0000000000
+//! /locks/job/_c_03c9...-lock-0000000002   监听 0000000001
+//! ```
+//!
+//! 锁节点的 `czxid` 作为 fencing token，后获得锁的持有者的 token 一定更大，
+//! 下游存储只需要拒绝 token 小于已见过的最大值的写入，就能排除会话过期后仍然以为自己持有锁的旧持有者
+
+use std::fmt::{Debug, Formatter};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+use crate::error::ServerErrorCode;
+use crate::recipes::{
+    create_sequential, delete_quietly, node_lost, spawn_delete, EphemeralGuard, NotifyWatcher,
+};
+use crate::sequential::SequentialName;
+use crate::{paths, ZKError, ZKResult, ZooKeeper};
+
+pub(crate) const LOCK_NAME: &str = "lock-";
+
+/// 根据排好序的参与者和自己的位置判断能否获得锁，不能时返回需要监听的节点的位置
+pub(crate) type LockDriver = fn(&[SequentialName], usize, &str) -> Option<usize>;
+
+/// 互斥锁：只有排在第一位的同名节点可以持有锁，否则监听前一个同名节点
+pub(crate) fn mutex_driver(nodes: &[SequentialName], index: usize, name: &str) -> Option<usize> {
+    nodes[..index]
+        .iter()
+        .rposition(|node| node.prefix() == name)
+}
+
+#[derive(Debug)]
+struct Hold {
+    node: String,
+    count: usize,
+    token: i64,
+}
+
+/// 基于临时顺序节点排队的可重入锁，[`InterProcessMutex`] 和读写锁共用
+pub(crate) struct LockInternals {
+    zk: ZooKeeper,
+    path: String,
+    name: &'static str,
+    driver: LockDriver,
+    data: Option<Vec<u8>>,
+    hold: Mutex<Option<Hold>>,
+    acquiring: tokio::sync::Mutex<()>,
+    granted_by: Option<(Arc<LockInternals>, &'static str)>,
+}
+
+impl Debug for LockInternals {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LockInternals")
+            .field("path", &self.path)
+            .field("name", &self.name)
+            .field("hold", &self.hold)
+            .field(
+                "granted_by",
+                &self.granted_by.as_ref().map(|(lock, _)| lock.name),
+            )
+            .finish()
+    }
+}
+
+impl LockInternals {
+    pub(crate) fn new(
+        zk: &ZooKeeper,
+        path: &str,
+        name: &'static str,
+        driver: LockDriver,
+    ) -> ZKResult<LockInternals> {
+        paths::validate_path(path)?;
+        Ok(LockInternals {
+            zk: zk.clone(),
+            path: path.to_string(),
+            name,
+            driver,
+            data: None,
+            hold: Mutex::new(None),
+            acquiring: tokio::sync::Mutex::new(()),
+            granted_by: None,
+        })
+    }
+
+    /// 锁节点的数据，默认为空
+    pub(crate) fn with_data(mut self, data: Vec<u8>) -> LockInternals {
+        self.data = Some(data);
+        self
+    }
+
+    /// 同一个对象持有 `lock` 时不需要排队直接获得锁，用于写锁降级为读锁。
+    /// 此时节点使用 `name` 创建，其他参与者据此识别出由持有者直接获得的节点
+    pub(crate) fn granted_by(
+        mut self,
+        lock: Arc<LockInternals>,
+        name: &'static str,
+    ) -> LockInternals {
+        self.granted_by = Some((lock, name));
+        self
+    }
+
+    /// 获得锁时返回 true，超时返回 false，`timeout` 为 `None` 时一直等待
+    pub(crate) async fn acquire(&self, timeout: Option<Duration>) -> ZKResult<bool> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        // 同一个对象同时只有一个 task 在排队，其余的等它获得锁后直接重入
+        let _acquiring = match deadline {
+            Some(deadline) => {
+                match tokio::time::timeout_at(deadline, self.acquiring.lock()).await {
+                    Ok(acquiring) => acquiring,
+                    Err(_) => return Ok(false),
+                }
+            }
+            None => self.acquiring.lock().await,
+        };
+        if let Some(hold) = self.hold.lock().unwrap().as_mut() {
+            if !self.zk.client.state().is_alive() {
+                return Err(self.zk.client.loss_error());
+            }
+            hold.count += 1;
+            return Ok(true);
+        }
+        // 创建节点之前确定是否直接获得锁，之后释放 `granted_by` 也不会再排队
+        let granted = self
+            .granted_by
+            .as_ref()
+            .and_then(|(lock, name)| lock.is_acquired().then_some(*name));
+        let name = granted.unwrap_or(self.name);
+        let guard = EphemeralGuard::new(
+            &self.zk,
+            create_sequential(&self.zk, &self.path, name, self.data.as_deref()).await?,
+        );
+        match self
+            .wait_for_turn(&guard, granted.is_some(), deadline)
+            .await
+        {
+            Ok(Some(token)) => {
+                let node = guard.keep();
+                debug!("Acquired lock `{}` with token {}", node, token);
+                *self.hold.lock().unwrap() = Some(Hold {
+                    node,
+                    count: 1,
+                    token,
+                });
+                Ok(true)
+            }
+            Ok(None) => {
+                guard.delete().await;
+                Ok(false)
+            }
+            Err(e) => {
+                guard.delete().await;
+                Err(e)
+            }
+        }
+    }
+
+    /// 排队直到可以获得锁，返回 fencing token，超时返回 `None`，`granted` 为 true 时不需要排队
+    async fn wait_for_turn(
+        &self,
+        guard: &EphemeralGuard,
+        granted: bool,
+        deadline: Option<Instant>,
+    ) -> ZKResult<Option<i64>> {
+        let node = guard.path();
+        let (_, name) = paths::split(node);
+        let dir = self.path.as_str();
+        let mut listener = self.zk.subscribe_state();
+        // 重连之后立即重新检查，读取操作按照客户端的重试策略等待连接恢复
+        loop {
+            let nodes = self
+                .zk
+                .retry(|zk| async move { zk.sequential_children(dir, "").await })
+                .await?;
+            let index = nodes
+                .iter()
+                .position(|n| n.name() == name)
+                .ok_or_else(|| node_lost(node))?;
+            let blocker = if granted {
+                None
+            } else {
+                (self.driver)(&nodes, index, self.name)
+            };
+            let predecessor = match blocker {
+                Some(predecessor) => paths::join(&self.path, nodes[predecessor].name()),
+                None => {
+                    // czxid 不会改变，节点不存在说明会话已经过期
+                    let stat = self
+                        .zk
+                        .retry(|zk| async move { zk.exists(node).await })
+                        .await?;
+                    return stat
+                        .map(|stat| Some(stat.czxid))
+                        .ok_or_else(|| node_lost(node));
+                }
+            };
+            let watcher = NotifyWatcher::default();
+            let predecessor = predecessor.as_str();
+            match self
+                .zk
+                .retry(|zk| {
+                    let watcher = watcher.clone();
+                    async move { zk.getw(predecessor, Some(watcher), None).await }
+                })
+                .await
+            {
+                Ok(_) => {
+                    if !watcher.wait(&self.zk, &mut listener, deadline).await? {
+                        return Ok(None);
+                    }
+                }
+                // 前一个节点已经删除，重新检查
+                Err(e) if e.code() == Some(ServerErrorCode::NoNode) => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    pub(crate) async fn release(&self) -> ZKResult<()> {
+        let node = {
+            let mut hold = self.hold.lock().unwrap();
+            match hold.as_mut() {
+                None => {
+                    return Err(ZKError::NotHeld {
+                        path: self.path.clone(),
+                    })
+                }
+                Some(h) if h.count > 1 => {
+                    h.count -= 1;
+                    return Ok(());
+                }
+                Some(_) => hold.take().unwrap().node,
+            }
+        };
+        debug!("Release lock `{}`", node);
+        delete_quietly(&self.zk, &node).await
+    }
+
+    /// 会话过期或者客户端关闭后锁节点已经被删除，不再视为持有锁
+    pub(crate) fn is_acquired(&self) -> bool {
+        self.hold.lock().unwrap().is_some() && self.zk.client.state().is_alive()
+    }
+
+    pub(crate) fn fencing_token(&self) -> Option<i64> {
+        self.hold.lock().unwrap().as_ref().map(|hold| hold.token)
+    }
+
+    pub(crate) fn lock_node(&self) -> Option<String> {
+        self.hold
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|hold| hold.node.clone())
+    }
+
+    /// 所有参与者的节点路径，按照排队顺序排列
+    pub(crate) async fn participant_nodes(&self) -> ZKResult<Vec<String>> {
+        let nodes = match self.zk.sequential_children(&self.path, "").await {
+            Ok(nodes) => nodes,
+            Err(e) if e.code() == Some(ServerErrorCode::NoNode) => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        Ok(nodes
+            .iter()
+            .map(|node| paths::join(&self.path, node.name()))
+            .collect())
+    }
+}
+
+impl Drop for LockInternals {
+    fn drop(&mut self) {
+        if let Some(hold) = self.hold.get_mut().unwrap().take() {
+            spawn_delete(&self.zk, hold.node);
+        }
+    }
+}
+
+/// 可重入的分布式互斥锁，同一个对象可以多次获得锁，释放相同的次数后才会真正释放。
+/// 对象被释放时如果仍然持有锁，会在后台删除锁节点
+/// # Examples
+///
+/// ```rust,ignore
+/// let mutex = InterProcessMutex::new(&zk, "/locks/job")?;
+/// mutex.acquire().await?;
+/// let token = mutex.fencing_token().unwrap();
+/// storage.write_with_token(token, data).await?;
+/// mutex.release().await?;
+///
+/// if mutex.try_acquire(Duration::from_secs(3)).await? {
+///     // ...
+///     mutex.release().await?;
+/// }
+/// ```
+#[derive(Debug)]
+pub struct InterProcessMutex {
+    internals: Arc<LockInternals>,
+}
+
+impl InterProcessMutex {
+    /// 创建锁对象，此时不会访问服务端
+    /// # Args
+    /// - `zk`： 客户端，锁节点属于该客户端的会话
+    /// - `path`： 锁路径，必须以 "/" 开头，所有参与者必须使用相同的路径
+    /// # Errors
+    ///
+    /// 路径不合法时返回 [`ZKError::PathError`]
+    pub fn new(zk: &ZooKeeper, path: impl AsRef<str>) -> ZKResult<InterProcessMutex> {
+        let internals = LockInternals::new(zk, path.as_ref(), LOCK_NAME, mutex_driver)?;
+        Ok(InterProcessMutex::from_internals(Arc::new(internals)))
+    }
+
+    pub(crate) fn from_internals(internals: Arc<LockInternals>) -> InterProcessMutex {
+        InterProcessMutex { internals }
+    }
+
+    /// 一直等待直到获得锁，已经持有锁时只增加持有次数
+    /// # Errors
+    ///
+    /// 等待过程中会话过期或者客户端关闭时返回异常，已经创建的锁节点会被删除
+    pub async fn acquire(&self) -> ZKResult<()> {
+        self.internals.acquire(None).await?;
+        Ok(())
+    }
+
+    /// 最多等待 `timeout`，超时返回 false，已经创建的锁节点会被删除
+    pub async fn try_acquire(&self, timeout: Duration) -> ZKResult<bool> {
+        self.internals.acquire(Some(timeout)).await
+    }
+
+    /// 释放一次锁，持有次数减为 0 时删除锁节点
+    /// # Errors
+    ///
+    /// 没有持有锁时返回 [`ZKError::NotHeld`]
+    pub async fn release(&self) -> ZKResult<()> {
+        self.internals.release().await
+    }
+
+    /// 当前对象是否持有锁，会话过期或者客户端关闭后返回 false
+    pub fn is_acquired(&self) -> bool {
+        self.internals.is_acquired()
+    }
+
+    /// 本次持有锁的 fencing token，即锁节点的 `czxid`，没有持有锁时为 `None`
+    pub fn fencing_token(&self) -> Option<i64> {
+        self.internals.fencing_token()
+    }
+
+    /// 当前对象持有的锁节点路径
+    pub fn lock_node(&self) -> Option<String> {
+        self.internals.lock_node()
+    }
+
+    /// 所有参与者（持有者和等待者）的锁节点路径，第一个为持有者
+    pub async fn participant_nodes(&self) -> ZKResult<Vec<String>> {
+        self.internals.participant_nodes().await
+    }
+}
@@ -1 +1,189 @@
 //! Advance recipe module
+//!
+//! 基于临时顺序节点和回调实现的分布式工具，语义参考 [Apache Curator](https://curator.apache.org/) 的同名实现：
+//! - [`leader::LeaderLatch`]：领导者选举，序号最小的参与者成为领导者，连接挂起或者会话过期时立即放弃领导权
+//! - [`leader::LeaderSelector`]：领导者选举，成为领导者之后执行调用方提供的任务，任务结束后可以重新排队
+//! - [`lock::InterProcessMutex`]：可重入的分布式互斥锁，每次持有锁都会得到一个单调递增的 fencing token
+//! - [`rwlock::InterProcessReadWriteLock`]：分布式读写锁，读锁共享、写锁独占，支持写锁降级为读锁
+//! - [`semaphore::InterProcessSemaphore`]：分布式计数信号量，最多同时发出 N 个租约，租约被释放时自动归还
+
+use std::sync::Arc;
+
+use tokio::sync::Notify;
+use tokio::time::Instant;
+
+use crate::constants::{CreateMode, OpCode};
+use crate::error::ServerErrorCode;
+use crate::recursive::ParentOptions;
+use crate::state::{ConnectionState, StateListener};
+use crate::watcher::{WatchedEvent, Watcher};
+use crate::{paths, ZKError, ZKResult, ZooKeeper};
+
+pub mod leader;
+pub mod lock;
+pub mod rwlock;
+pub mod semaphore;
+
+/// 回调触发时唤醒等待者，`Notify` 会保存一次唤醒，回调先于等待触发时也不会丢失
+#[derive(Debug, Clone, Default)]
+pub(crate) struct NotifyWatcher(Arc<Notify>);
+
+impl NotifyWatcher {
+    /// 等待回调触发
+    pub(crate) async fn notified(&self) {
+        self.0.notified().await
+    }
+
+    /// 等待回调触发或者重新连接，超过 `deadline` 时返回 false，会话过期或者客户端关闭时返回错误
+    pub(crate) async fn wait(
+        &self,
+        zk: &ZooKeeper,
+        listener: &mut StateListener,
+        deadline: Option<Instant>,
+    ) -> ZKResult<bool> {
+        let timeout = async {
+            match deadline {
+                Some(deadline) => tokio::time::sleep_until(deadline).await,
+                None => std::future::pending().await,
+            }
+        };
+        tokio::pin!(timeout);
+        loop {
+            if !zk.client.state().is_alive() {
+                return Err(zk.client.loss_error());
+            }
+            tokio::select! {
+                _ = self.0.notified() => return Ok(true),
+                _ = &mut timeout => return Ok(false),
+                event = listener.recv() => match event {
+                    Some(event) if event.state.is_terminal() => return Err(zk.client.loss_error()),
+                    // 重连之后重新检查，断开期间的变化由调用方重新读取确认
+                    Some(event) if event.state == ConnectionState::Reconnected => return Ok(true),
+                    Some(_) => {}
+                    None => return Err(zk.client.loss_error()),
+                }
+            }
+        }
+    }
+}
+
+impl Watcher for NotifyWatcher {
+    fn process(&self, _: &WatchedEvent) {
+        self.0.notify_one();
+    }
+}
+
+/// 按照客户端的重试策略删除节点，节点已经不存在时视为成功
+pub(crate) async fn delete_quietly(zk: &ZooKeeper, path: &str) -> ZKResult<()> {
+    zk.retry(|zk| async move {
+        match zk.delete(path).await {
+            Err(e) if e.code() == Some(ServerErrorCode::NoNode) => Ok(()),
+            result => result,
+        }
+    })
+    .await
+}
+
+/// 在 `dir` 下创建名称前缀为 `name`、数据为 `data` 的临时顺序节点，返回节点路径。
+/// `dir` 不存在时创建为容器节点，最后一个参与者离开后由服务端清理
+pub(crate) async fn create_sequential(
+    zk: &ZooKeeper,
+    dir: &str,
+    name: &str,
+    data: Option<&[u8]>,
+) -> ZKResult<String> {
+    let prefix = paths::join(dir, name);
+    let acl_list = zk.default_acl();
+    let mut created_parents = false;
+    loop {
+        match zk
+            .create_with_retry(
+                &prefix,
+                data,
+                acl_list.clone(),
+                CreateMode::EphemeralSequential,
+            )
+            .await
+        {
+            Err(e) if e.code() == Some(ServerErrorCode::NoNode) && !created_parents => {
+                let parents = ParentOptions::default().container(true);
+                match zk
+                    .create_all_with(dir, None, acl_list.clone(), CreateMode::Container, &parents)
+                    .await
+                {
+                    Err(e) if e.code() != Some(ServerErrorCode::NodeExists) => return Err(e),
+                    _ => created_parents = true,
+                }
+            }
+            result => return result,
+        }
+    }
+}
+
+/// 自己创建的临时节点不见了，说明会话已经过期
+pub(crate) fn node_lost(node: &str) -> ZKError {
+    ZKError::ServerError(ServerErrorCode::NoNode, -101).with_operation(OpCode::GetChildren, node)
+}
+
+/// 正在参与竞争的临时节点，没有调用 [`EphemeralGuard::keep`] 就被释放时（例如等待的 future 被取消）
+/// 在后台删除节点，避免遗留的节点阻塞其他参与者直到会话过期
+#[derive(Debug)]
+pub(crate) struct EphemeralGuard {
+    zk: ZooKeeper,
+    path: Option<String>,
+}
+
+impl EphemeralGuard {
+    pub(crate) fn new(zk: &ZooKeeper, path: String) -> EphemeralGuard {
+        EphemeralGuard {
+            zk: zk.clone(),
+            path: Some(path),
+        }
+    }
+
+    pub(crate) fn path(&self) -> &str {
+        self.path.as_deref().unwrap()
+    }
+
+    /// 保留节点，返回节点路径
+    pub(crate) fn keep(mut self) -> String {
+        self.path.take().unwrap()
+    }
+
+    /// 立即删除节点，节点已经不存在时视为成功
+    pub(crate) async fn release(mut self) -> ZKResult<()> {
+        let path = self.path.take().unwrap();
+        delete_quietly(&self.zk, &path).await
+    }
+
+    /// 立即删除节点，忽略删除失败，节点会在会话过期后由服务端删除
+    pub(crate) async fn delete(self) {
+        let path = self.path().to_string();
+        if let Err(e) = self.release().await {
+            warn!("Delete `{}` failed: {}", path, e);
+        }
+    }
+}
+
+impl Drop for EphemeralGuard {
+    fn drop(&mut self) {
+        if let Some(path) = self.path.take() {
+            spawn_delete(&self.zk, path);
+        }
+    }
+}
+
+/// 在后台删除节点，不在 tokio 运行时中时只能等待会话过期
+pub(crate) fn spawn_delete(zk: &ZooKeeper, path: String) {
+    match tokio::runtime::Handle::try_current() {
+        Ok(handle) => {
+            let zk = zk.clone();
+            handle.spawn(async move {
+                if let Err(e) = delete_quietly(&zk, &path).await {
+                    warn!("Delete `{}` failed: {}", path, e);
+                }
+            });
+        }
+        Err(_) => warn!("No tokio runtime to delete `{}`", path),
+    }
+}
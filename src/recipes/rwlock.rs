@@ -0,0 +1,124 @@
+//! # 分布式读写锁模块
+//! 读者和写者在同一个锁路径下分别创建 `read-` 和 `write-` 临时顺序节点，按照序号排队：
+//! - 写锁：排在所有节点的第一位时获得锁，否则监听前一个节点
+//! - 读锁：前面没有写节点时获得锁，否则监听前面最后一个写节点，因此多个读者可以同时持有锁
+//!
+//! 同一个对象持有写锁时可以直接获得读锁，之后释放写锁即完成降级。降级得到的读节点使用 `read-granted-` 前缀，
+//! 它排在已经排队的写者之后，写者需要同时等待这些节点，否则写锁释放后排队的写者会和降级的读者同时持有锁。
+//! 不支持读锁升级为写锁，持有读锁时获取写锁会一直等待自己的读节点
+
+use std::sync::Arc;
+
+use crate::recipes::lock::{InterProcessMutex, LockInternals};
+use crate::sequential::SequentialName;
+use crate::{ZKResult, ZooKeeper};
+
+const READ_NAME: &str = "read-";
+const WRITE_NAME: &str = "write-";
+/// 持有写锁时直接获得的读节点
+const GRANTED_READ_NAME: &str = "read-granted-";
+
+fn is_lock_node(node: &SequentialName) -> bool {
+    matches!(node.prefix(), READ_NAME | WRITE_NAME | GRANTED_READ_NAME)
+}
+
+/// 写锁：前面有任何读节点或者写节点时监听前一个节点。
+/// 降级得到的读节点只能在持有写锁时创建，所以即使排在自己后面也一定早于自己获得锁，同样需要等待
+fn write_driver(nodes: &[SequentialName], index: usize, _: &str) -> Option<usize> {
+    nodes[..index].iter().rposition(is_lock_node).or_else(|| {
+        nodes[index + 1..]
+            .iter()
+            .position(|node| node.prefix() == GRANTED_READ_NAME)
+            .map(|granted| index + 1 + granted)
+    })
+}
+
+/// 读锁：只被前面的写节点阻塞，监听前面最后一个写节点
+fn read_driver(nodes: &[SequentialName], index: usize, _: &str) -> Option<usize> {
+    nodes[..index]
+        .iter()
+        .rposition(|node| node.prefix() == WRITE_NAME)
+}
+
+/// 可重入的分布式读写锁，读锁和写锁都是 [`InterProcessMutex`]，各自独立计数
+/// # Examples
+///
+/// ```rust,ignore
+/// let lock = InterProcessReadWriteLock::new(&zk, "/locks/config")?;
+/// lock.read_lock().acquire().await?;
+/// let config = zk.get("/config", None).await?;
+/// lock.read_lock().release().await?;
+///
+/// // 写锁降级为读锁
+/// lock.write_lock().acquire().await?;
+/// zk.set("/config", b"v2").await?;
+/// lock.read_lock().acquire().await?;
+/// lock.write_lock().release().await?;
+/// ```
+#[derive(Debug)]
+pub struct InterProcessReadWriteLock {
+    read: InterProcessMutex,
+    write: InterProcessMutex,
+}
+
+impl InterProcessReadWriteLock {
+    /// 创建读写锁对象，此时不会访问服务端
+    /// # Args
+    /// - `zk`： 客户端，锁节点属于该客户端的会话
+    /// - `path`： 锁路径，必须以 "/" 开头，所有参与者必须使用相同的路径
+    /// # Errors
+    ///
+    /// 路径不合法时返回 [`crate::ZKError::PathError`]
+    pub fn new(zk: &ZooKeeper, path: impl AsRef<str>) -> ZKResult<InterProcessReadWriteLock> {
+        let path = path.as_ref();
+        let write = Arc::new(LockInternals::new(zk, path, WRITE_NAME, write_driver)?);
+        let read = LockInternals::new(zk, path, READ_NAME, read_driver)?
+            .granted_by(write.clone(), GRANTED_READ_NAME);
+        Ok(InterProcessReadWriteLock {
+            read: InterProcessMutex::from_internals(Arc::new(read)),
+            write: InterProcessMutex::from_internals(write),
+        })
+    }
+
+    /// 共享的读锁，前面没有等待中或者持有中的写锁时即可获得
+    pub fn read_lock(&self) -> &InterProcessMutex {
+        &self.read
+    }
+
+    /// 独占的写锁，排在所有读者和写者之后
+    pub fn write_lock(&self) -> &InterProcessMutex {
+        &self.write
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn nodes(names: &[&str]) -> Vec<SequentialName> {
+        names
+            .iter()
+            .map(|name| SequentialName::parse(*name).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_drivers() {
+        let nodes = nodes(&[
+            "read-0000000000",
+            "read-0000000001",
+            "write-0000000002",
+            "read-0000000003",
+            "other-0000000004",
+            "write-0000000005",
+            "read-granted-0000000006",
+        ]);
+        assert_eq!(read_driver(&nodes, 1, READ_NAME), None);
+        assert_eq!(read_driver(&nodes, 3, READ_NAME), Some(2));
+        assert_eq!(write_driver(&nodes, 2, WRITE_NAME), Some(1));
+        assert_eq!(write_driver(&nodes, 5, WRITE_NAME), Some(3));
+        assert_eq!(write_driver(&nodes[2..], 0, WRITE_NAME), Some(4));
+        assert_eq!(write_driver(&nodes[2..6], 0, WRITE_NAME), None);
+        assert_eq!(read_driver(&nodes, 6, READ_NAME), Some(5));
+    }
+}
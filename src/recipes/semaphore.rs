@@ -0,0 +1,315 @@
+//! # 分布式信号量模块
+//! 每个租约是 `leases` 下的一个临时顺序节点，排在前 N 位的节点有效。创建租约和等待租约由 `locks`
+//! 下的互斥锁串行化，同一时间只有一个等待者在监听，租约释放时不会唤醒所有等待者。
+//! 一次获取多个租约时持有互斥锁直到全部获得，多个参与者各自拿到一部分租约后互相等待的情况不会发生
+//!
+//! ```text
+//! /semaphores/db                      信号量路径
+//! /semaphores/db/locks                创建租约的互斥锁
+//! /semaphores/db/leases/_c_...-lease-0000000000
+//! /semaphores/db/leases/_c_...-lease-0000000001
+//! ```
+
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+use crate::error::ServerErrorCode;
+use crate::recipes::lock::InterProcessMutex;
+use crate::recipes::{create_sequential, node_lost, EphemeralGuard, NotifyWatcher};
+use crate::sequential::SequentialName;
+use crate::{paths, ZKError, ZKResult, ZooKeeper};
+
+const LOCK_DIR: &str = "locks";
+const LEASE_DIR: &str = "leases";
+const LEASE_NAME: &str = "lease-";
+
+#[derive(Debug, Clone)]
+enum MaxLeases {
+    Fixed(usize),
+    Shared(String),
+}
+
+/// 信号量的一个租约，调用 [`Lease::release`] 或者被释放时归还，被释放时在后台删除租约节点
+#[derive(Debug)]
+pub struct Lease {
+    guard: EphemeralGuard,
+}
+
+impl Lease {
+    /// 租约节点的路径
+    pub fn node(&self) -> &str {
+        self.guard.path()
+    }
+
+    /// 立即归还租约
+    /// # Errors
+    ///
+    /// 删除租约节点失败时返回异常，节点会在会话过期后由服务端删除
+    pub async fn release(self) -> ZKResult<()> {
+        self.guard.release().await
+    }
+}
+
+/// 分布式计数信号量，同一个路径下最多同时存在 N 个有效租约，N 为固定值或者来自共享计数节点。
+/// 所有参与者必须使用相同的 N，使用共享计数节点可以在运行时调整
+/// # Examples
+///
+/// ```rust,ignore
+/// let semaphore = InterProcessSemaphore::new(&zk, "/semaphores/db", 4)?;
+/// let lease = semaphore.acquire().await?;
+/// run_batch_job().await;
+/// lease.release().await?;
+///
+/// if let Some(leases) = semaphore.try_acquire_many(2, Duration::from_secs(10)).await? {
+///     // 租约被释放时自动归还
+/// }
+/// ```
+#[derive(Debug)]
+pub struct InterProcessSemaphore {
+    zk: ZooKeeper,
+    path: String,
+    leases_path: String,
+    max_leases: MaxLeases,
+    lock: InterProcessMutex,
+    // 互斥锁对同一个对象可重入，同一个对象的多个 task 需要在本地排队
+    acquiring: tokio::sync::Mutex<()>,
+}
+
+impl InterProcessSemaphore {
+    /// 创建租约数量固定为 `max_leases` 的信号量对象，此时不会访问服务端
+    /// # Args
+    /// - `zk`： 客户端，租约节点属于该客户端的会话
+    /// - `path`： 信号量路径，必须以 "/" 开头
+    /// - `max_leases`： 最多同时存在的租约数量
+    /// # Errors
+    ///
+    /// 路径不合法时返回 [`ZKError::PathError`]
+    pub fn new(
+        zk: &ZooKeeper,
+        path: impl AsRef<str>,
+        max_leases: usize,
+    ) -> ZKResult<InterProcessSemaphore> {
+        InterProcessSemaphore::create(zk, path.as_ref(), MaxLeases::Fixed(max_leases))
+    }
+
+    /// 创建租约数量来自共享计数节点的信号量对象，计数节点的数据为十进制文本，例如 `b"4"`。
+    /// 修改计数节点后等待中的参与者会重新检查，已经发出的租约不受影响
+    /// # Args
+    /// - `zk`： 客户端，租约节点属于该客户端的会话
+    /// - `path`： 信号量路径，必须以 "/" 开头
+    /// - `count_path`： 共享计数节点的路径，获取租约时必须存在
+    /// # Errors
+    ///
+    /// 路径不合法时返回 [`ZKError::PathError`]
+    pub fn with_shared_count(
+        zk: &ZooKeeper,
+        path: impl AsRef<str>,
+        count_path: impl AsRef<str>,
+    ) -> ZKResult<InterProcessSemaphore> {
+        let count_path = count_path.as_ref();
+        paths::validate_path(count_path)?;
+        let max_leases = MaxLeases::Shared(count_path.to_string());
+        InterProcessSemaphore::create(zk, path.as_ref(), max_leases)
+    }
+
+    fn create(
+        zk: &ZooKeeper,
+        path: &str,
+        max_leases: MaxLeases,
+    ) -> ZKResult<InterProcessSemaphore> {
+        paths::validate_path(path)?;
+        Ok(InterProcessSemaphore {
+            zk: zk.clone(),
+            path: path.to_string(),
+            leases_path: paths::join(path, LEASE_DIR),
+            max_leases,
+            lock: InterProcessMutex::new(zk, paths::join(path, LOCK_DIR))?,
+            acquiring: tokio::sync::Mutex::new(()),
+        })
+    }
+
+    /// 一直等待直到获得一个租约
+    /// # Errors
+    ///
+    /// 等待过程中会话过期或者客户端关闭时返回异常；共享计数节点不存在时返回 `NoNode`，
+    /// 数据不合法时返回 [`ZKError::InvalidCount`]
+    pub async fn acquire(&self) -> ZKResult<Lease> {
+        let mut leases = self.acquire_leases(1, None).await?.unwrap();
+        Ok(leases.pop().unwrap())
+    }
+
+    /// 一直等待直到同时获得 `count` 个租约，参考 [`InterProcessSemaphore::acquire`]。
+    /// 等待期间持有创建租约的互斥锁，其他参与者在此之后排队；使用共享计数节点时，计数小于 `count` 会一直等待到计数被调大
+    /// # Errors
+    ///
+    /// `count` 为 0 或者超过固定的租约上限时返回 [`ZKError::InvalidLeaseCount`]
+    pub async fn acquire_many(&self, count: usize) -> ZKResult<Vec<Lease>> {
+        Ok(self.acquire_leases(count, None).await?.unwrap())
+    }
+
+    /// 最多等待 `timeout`，超时返回 `None`，参考 [`InterProcessSemaphore::acquire`]
+    pub async fn try_acquire(&self, timeout: Duration) -> ZKResult<Option<Lease>> {
+        let leases = self.acquire_leases(1, Some(timeout)).await?;
+        Ok(leases.and_then(|mut leases| leases.pop()))
+    }
+
+    /// 最多等待 `timeout` 获得 `count` 个租约，超时返回 `None`，已经获得的租约会被归还，
+    /// 参考 [`InterProcessSemaphore::acquire_many`]
+    pub async fn try_acquire_many(
+        &self,
+        count: usize,
+        timeout: Duration,
+    ) -> ZKResult<Option<Vec<Lease>>> {
+        self.acquire_leases(count, Some(timeout)).await
+    }
+
+    /// 所有租约节点的路径，包括正在等待的租约，按照创建顺序排列
+    pub async fn lease_nodes(&self) -> ZKResult<Vec<String>> {
+        let nodes = match self.zk.sequential_children(&self.leases_path, "").await {
+            Ok(nodes) => nodes,
+            Err(e) if e.code() == Some(ServerErrorCode::NoNode) => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        Ok(nodes
+            .iter()
+            .map(|node| paths::join(&self.leases_path, node.name()))
+            .collect())
+    }
+
+    async fn acquire_leases(
+        &self,
+        count: usize,
+        timeout: Option<Duration>,
+    ) -> ZKResult<Option<Vec<Lease>>> {
+        let too_many =
+            matches!(self.max_leases, MaxLeases::Fixed(max_leases) if count > max_leases);
+        if count == 0 || too_many {
+            return Err(ZKError::InvalidLeaseCount {
+                path: self.path.clone(),
+                count,
+            });
+        }
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let _acquiring = match deadline {
+            Some(deadline) => {
+                match tokio::time::timeout_at(deadline, self.acquiring.lock()).await {
+                    Ok(acquiring) => acquiring,
+                    Err(_) => return Ok(None),
+                }
+            }
+            None => self.acquiring.lock().await,
+        };
+        let locked = match deadline {
+            Some(deadline) => {
+                let timeout = deadline.saturating_duration_since(Instant::now());
+                self.lock.try_acquire(timeout).await?
+            }
+            None => self.lock.acquire().await.map(|_| true)?,
+        };
+        if !locked {
+            return Ok(None);
+        }
+        let result = self.wait_for_leases(count, deadline).await;
+        let released = self.lock.release().await;
+        let leases = result?;
+        released?;
+        Ok(leases)
+    }
+
+    /// 持有互斥锁时依次获得 `count` 个租约，超时或者出错时归还已经获得的租约
+    async fn wait_for_leases(
+        &self,
+        count: usize,
+        deadline: Option<Instant>,
+    ) -> ZKResult<Option<Vec<Lease>>> {
+        let mut leases = Vec::with_capacity(count);
+        for _ in 0..count {
+            // 出错时已经获得的租约被释放，在后台归还
+            match self.wait_for_lease(deadline).await? {
+                Some(lease) => leases.push(lease),
+                None => {
+                    for lease in leases {
+                        if let Err(e) = lease.release().await {
+                            warn!("Return lease failed: {}", e);
+                        }
+                    }
+                    return Ok(None);
+                }
+            }
+        }
+        Ok(Some(leases))
+    }
+
+    /// 持有互斥锁时创建租约节点，等待排到前 N 位，超时返回 `None`
+    async fn wait_for_lease(&self, deadline: Option<Instant>) -> ZKResult<Option<Lease>> {
+        let node = create_sequential(&self.zk, &self.leases_path, LEASE_NAME, None).await?;
+        let guard = EphemeralGuard::new(&self.zk, node);
+        match self.wait_for_turn(&guard, deadline).await {
+            Ok(true) => {
+                debug!("Acquired lease `{}`", guard.path());
+                Ok(Some(Lease { guard }))
+            }
+            Ok(false) => {
+                guard.delete().await;
+                Ok(None)
+            }
+            Err(e) => {
+                guard.delete().await;
+                Err(e)
+            }
+        }
+    }
+
+    async fn wait_for_turn(
+        &self,
+        guard: &EphemeralGuard,
+        deadline: Option<Instant>,
+    ) -> ZKResult<bool> {
+        let node = guard.path();
+        let (_, name) = paths::split(node);
+        let dir = self.leases_path.as_str();
+        let mut listener = self.zk.subscribe_state();
+        loop {
+            // 租约节点和共享计数节点的变化都会唤醒等待者
+            let watcher = NotifyWatcher::default();
+            let max_leases = self.max_leases(&watcher).await?;
+            let children = self
+                .zk
+                .retry(|zk| {
+                    let watcher = watcher.clone();
+                    async move { zk.childrenw(dir, Some(watcher)).await }
+                })
+                .await?;
+            let index = SequentialName::sort_children(&children)
+                .iter()
+                .position(|n| n.name() == name)
+                .ok_or_else(|| node_lost(node))?;
+            if index < max_leases {
+                return Ok(true);
+            }
+            if !watcher.wait(&self.zk, &mut listener, deadline).await? {
+                return Ok(false);
+            }
+        }
+    }
+
+    async fn max_leases(&self, watcher: &NotifyWatcher) -> ZKResult<usize> {
+        let count_path = match &self.max_leases {
+            MaxLeases::Fixed(max_leases) => return Ok(*max_leases),
+            MaxLeases::Shared(count_path) => count_path.as_str(),
+        };
+        let data = self
+            .zk
+            .retry(|zk| {
+                let watcher = watcher.clone();
+                async move { zk.getw(count_path, Some(watcher), None).await }
+            })
+            .await?;
+        let text = String::from_utf8_lossy(&data);
+        text.trim().parse().map_err(|_| ZKError::InvalidCount {
+            path: count_path.to_string(),
+            data: text.to_string(),
+        })
+    }
+}
@@ -0,0 +1,311 @@
+//! # 递归操作模块
+//! 提供递归创建父节点的 [`ZooKeeper::create_all`] 和递归删除子树的 [`ZooKeeper::delete_all`]
+
+use crate::constants::CreateMode;
+use crate::error::ServerErrorCode;
+use crate::protocol::req::{Op, ACL};
+use crate::{paths, ZKError, ZKResult, ZooKeeper};
+
+/// 父节点被并发删除（例如空的容器节点被服务端清理）时，重新创建父节点的次数
+const CREATE_ATTEMPTS: usize = 3;
+/// ZooKeeper 自身使用的节点，服务端不允许删除
+const SYSTEM_PATH: &str = "/zookeeper";
+
+/// 递归创建时父节点的配置
+/// # Examples
+///
+/// ```rust,ignore
+/// let options = ParentOptions::default().container(true).acl(CREATOR_ALL_ACL.clone());
+/// zk.create_all_with("/a/b/c", None, ACL::world_acl(), CreateMode::Persistent, &options).await?;
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ParentOptions {
+    container: bool,
+    acl_list: Option<Vec<ACL>>,
+}
+
+impl ParentOptions {
+    /// 父节点是否创建为容器节点，子节点全部删除后由服务端自动清理，默认为持久节点
+    pub fn container(mut self, container: bool) -> Self {
+        self.container = container;
+        self
+    }
+
+    /// 父节点的 ACL 列表，默认与目标节点相同
+    pub fn acl(mut self, acl_list: Vec<ACL>) -> Self {
+        self.acl_list = Some(acl_list);
+        self
+    }
+
+    fn create_mode(&self) -> CreateMode {
+        if self.container {
+            CreateMode::Container
+        } else {
+            CreateMode::Persistent
+        }
+    }
+}
+
+/// 递归删除的配置
+/// # Examples
+///
+/// ```rust,ignore
+/// let options = DeleteOptions::default().max_nodes(1000).dry_run(true);
+/// let planned = zk.delete_all_with("/app", &options).await?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct DeleteOptions {
+    max_nodes: Option<usize>,
+    dry_run: bool,
+    protect_system: bool,
+    batch_size: usize,
+}
+
+impl Default for DeleteOptions {
+    fn default() -> Self {
+        DeleteOptions {
+            max_nodes: None,
+            dry_run: false,
+            protect_system: true,
+            batch_size: 500,
+        }
+    }
+}
+
+impl DeleteOptions {
+    /// 子树的节点数（包含目标节点）超过该值时不删除任何节点，返回 [`ZKError::TooManyNodes`]，默认不限制
+    pub fn max_nodes(mut self, max_nodes: usize) -> Self {
+        self.max_nodes = Some(max_nodes);
+        self
+    }
+
+    /// 只返回计划删除的节点，不实际删除，默认为 false
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// 拒绝删除 "/" 和 "/zookeeper"，默认为 true。关闭后删除 "/" 会清空所有数据，
+    /// 根节点和 "/zookeeper" 本身始终不会被删除
+    pub fn protect_system(mut self, protect_system: bool) -> Self {
+        self.protect_system = protect_system;
+        self
+    }
+
+    /// 单个 multi 请求中删除的节点数，默认 500，为 1 时逐个删除
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+}
+
+impl ZooKeeper {
+    /// 创建目标路径的节点，不存在的父节点会自动创建为持久节点，父节点的 ACL 与目标节点相同，
+    /// 参考 [`ZooKeeper::create_all_with`]
+    /// # Examples
+    /// ```rust,ignore
+    /// let path = zk.create_all("/a/b/c", Some(b"data"), ACL::world_acl(), CreateMode::Persistent).await?;
+    /// ```
+    pub async fn create_all(
+        &self,
+        path: impl AsRef<str>,
+        data: Option<&[u8]>,
+        acl_list: Vec<ACL>,
+        create_model: CreateMode,
+    ) -> ZKResult<String> {
+        self.create_all_with(
+            path,
+            data,
+            acl_list,
+            create_model,
+            &ParentOptions::default(),
+        )
+        .await
+    }
+
+    /// 创建目标路径的节点，并按照 `parents` 的配置创建不存在的父节点。
+    /// 父节点已经被其他客户端创建时视为成功，目标节点已经存在时返回 `NodeExists`
+    /// # Examples
+    /// ```rust,ignore
+    /// let options = ParentOptions::default().container(true);
+    /// let path = zk.create_all_with("/queue/jobs/job-", None, ACL::world_acl(), CreateMode::PersistentSequential, &options)
+    ///        .await?;
+    /// ```
+    ///
+    /// # Args
+    /// - `path`： 目标路径，必须以 "/" 开头
+    /// - `data`： 节点的数据，可选，父节点没有数据
+    /// - `acl`： 该节点的权限数据，可以有多个，参考 [`ACL`]
+    /// - `create_model`： 节点的模式，参考 [`CreateMode`]
+    /// - `parents`： 父节点的配置，参考 [`ParentOptions`]
+    /// # Returns
+    /// - `String`：实际创建的路径
+    pub async fn create_all_with(
+        &self,
+        path: impl AsRef<str>,
+        data: Option<&[u8]>,
+        acl_list: Vec<ACL>,
+        create_model: CreateMode,
+        parents: &ParentOptions,
+    ) -> ZKResult<String> {
+        let path = path.as_ref();
+        paths::validate_path(path)?;
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            match self
+                .create(path, data, acl_list.clone(), create_model)
+                .await
+            {
+                Err(e)
+                    if e.code() == Some(ServerErrorCode::NoNode) && attempt < CREATE_ATTEMPTS =>
+                {
+                    let parent_acl = parents.acl_list.as_ref().unwrap_or(&acl_list);
+                    self.create_parents(path, parent_acl, parents.create_mode())
+                        .await?;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// 从根节点开始依次创建目标路径的所有父节点
+    async fn create_parents(
+        &self,
+        path: &str,
+        acl_list: &[ACL],
+        create_model: CreateMode,
+    ) -> ZKResult<()> {
+        let (parent, _) = paths::split(path);
+        let ancestors = parent
+            .match_indices('/')
+            .skip(1)
+            .map(|(i, _)| &parent[..i])
+            .chain(std::iter::once(parent))
+            .filter(|ancestor| *ancestor != "/");
+        for ancestor in ancestors {
+            match self
+                .create(ancestor, None, acl_list.to_vec(), create_model)
+                .await
+            {
+                Ok(_) => {}
+                Err(e) if e.code() == Some(ServerErrorCode::NodeExists) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    /// 使用默认配置递归删除目标节点以及所有子孙节点，参考 [`ZooKeeper::delete_all_with`]
+    /// # Examples
+    /// ```rust,ignore
+    /// let deleted = zk.delete_all("/app").await?;
+    /// ```
+    pub async fn delete_all(&self, path: impl AsRef<str>) -> ZKResult<Vec<String>> {
+        self.delete_all_with(path, &DeleteOptions::default()).await
+    }
+
+    /// 深度优先遍历子树，先删除叶子节点再删除父节点，删除请求按照 `batch_size` 合并为 multi。
+    /// 其他客户端同时删除导致的 `NoNode` 会被忽略
+    /// # Examples
+    /// ```rust,ignore
+    /// let options = DeleteOptions::default().max_nodes(1000);
+    /// let deleted = zk.delete_all_with("/app", &options).await?;
+    /// ```
+    ///
+    /// # Args
+    /// - `path`： 目标路径，必须以 "/" 开头
+    /// - `options`： 删除配置，参考 [`DeleteOptions`]
+    /// # Returns
+    /// - `Vec<String>`：按删除顺序排列的节点路径，开启 dry run 时为计划删除的节点
+    /// # Errors
+    ///
+    /// 目标为受保护的路径时返回 [`ZKError::PathError`]，节点数超过限制时返回 [`ZKError::TooManyNodes`]
+    pub async fn delete_all_with(
+        &self,
+        path: impl AsRef<str>,
+        options: &DeleteOptions,
+    ) -> ZKResult<Vec<String>> {
+        let path = path.as_ref();
+        paths::validate_path(path)?;
+        if options.protect_system && (path == "/" || self.is_system_path(path)) {
+            return Err(ZKError::PathError(
+                path.to_string(),
+                "Refuse to delete system path".into(),
+            ));
+        }
+        let planned = self.plan_delete(path, options.max_nodes).await?;
+        if options.dry_run {
+            return Ok(planned);
+        }
+        let mut deleted = Vec::with_capacity(planned.len());
+        for batch in planned.chunks(options.batch_size) {
+            if batch.len() > 1 {
+                let ops = batch.iter().map(|node| Op::delete(node.as_str(), -1));
+                match self.multi(ops.collect()).await {
+                    Ok(_) => {
+                        deleted.extend_from_slice(batch);
+                        continue;
+                    }
+                    // 部分节点已经被删除，整个事务失败，改为逐个删除
+                    Err(e) if e.code() == Some(ServerErrorCode::NoNode) => {}
+                    Err(e) => return Err(e),
+                }
+            }
+            for node in batch {
+                match self.delete(node.as_str()).await {
+                    Ok(_) => deleted.push(node.clone()),
+                    Err(e) if e.code() == Some(ServerErrorCode::NoNode) => {}
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+        Ok(deleted)
+    }
+
+    /// 后序遍历子树，子节点在父节点之前
+    async fn plan_delete(&self, path: &str, max_nodes: Option<usize>) -> ZKResult<Vec<String>> {
+        let mut planned = Vec::new();
+        let mut discovered = 1;
+        let mut stack = vec![(path.to_string(), false)];
+        while let Some((node, expanded)) = stack.pop() {
+            if expanded {
+                planned.push(node);
+                continue;
+            }
+            let children = match self.children(node.as_str()).await {
+                Ok(children) => children,
+                Err(e) if e.code() == Some(ServerErrorCode::NoNode) => continue,
+                Err(e) => return Err(e),
+            };
+            discovered += children.len();
+            if let Some(limit) = max_nodes {
+                if discovered > limit {
+                    return Err(ZKError::TooManyNodes {
+                        path: path.to_string(),
+                        limit,
+                    });
+                }
+            }
+            // 根节点和系统节点无法删除，只删除它们的子孙节点
+            if node != "/" && !self.is_system_path(&node) {
+                stack.push((node.clone(), true));
+            }
+            for child in children.iter().rev() {
+                let child = paths::join(&node, child);
+                if !self.is_system_path(&child) {
+                    stack.push((child, false));
+                }
+            }
+        }
+        Ok(planned)
+    }
+
+    /// 拼接 chroot 后是否为 "/zookeeper" 或者它的子孙节点
+    fn is_system_path(&self, path: &str) -> bool {
+        let full_path = self.client.get_path(path);
+        full_path
+            .strip_prefix(SYSTEM_PATH)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+    }
+}
@@ -0,0 +1,248 @@
+//! # 重试策略模块
+//! 定义 [`RetryPolicy`] 以及常用的内置策略，配合 [`ZooKeeper::retry`] 在连接断开等暂时性错误时自动重试
+//!
+//! [`ZooKeeper::retry`]: crate::ZooKeeper::retry
+
+use std::fmt::Debug;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use crate::ZKResult;
+
+/// 重试策略，决定失败的操作是否需要重试以及重试前需要等待多久
+pub trait RetryPolicy: Debug + Send + Sync {
+    /// 操作失败并且错误可以重试时调用
+    /// # Args
+    /// - `retry_count`： 已经重试的次数，第一次失败时为 0
+    /// - `elapsed`： 从第一次执行操作到现在经过的时间
+    /// # Returns
+    /// - `Some(Duration)`： 等待该时间后重试
+    /// - `None`： 不再重试，返回最后一次的错误
+    fn allow_retry(&self, retry_count: u32, elapsed: Duration) -> Option<Duration>;
+}
+
+/// 最多重试 `n` 次，每次重试前等待固定的时间
+#[derive(Debug, Clone)]
+pub struct RetryNTimes {
+    n: u32,
+    sleep: Duration,
+}
+
+impl RetryNTimes {
+    pub fn new(n: u32, sleep: Duration) -> RetryNTimes {
+        RetryNTimes { n, sleep }
+    }
+}
+
+impl RetryPolicy for RetryNTimes {
+    fn allow_retry(&self, retry_count: u32, _: Duration) -> Option<Duration> {
+        (retry_count < self.n).then_some(self.sleep)
+    }
+}
+
+/// 指数退避重试，第 `k` 次重试前等待 `base_sleep` 乘以 `[1, 2^(k+1))` 之间的随机数，
+/// 随机化可以避免大量客户端在同一时刻重试
+/// # Examples
+///
+/// ```rust,ignore
+/// let policy = ExponentialBackoffRetry::new(Duration::from_millis(100), 5)
+///     .max_sleep(Duration::from_secs(2));
+/// ```
+#[derive(Debug, Clone)]
+pub struct ExponentialBackoffRetry {
+    base_sleep: Duration,
+    max_retries: u32,
+    max_sleep: Option<Duration>,
+}
+
+impl ExponentialBackoffRetry {
+    /// 为了避免溢出，重试次数最多为 29 次
+    const MAX_RETRIES_LIMIT: u32 = 29;
+
+    /// # Args
+    /// - `base_sleep`： 基础等待时间
+    /// - `max_retries`： 最多重试次数，超过 29 时按 29 处理
+    pub fn new(base_sleep: Duration, max_retries: u32) -> ExponentialBackoffRetry {
+        ExponentialBackoffRetry {
+            base_sleep,
+            max_retries: max_retries.min(Self::MAX_RETRIES_LIMIT),
+            max_sleep: None,
+        }
+    }
+
+    /// 每次重试前最多等待的时间，默认不限制
+    pub fn max_sleep(mut self, max_sleep: Duration) -> Self {
+        self.max_sleep = Some(max_sleep);
+        self
+    }
+}
+
+impl RetryPolicy for ExponentialBackoffRetry {
+    fn allow_retry(&self, retry_count: u32, _: Duration) -> Option<Duration> {
+        if retry_count >= self.max_retries {
+            return None;
+        }
+        let factor = rand::random::<u32>() % (1 << (retry_count + 1));
+        let sleep = self.base_sleep * factor.max(1);
+        Some(match self.max_sleep {
+            Some(max_sleep) => sleep.min(max_sleep),
+            None => sleep,
+        })
+    }
+}
+
+/// 在 `max_elapsed` 时间内一直重试，每次重试前等待固定的时间
+#[derive(Debug, Clone)]
+pub struct RetryUntilElapsed {
+    max_elapsed: Duration,
+    sleep: Duration,
+}
+
+impl RetryUntilElapsed {
+    pub fn new(max_elapsed: Duration, sleep: Duration) -> RetryUntilElapsed {
+        RetryUntilElapsed { max_elapsed, sleep }
+    }
+}
+
+impl RetryPolicy for RetryUntilElapsed {
+    fn allow_retry(&self, _: u32, elapsed: Duration) -> Option<Duration> {
+        (elapsed < self.max_elapsed).then_some(self.sleep)
+    }
+}
+
+/// 一直重试直到成功或者遇到不可重试的错误，每次重试前等待固定的时间
+#[derive(Debug, Clone)]
+pub struct RetryForever {
+    sleep: Duration,
+}
+
+impl RetryForever {
+    pub fn new(sleep: Duration) -> RetryForever {
+        RetryForever { sleep }
+    }
+}
+
+impl RetryPolicy for RetryForever {
+    fn allow_retry(&self, _: u32, _: Duration) -> Option<Duration> {
+        Some(self.sleep)
+    }
+}
+
+/// 按照策略执行操作，只有 [`ZKError::is_retryable`] 的错误才会重试
+///
+/// [`ZKError::is_retryable`]: crate::ZKError::is_retryable
+pub(crate) async fn run<T, F, Fut>(policy: &dyn RetryPolicy, mut operation: F) -> ZKResult<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = ZKResult<T>>,
+{
+    let start = Instant::now();
+    let mut retry_count = 0;
+    loop {
+        match operation().await {
+            Err(e) if e.is_retryable() => match policy.allow_retry(retry_count, start.elapsed()) {
+                Some(sleep) => {
+                    debug!(
+                        "Retry {} after {:?} because of: {}",
+                        retry_count + 1,
+                        sleep,
+                        e
+                    );
+                    tokio::time::sleep(sleep).await;
+                    retry_count += 1;
+                }
+                None => return Err(e),
+            },
+            result => return result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::AtomicU32;
+    use std::sync::atomic::Ordering::SeqCst;
+
+    use crate::error::ServerErrorCode;
+    use crate::ZKError;
+
+    use super::*;
+
+    fn server_error(code: ServerErrorCode) -> ZKError {
+        ZKError::ServerError(code, 0)
+    }
+
+    #[test]
+    fn test_policies() {
+        let elapsed = Duration::ZERO;
+        let policy = RetryNTimes::new(2, Duration::from_millis(10));
+        assert_eq!(
+            policy.allow_retry(1, elapsed),
+            Some(Duration::from_millis(10))
+        );
+        assert_eq!(policy.allow_retry(2, elapsed), None);
+
+        let policy = RetryUntilElapsed::new(Duration::from_secs(1), Duration::from_millis(10));
+        assert!(policy
+            .allow_retry(100, Duration::from_millis(999))
+            .is_some());
+        assert!(policy.allow_retry(0, Duration::from_secs(1)).is_none());
+
+        assert!(RetryForever::new(Duration::ZERO)
+            .allow_retry(u32::MAX, Duration::MAX)
+            .is_some());
+    }
+
+    #[test]
+    fn test_exponential_backoff() {
+        let base = Duration::from_millis(10);
+        let policy = ExponentialBackoffRetry::new(base, 100);
+        assert_eq!(policy.max_retries, 29);
+        for retry_count in 0..5 {
+            let sleep = policy.allow_retry(retry_count, Duration::ZERO).unwrap();
+            assert!(sleep >= base);
+            assert!(sleep < base * (1 << (retry_count + 1)));
+        }
+        assert!(policy.allow_retry(29, Duration::ZERO).is_none());
+
+        let policy = ExponentialBackoffRetry::new(base, 29).max_sleep(Duration::from_millis(15));
+        for retry_count in 0..29 {
+            let sleep = policy.allow_retry(retry_count, Duration::ZERO).unwrap();
+            assert!(sleep <= Duration::from_millis(15));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run() {
+        let policy = RetryNTimes::new(3, Duration::ZERO);
+        let count = AtomicU32::new(0);
+        let result = run(&policy, || async {
+            match count.fetch_add(1, SeqCst) {
+                0 | 1 => Err(server_error(ServerErrorCode::ConnectionLoss)),
+                n => Ok(n),
+            }
+        })
+        .await;
+        assert_eq!(result.unwrap(), 2);
+
+        // 不可重试的错误直接返回
+        count.store(0, SeqCst);
+        let result: ZKResult<()> = run(&policy, || async {
+            count.fetch_add(1, SeqCst);
+            Err(server_error(ServerErrorCode::NoNode))
+        })
+        .await;
+        assert_eq!(result.unwrap_err().code(), Some(ServerErrorCode::NoNode));
+        assert_eq!(count.load(SeqCst), 1);
+
+        // 超过重试次数后返回最后一次的错误
+        count.store(0, SeqCst);
+        let result: ZKResult<()> = run(&policy, || async {
+            count.fetch_add(1, SeqCst);
+            Err(server_error(ServerErrorCode::OperationTimeout))
+        })
+        .await;
+        assert!(result.unwrap_err().is_retryable());
+        assert_eq!(count.load(SeqCst), 4);
+    }
+}
@@ -0,0 +1,311 @@
+//! # SASL 鉴权模块
+//! 实现 DIGEST-MD5 机制（[RFC 2831](https://www.rfc-editor.org/rfc/rfc2831)），会话建立后通过
+//! `GetSASLRequest`/`SetSASLResponse` 与服务端完成 challenge/response 交换
+
+use std::fmt::{Debug, Formatter};
+
+use md5::{Digest, Md5};
+
+use crate::{ZKError, ZKResult};
+
+/// SASL 鉴权配置，目前只支持 DIGEST-MD5 机制
+/// # Examples
+///
+/// ```rust,ignore
+/// let zk = ZooKeeperBuilder::new("127.0.0.1:2181")
+///     .sasl(SaslConfig::digest_md5("super", "password"))
+///     .build()
+///     .await?;
+/// ```
+#[derive(Clone, Eq, PartialEq)]
+pub struct SaslConfig {
+    pub(crate) username: String,
+    pub(crate) password: String,
+    pub(crate) service: String,
+    pub(crate) server_name: String,
+}
+
+impl SaslConfig {
+    /// 使用 DIGEST-MD5 机制鉴权
+    /// # Args
+    /// - `username`： 服务端 JAAS 配置中的用户名
+    /// - `password`： 对应的密码
+    pub fn digest_md5(username: impl Into<String>, password: impl Into<String>) -> SaslConfig {
+        SaslConfig {
+            username: username.into(),
+            password: password.into(),
+            service: "zookeeper".to_string(),
+            server_name: "zk-sasl-md5".to_string(),
+        }
+    }
+
+    /// 服务名，默认为 "zookeeper"，与 Java 客户端的 `zookeeper.sasl.client.username` 一致
+    pub fn service(mut self, service: impl Into<String>) -> Self {
+        self.service = service.into();
+        self
+    }
+
+    /// 服务端名称，默认为 "zk-sasl-md5"，和服务名一起组成 digest-uri
+    pub fn server_name(mut self, server_name: impl Into<String>) -> Self {
+        self.server_name = server_name.into();
+        self
+    }
+}
+
+impl Debug for SaslConfig {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        // 不输出密码
+        f.debug_struct("SaslConfig")
+            .field("username", &self.username)
+            .field("service", &self.service)
+            .field("server_name", &self.server_name)
+            .finish()
+    }
+}
+
+#[derive(Debug)]
+enum Step {
+    Challenge,
+    Verify(String),
+    Complete,
+}
+
+/// DIGEST-MD5 客户端，只支持 `qop=auth`
+#[derive(Debug)]
+pub(crate) struct DigestMd5Client {
+    config: SaslConfig,
+    cnonce: String,
+    step: Step,
+}
+
+impl DigestMd5Client {
+    pub(crate) fn new(config: SaslConfig) -> Self {
+        let cnonce = to_hex(&rand::random::<[u8; 16]>());
+        DigestMd5Client::with_cnonce(config, cnonce)
+    }
+
+    fn with_cnonce(config: SaslConfig, cnonce: String) -> Self {
+        DigestMd5Client {
+            config,
+            cnonce,
+            step: Step::Challenge,
+        }
+    }
+
+    /// DIGEST-MD5 没有初始响应，第一次发送空的 token
+    pub(crate) fn initial_response(&self) -> Vec<u8> {
+        Vec::new()
+    }
+
+    /// 处理服务端发来的 token
+    /// # Returns
+    /// - `Some(token)`： 需要继续发送给服务端的 token
+    /// - `None`： 鉴权完成
+    ///
+    /// # Errors
+    ///
+    /// 服务端的 challenge 格式有误或者服务端没有通过校验
+    pub(crate) fn evaluate_challenge(&mut self, challenge: &[u8]) -> ZKResult<Option<Vec<u8>>> {
+        let challenge = std::str::from_utf8(challenge)
+            .map_err(|_| sasl_error("challenge is not valid UTF-8"))?;
+        let directives = parse_directives(challenge)?;
+        match std::mem::replace(&mut self.step, Step::Complete) {
+            Step::Challenge => {
+                let (response, rspauth) = self.digest_response(&directives)?;
+                self.step = Step::Verify(rspauth);
+                Ok(Some(response.into_bytes()))
+            }
+            Step::Verify(expected) => match find(&directives, "rspauth") {
+                Some(rspauth) if rspauth == expected => Ok(None),
+                _ => Err(sasl_error("server rspauth mismatch")),
+            },
+            Step::Complete => Err(sasl_error("authentication already completed")),
+        }
+    }
+
+    /// 计算发给服务端的 digest-response 以及期望服务端返回的 rspauth
+    fn digest_response(&self, directives: &[(String, String)]) -> ZKResult<(String, String)> {
+        let nonce = find(directives, "nonce").ok_or_else(|| sasl_error("missing nonce"))?;
+        if let Some(algorithm) = find(directives, "algorithm") {
+            if !algorithm.eq_ignore_ascii_case("md5-sess") {
+                return Err(sasl_error(&format!("unsupported algorithm {}", algorithm)));
+            }
+        }
+        if let Some(qop) = find(directives, "qop") {
+            if !qop.split(',').any(|q| q.trim() == "auth") {
+                return Err(sasl_error(&format!("unsupported qop {}", qop)));
+            }
+        }
+        let realm = find(directives, "realm").unwrap_or("");
+        let utf8 = find(directives, "charset").is_some_and(|c| c.eq_ignore_ascii_case("utf-8"));
+        let digest_uri = format!("{}/{}", self.config.service, self.config.server_name);
+        let nc = "00000001";
+
+        let mut a1 = Md5::digest(format!(
+            "{}:{}:{}",
+            self.config.username, realm, self.config.password
+        ))
+        .to_vec();
+        a1.extend(format!(":{}:{}", nonce, self.cnonce).as_bytes());
+        let ha1 = to_hex(&Md5::digest(&a1));
+        let kd = |a2: String| {
+            let ha2 = to_hex(&Md5::digest(a2));
+            to_hex(&Md5::digest(format!(
+                "{}:{}:{}:{}:auth:{}",
+                ha1, nonce, nc, self.cnonce, ha2
+            )))
+        };
+        let response = kd(format!("AUTHENTICATE:{}", digest_uri));
+        let rspauth = kd(format!(":{}", digest_uri));
+
+        let mut fields = Vec::new();
+        if utf8 {
+            fields.push("charset=utf-8".to_string());
+        }
+        fields.push(format!("username={}", quote(&self.config.username)));
+        if !realm.is_empty() {
+            fields.push(format!("realm={}", quote(realm)));
+        }
+        fields.push(format!("nonce={}", quote(nonce)));
+        fields.push(format!("nc={}", nc));
+        fields.push(format!("cnonce={}", quote(&self.cnonce)));
+        fields.push(format!("digest-uri={}", quote(&digest_uri)));
+        fields.push("maxbuf=65536".to_string());
+        fields.push(format!("response={}", response));
+        fields.push("qop=auth".to_string());
+        Ok((fields.join(","), rspauth))
+    }
+}
+
+fn sasl_error(message: &str) -> ZKError {
+    ZKError::SaslError(message.to_string())
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// 同名的指令只取第一个
+fn find<'a>(directives: &'a [(String, String)], key: &str) -> Option<&'a str> {
+    directives
+        .iter()
+        .find(|(k, _)| k == key)
+        .map(|(_, v)| v.as_str())
+}
+
+/// 解析 `key=value,key="quoted value"` 格式的指令列表
+fn parse_directives(input: &str) -> ZKResult<Vec<(String, String)>> {
+    let mut directives = Vec::new();
+    let mut chars = input.chars().peekable();
+    loop {
+        while matches!(chars.peek(), Some(c) if c.is_whitespace() || *c == ',') {
+            chars.next();
+        }
+        if chars.peek().is_none() {
+            return Ok(directives);
+        }
+        let mut key = String::new();
+        for c in chars.by_ref() {
+            if c == '=' {
+                break;
+            }
+            key.push(c);
+        }
+        let mut value = String::new();
+        if chars.peek() == Some(&'"') {
+            chars.next();
+            loop {
+                match chars.next() {
+                    Some('\\') => match chars.next() {
+                        Some(c) => value.push(c),
+                        None => return Err(sasl_error("unterminated quoted value")),
+                    },
+                    Some('"') => break,
+                    Some(c) => value.push(c),
+                    None => return Err(sasl_error("unterminated quoted value")),
+                }
+            }
+        } else {
+            while let Some(c) = chars.next_if(|c| *c != ',') {
+                value.push(c);
+            }
+            value = value.trim().to_string();
+        }
+        directives.push((key.trim().to_lowercase(), value));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// RFC 2831 第 4 节的示例
+    fn rfc_client() -> DigestMd5Client {
+        let config = SaslConfig::digest_md5("chris", "secret")
+            .service("imap")
+            .server_name("elwood.innosoft.com");
+        DigestMd5Client::with_cnonce(config, "OA6MHXh6VqTrRk".to_string())
+    }
+
+    #[test]
+    fn test_rfc_example() {
+        let mut client = rfc_client();
+        assert!(client.initial_response().is_empty());
+        let challenge = br#"realm="elwood.innosoft.com",nonce="OA6MG9tEQGm2hh",qop="auth",algorithm=md5-sess,charset=utf-8"#;
+        let response = client.evaluate_challenge(challenge).unwrap().unwrap();
+        let response = String::from_utf8(response).unwrap();
+        assert_eq!(
+            response,
+            "charset=utf-8,username=\"chris\",realm=\"elwood.innosoft.com\",\
+             nonce=\"OA6MG9tEQGm2hh\",nc=00000001,cnonce=\"OA6MHXh6VqTrRk\",\
+             digest-uri=\"imap/elwood.innosoft.com\",maxbuf=65536,\
+             response=d388dad90d4bbd760a152321f2143af7,qop=auth"
+        );
+        let result = client
+            .evaluate_challenge(b"rspauth=ea40f60335c427b5527b84dbabcdfffd")
+            .unwrap();
+        assert!(result.is_none());
+        // 完成后不应该再收到 challenge
+        assert!(client.evaluate_challenge(b"rspauth=x").is_err());
+    }
+
+    #[test]
+    fn test_rspauth_mismatch() {
+        let mut client = rfc_client();
+        client
+            .evaluate_challenge(br#"nonce="OA6MG9tEQGm2hh",qop="auth""#)
+            .unwrap();
+        assert!(client.evaluate_challenge(b"rspauth=0000").is_err());
+    }
+
+    #[test]
+    fn test_unsupported_challenge() {
+        assert!(rfc_client()
+            .evaluate_challenge(br#"realm="r",qop="auth""#)
+            .is_err());
+        assert!(rfc_client()
+            .evaluate_challenge(br#"nonce="n",qop="auth-conf""#)
+            .is_err());
+        assert!(rfc_client()
+            .evaluate_challenge(br#"nonce="n",algorithm=md5"#)
+            .is_err());
+        assert!(rfc_client().evaluate_challenge(br#"nonce="n"#).is_err());
+    }
+
+    #[test]
+    fn test_parse_directives() {
+        let directives = parse_directives(r#" a=1, b="x,\"y\"" ,c = z "#).unwrap();
+        assert_eq!(
+            directives,
+            vec![
+                ("a".to_string(), "1".to_string()),
+                ("b".to_string(), "x,\"y\"".to_string()),
+                ("c".to_string(), "z".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,274 @@
+//! # 顺序节点模块
+//! 服务端为顺序节点添加的后缀是父节点 `cversion` 按照 `%010d` 格式化的结果，`cversion` 超过 `i32::MAX`
+//! 后会回绕为负数。[`SequentialName`] 拆分节点名称中的保护前缀、名称前缀和序号，并按照回绕后的顺序排序
+
+use std::fmt::{Display, Formatter};
+
+use crate::{ZKResult, ZooKeeper};
+
+const PROTECTED_PREFIX: &str = "_c_";
+const SEQUENCE_LEN: usize = 10;
+
+/// 顺序节点的名称，例如 `_c_1f0e...-lock-0000000003` 拆分为保护 ID `1f0e...`、前缀 `lock-` 和序号 3
+/// # Examples
+///
+/// ```rust,ignore
+/// let children = zk.children("/locks").await?;
+/// let members = SequentialName::sort_children(&children);
+/// let leader = &members[0];
+/// println!("{} {}", leader.prefix(), leader.sequence());
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct SequentialName {
+    name: String,
+    protected_id: Option<(usize, usize)>,
+    prefix: (usize, usize),
+    sequence: i32,
+}
+
+impl SequentialName {
+    /// 解析节点名称，没有序号后缀时返回 `None`。
+    ///
+    /// 后缀一般为 10 位，回绕后小于 -999999999 的序号为 11 位，例如 `-2147483648`。
+    /// 名称前缀以 `-` 结尾时这两种格式可能无法区分，只有按照正数解析溢出时才视为负数，
+    /// 已知名称前缀时请使用 [`SequentialName::parse_with_prefix`]
+    pub fn parse(name: impl Into<String>) -> Option<SequentialName> {
+        let name = name.into();
+        let (protected_id, prefix_start) = split_protected(&name);
+        let suffix_start = name.len().checked_sub(SEQUENCE_LEN)?;
+        if suffix_start < prefix_start || !name.is_char_boundary(suffix_start) {
+            return None;
+        }
+        let (sequence, suffix_start) = match parse_sequence(&name[suffix_start..]) {
+            Some(sequence) => (sequence, suffix_start),
+            // 溢出的 10 位数字只能是 11 位的负数
+            None if suffix_start > prefix_start && name[..suffix_start].ends_with('-') => {
+                (parse_sequence(&name[suffix_start - 1..])?, suffix_start - 1)
+            }
+            None => return None,
+        };
+        Some(SequentialName {
+            protected_id,
+            prefix: (prefix_start, suffix_start),
+            sequence,
+            name,
+        })
+    }
+
+    /// 按照已知的名称前缀解析节点名称，名称前缀不匹配或者没有序号后缀时返回 `None`
+    pub fn parse_with_prefix(name: impl Into<String>, prefix: &str) -> Option<SequentialName> {
+        let name = name.into();
+        let (protected_id, prefix_start) = split_protected(&name);
+        let suffix = name[prefix_start..].strip_prefix(prefix)?;
+        let sequence = parse_sequence(suffix)?;
+        Some(SequentialName {
+            protected_id,
+            prefix: (prefix_start, prefix_start + prefix.len()),
+            sequence,
+            name,
+        })
+    }
+
+    /// 完整的节点名称
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// 去掉保护前缀和序号之后的名称前缀，例如 `lock-`
+    pub fn prefix(&self) -> &str {
+        &self.name[self.prefix.0..self.prefix.1]
+    }
+
+    /// 保护前缀 `_c_<id>-` 中的 ID，参考 [`ZooKeeper::create_with_retry`]
+    pub fn protected_id(&self) -> Option<&str> {
+        self.protected_id.map(|(start, end)| &self.name[start..end])
+    }
+
+    /// 服务端分配的序号
+    pub fn sequence(&self) -> i32 {
+        self.sequence
+    }
+
+    /// 按照序号回绕的规则判断是否在 `other` 之前创建，两个序号的差值需要小于 2^31
+    pub fn is_before(&self, other: &SequentialName) -> bool {
+        other.sequence.wrapping_sub(self.sequence) > 0
+    }
+
+    /// 按照创建顺序排序，序号回绕后负数的序号在正数之后，同时存在的节点的序号跨度需要小于 2^31
+    pub fn sort(mut names: Vec<SequentialName>) -> Vec<SequentialName> {
+        names.sort_by(|a, b| {
+            a.sequence
+                .cmp(&b.sequence)
+                .then_with(|| a.name.cmp(&b.name))
+        });
+        // 环形序号空间中最大的空隙之后是最早创建的节点
+        let len = names.len();
+        if len > 1 {
+            let gap = |i: usize| {
+                let (a, b) = (names[i].sequence, names[(i + 1) % len].sequence);
+                b.wrapping_sub(a) as u32
+            };
+            let widest = (0..len).max_by_key(|i| gap(*i)).unwrap();
+            names.rotate_left((widest + 1) % len);
+        }
+        names
+    }
+
+    /// 解析并按照创建顺序排序子节点列表，非顺序节点会被忽略，参考 [`SequentialName::sort`]
+    pub fn sort_children<S: AsRef<str>>(children: &[S]) -> Vec<SequentialName> {
+        let names = children
+            .iter()
+            .filter_map(|child| SequentialName::parse(child.as_ref()))
+            .collect();
+        SequentialName::sort(names)
+    }
+}
+
+impl Display for SequentialName {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.name)
+    }
+}
+
+/// 解析 `%010d` 格式的序号
+fn parse_sequence(suffix: &str) -> Option<i32> {
+    let digits = suffix.strip_prefix('-').unwrap_or(suffix);
+    let valid = match suffix.len() {
+        SEQUENCE_LEN => true,
+        // 11 位只有小于 -999999999 的负数
+        11 => suffix.starts_with('-') && !digits.starts_with('0'),
+        _ => false,
+    };
+    if !valid || digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    suffix.parse().ok()
+}
+
+/// 拆分出保护前缀中 ID 的位置和名称前缀的起始位置，支持 32 位十六进制和 Curator 使用的 36 位 UUID
+fn split_protected(name: &str) -> (Option<(usize, usize)>, usize) {
+    let rest = match name.strip_prefix(PROTECTED_PREFIX) {
+        Some(rest) => rest,
+        None => return (None, 0),
+    };
+    let id_len = [36, 32].into_iter().find(|len| {
+        rest.len() > *len
+            && rest.as_bytes()[*len] == b'-'
+            && rest.as_bytes()[..*len].iter().enumerate().all(|(i, b)| {
+                if *len == 36 && matches!(i, 8 | 13 | 18 | 23) {
+                    *b == b'-'
+                } else {
+                    b.is_ascii_hexdigit()
+                }
+            })
+    });
+    match id_len {
+        Some(len) => {
+            let start = PROTECTED_PREFIX.len();
+            (Some((start, start + len)), start + len + 1)
+        }
+        None => (None, 0),
+    }
+}
+
+impl ZooKeeper {
+    /// 获取名称前缀为 `prefix` 的顺序子节点，按照创建顺序排序，参考 [`SequentialName::sort_children`]
+    /// # Examples
+    /// ```rust,ignore
+    /// let members = zk.sequential_children("/locks/app", "lock-").await?;
+    /// ```
+    ///
+    /// # Args
+    /// - `path`： 父节点路径，必须以 "/" 开头
+    /// - `prefix`： 去掉保护前缀之后的名称前缀，为空时返回所有顺序子节点
+    /// # Returns
+    /// - `Vec<SequentialName>`：排序后的顺序子节点
+    pub async fn sequential_children(
+        &self,
+        path: impl AsRef<str>,
+        prefix: &str,
+    ) -> ZKResult<Vec<SequentialName>> {
+        let children = self.children(path).await?;
+        if prefix.is_empty() {
+            return Ok(SequentialName::sort_children(&children));
+        }
+        let names = children
+            .into_iter()
+            .filter_map(|child| SequentialName::parse_with_prefix(child, prefix))
+            .collect();
+        Ok(SequentialName::sort(names))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let name = SequentialName::parse("lock-0000000003").unwrap();
+        assert_eq!(name.prefix(), "lock-");
+        assert_eq!(name.sequence(), 3);
+        assert_eq!(name.protected_id(), None);
+
+        let name =
+            SequentialName::parse("_c_0123456789abcdef0123456789abcdef-lock-0000000012").unwrap();
+        assert_eq!(
+            name.protected_id(),
+            Some("0123456789abcdef0123456789abcdef")
+        );
+        assert_eq!(name.prefix(), "lock-");
+        assert_eq!(name.sequence(), 12);
+
+        // Curator 的保护前缀
+        let name =
+            SequentialName::parse("_c_4b5f2a1e-8c3d-4e6f-9a7b-1c2d3e4f5a6b-0000000007").unwrap();
+        assert_eq!(
+            name.protected_id(),
+            Some("4b5f2a1e-8c3d-4e6f-9a7b-1c2d3e4f5a6b")
+        );
+        assert_eq!(name.prefix(), "");
+        assert_eq!(name.sequence(), 7);
+
+        // 回绕后的负数序号
+        let name = SequentialName::parse("lock--000000005").unwrap();
+        assert_eq!((name.prefix(), name.sequence()), ("lock-", -5));
+        let name = SequentialName::parse("lock-2147483648").unwrap();
+        assert_eq!((name.prefix(), name.sequence()), ("lock", i32::MIN));
+
+        assert_eq!(SequentialName::parse("config"), None);
+        assert_eq!(SequentialName::parse("lock-00000001a3"), None);
+        assert_eq!(
+            SequentialName::parse("_c_xyz-0000000001").unwrap().prefix(),
+            "_c_xyz-"
+        );
+    }
+
+    #[test]
+    fn test_sort_children() {
+        let children = ["b-0000000002", "config", "a-0000000010", "c-0000000001"];
+        let names: Vec<String> = SequentialName::sort_children(&children)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(names, ["c-0000000001", "b-0000000002", "a-0000000010"]);
+
+        // 回绕后的序号排在最后
+        let children = [
+            "n-2147483646",
+            "n--000000002",
+            "n-2147483647",
+            "n-2147483648",
+        ];
+        let sequences: Vec<i32> = SequentialName::sort_children(&children)
+            .iter()
+            .map(SequentialName::sequence)
+            .collect();
+        assert_eq!(sequences, [i32::MAX - 1, i32::MAX, i32::MIN, -2]);
+
+        let max = SequentialName::parse("n-2147483647").unwrap();
+        let min = SequentialName::parse("n-2147483648").unwrap();
+        assert!(max.is_before(&min));
+        assert!(!min.is_before(&max));
+    }
+}
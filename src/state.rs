@@ -0,0 +1,179 @@
+//! # 连接状态模块
+//! 记录客户端会话的生命周期，并把每一次状态变化广播给订阅者
+
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering::{Acquire, Release};
+use std::time::Duration;
+
+use chrono::prelude::*;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{broadcast, watch};
+
+use crate::constants::{KeeperState, States};
+use crate::error::ServerErrorCode;
+use crate::{ZKError, ZKResult};
+
+/// 客户端连接的状态变化
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum ConnectionState {
+    /// 第一次成功建立会话
+    Connected,
+    /// 与服务端的连接断开，会话可能仍然有效，此时应暂停所有依赖会话的工作
+    Suspended,
+    /// 断开后重新连接成功，会话没有过期
+    Reconnected,
+    /// 以只读模式连接到了服务端
+    ReadOnly,
+    /// 会话已经过期，所有临时节点和回调都已失效。断线超过会话超时时间仍然没有重连成功时在本地判定过期
+    Lost,
+    /// 客户端被主动关闭
+    Closed,
+    /// 鉴权失败，服务端断开了连接，客户端不再可用
+    AuthFailed,
+}
+
+impl ConnectionState {
+    /// 当前状态下是否可以向服务端发送请求
+    pub fn is_connected(&self) -> bool {
+        matches!(
+            self,
+            ConnectionState::Connected | ConnectionState::Reconnected | ConnectionState::ReadOnly
+        )
+    }
+
+    /// 会话是否已经不可恢复
+    pub fn is_terminal(&self) -> bool {
+        matches!(
+            self,
+            ConnectionState::Lost | ConnectionState::Closed | ConnectionState::AuthFailed
+        )
+    }
+}
+
+/// 一次状态变化
+/// - `state`： 变化后的连接状态，详细可见 [`ConnectionState`]
+/// - `keeper_state`： 对应的服务端状态，详细可见 [`KeeperState`]
+/// - `timestamp`： 状态变化发生的时间
+#[derive(Debug, Clone)]
+pub struct StateEvent {
+    pub state: ConnectionState,
+    pub keeper_state: KeeperState,
+    pub timestamp: DateTime<Local>,
+}
+
+/// 状态变化的订阅者，通过 [`crate::ZooKeeper::subscribe_state`] 获取
+#[derive(Debug)]
+pub struct StateListener {
+    rx: broadcast::Receiver<StateEvent>,
+}
+
+impl StateListener {
+    /// 等待下一次状态变化，客户端被释放后返回 `None`
+    ///
+    /// 订阅者处理太慢时会跳过最早的状态变化，只保留最新的部分
+    pub async fn recv(&mut self) -> Option<StateEvent> {
+        loop {
+            match self.rx.recv().await {
+                Ok(event) => return Some(event),
+                Err(RecvError::Lagged(n)) => {
+                    warn!("StateListener lagged, skipped {} state events", n);
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct StateManager {
+    states: watch::Sender<States>,
+    events: broadcast::Sender<StateEvent>,
+    closing: AtomicBool,
+}
+
+impl StateManager {
+    pub(crate) fn new(capacity: usize) -> Self {
+        let (states, _) = watch::channel(States::NotConnected);
+        let (events, _) = broadcast::channel(capacity);
+        StateManager {
+            states,
+            events,
+            closing: AtomicBool::new(false),
+        }
+    }
+
+    pub(crate) fn states(&self) -> States {
+        self.states.borrow().clone()
+    }
+
+    pub(crate) fn set_states(&self, states: States) {
+        self.states.send_replace(states);
+    }
+
+    pub(crate) fn is_connected(&self) -> bool {
+        self.states.borrow().is_connected()
+    }
+
+    /// 标记客户端正在关闭，此后的断线不再触发重连
+    pub(crate) fn set_closing(&self) {
+        self.closing.store(true, Release);
+    }
+
+    pub(crate) fn is_closing(&self) -> bool {
+        self.closing.load(Acquire)
+    }
+
+    pub(crate) fn publish(&self, state: ConnectionState, keeper_state: KeeperState) {
+        let event = StateEvent {
+            state,
+            keeper_state,
+            timestamp: Local::now(),
+        };
+        info!("Connection state changed to {:?}", state);
+        // 没有订阅者时发送会失败，忽略即可
+        let _ = self.events.send(event);
+    }
+
+    pub(crate) fn subscribe(&self) -> StateListener {
+        StateListener {
+            rx: self.events.subscribe(),
+        }
+    }
+
+    /// 与 Java 客户端一致，根据当前状态决定无法发送请求时返回的错误
+    pub(crate) fn loss_error(&self) -> ZKError {
+        match self.states() {
+            States::AuthFailed => ZKError::ServerError(ServerErrorCode::AuthFailed, -115),
+            States::Closed => ZKError::ServerError(ServerErrorCode::SessionExpired, -112),
+            _ => ZKError::ServerError(ServerErrorCode::ConnectionLoss, -4),
+        }
+    }
+
+    pub(crate) async fn wait_until_connected(&self, timeout: Duration) -> ZKResult<()> {
+        let mut rx = self.states.subscribe();
+        let wait = rx.wait_for(|s| s.is_connected() || !s.is_alive());
+        let connected = match tokio::time::timeout(timeout, wait).await {
+            Ok(Ok(s)) => s.is_connected(),
+            Ok(Err(_)) => false,
+            Err(_) => {
+                return Err(ZKError::NetworkError(format!(
+                    "Wait for connected timeout after {:?}",
+                    timeout
+                )))
+            }
+        };
+        if connected {
+            Ok(())
+        } else {
+            Err(self.loss_error())
+        }
+    }
+
+    pub(crate) async fn wait_until_closed(&self, timeout: Duration) {
+        let mut rx = self.states.subscribe();
+        let wait = rx.wait_for(|s| !s.is_alive());
+        if tokio::time::timeout(timeout, wait).await.is_err() {
+            warn!("Wait for client closed timeout after {:?}", timeout);
+        }
+    }
+}
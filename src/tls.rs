@@ -0,0 +1,304 @@
+//! # TLS 模块
+//! 基于 rustls 连接服务端的 `secureClientPort`，需要开启 `tls` feature
+
+use std::fmt::{Debug, Formatter};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use tokio::net::TcpStream;
+use tokio_rustls::client::TlsStream;
+use tokio_rustls::rustls::client::danger::{
+    HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier,
+};
+use tokio_rustls::rustls::client::WebPkiServerVerifier;
+use tokio_rustls::rustls::crypto::ring;
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use tokio_rustls::rustls::{
+    CertificateError, ClientConfig, DigitallySignedStruct, Error, RootCertStore, SignatureScheme,
+};
+
+use crate::{ZKError, ZKResult};
+
+/// PEM 格式的证书或私钥，可以来自文件也可以直接提供内容
+#[derive(Clone)]
+enum PemSource {
+    File(PathBuf),
+    Bytes(Vec<u8>),
+}
+
+impl PemSource {
+    fn load(&self) -> ZKResult<Vec<u8>> {
+        match self {
+            PemSource::File(path) => std::fs::read(path)
+                .map_err(|e| tls_error(format!("read {} error: {}", path.display(), e))),
+            PemSource::Bytes(bytes) => Ok(bytes.clone()),
+        }
+    }
+
+    fn certificates(&self) -> ZKResult<Vec<CertificateDer<'static>>> {
+        let pem = self.load()?;
+        let certs = CertificateDer::pem_slice_iter(&pem)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| tls_error(format!("parse certificate error: {}", e)))?;
+        if certs.is_empty() {
+            return Err(tls_error("no certificate found".to_string()));
+        }
+        Ok(certs)
+    }
+
+    fn private_key(&self) -> ZKResult<PrivateKeyDer<'static>> {
+        PrivateKeyDer::from_pem_slice(&self.load()?)
+            .map_err(|e| tls_error(format!("parse private key error: {}", e)))
+    }
+}
+
+impl Debug for PemSource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PemSource::File(path) => write!(f, "File({})", path.display()),
+            // 不输出私钥内容
+            PemSource::Bytes(_) => f.write_str("Bytes(..)"),
+        }
+    }
+}
+
+/// TLS 配置
+/// # Examples
+///
+/// ```rust,ignore
+/// let tls = TlsConfig::new()
+///     .ca_file("/etc/zookeeper/ca.pem")
+///     .client_auth_file("/etc/zookeeper/client.pem", "/etc/zookeeper/client.key");
+/// let zk = ZooKeeperBuilder::new("zk1.example.com:2281")
+///     .tls(tls)
+///     .build()
+///     .await?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct TlsConfig {
+    ca: Option<PemSource>,
+    client_auth: Option<(PemSource, PemSource)>,
+    verify_hostname: bool,
+    server_name: Option<String>,
+}
+
+impl Default for TlsConfig {
+    fn default() -> Self {
+        TlsConfig {
+            ca: None,
+            client_auth: None,
+            verify_hostname: true,
+            server_name: None,
+        }
+    }
+}
+
+impl TlsConfig {
+    /// 创建 TLS 配置，默认使用 Mozilla 的根证书并校验主机名
+    pub fn new() -> TlsConfig {
+        TlsConfig::default()
+    }
+
+    /// 从文件中读取 PEM 格式的 CA 证书，可以包含多个证书
+    pub fn ca_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.ca = Some(PemSource::File(path.into()));
+        self
+    }
+
+    /// 直接提供 PEM 格式的 CA 证书内容
+    pub fn ca_pem(mut self, pem: impl Into<Vec<u8>>) -> Self {
+        self.ca = Some(PemSource::Bytes(pem.into()));
+        self
+    }
+
+    /// 从文件中读取 PEM 格式的客户端证书和私钥，用于双向认证以及 `x509` ACL
+    pub fn client_auth_file(
+        mut self,
+        cert_path: impl Into<PathBuf>,
+        key_path: impl Into<PathBuf>,
+    ) -> Self {
+        self.client_auth = Some((
+            PemSource::File(cert_path.into()),
+            PemSource::File(key_path.into()),
+        ));
+        self
+    }
+
+    /// 直接提供 PEM 格式的客户端证书和私钥内容
+    pub fn client_auth_pem(mut self, cert: impl Into<Vec<u8>>, key: impl Into<Vec<u8>>) -> Self {
+        self.client_auth = Some((PemSource::Bytes(cert.into()), PemSource::Bytes(key.into())));
+        self
+    }
+
+    /// 是否校验服务端证书中的主机名，默认校验。关闭后仍然会校验证书链
+    pub fn verify_hostname(mut self, verify_hostname: bool) -> Self {
+        self.verify_hostname = verify_hostname;
+        self
+    }
+
+    /// 校验证书时使用的服务端名称，默认为连接字符串中的主机名
+    pub fn server_name(mut self, server_name: impl Into<String>) -> Self {
+        self.server_name = Some(server_name.into());
+        self
+    }
+
+    /// 读取证书并创建连接器
+    pub(crate) fn connector(&self) -> ZKResult<TlsConnector> {
+        let provider = Arc::new(ring::default_provider());
+        let mut roots = RootCertStore::empty();
+        match &self.ca {
+            Some(ca) => {
+                for cert in ca.certificates()? {
+                    roots
+                        .add(cert)
+                        .map_err(|e| tls_error(format!("invalid CA certificate: {}", e)))?;
+                }
+            }
+            None => roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned()),
+        }
+        let verifier =
+            WebPkiServerVerifier::builder_with_provider(Arc::new(roots), provider.clone())
+                .build()
+                .map_err(|e| tls_error(e.to_string()))?;
+        let builder = ClientConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .map_err(|e| tls_error(e.to_string()))?
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(CertVerifier {
+                inner: verifier,
+                verify_hostname: self.verify_hostname,
+            }));
+        let config = match &self.client_auth {
+            Some((cert, key)) => builder
+                .with_client_auth_cert(cert.certificates()?, key.private_key()?)
+                .map_err(|e| tls_error(e.to_string()))?,
+            None => builder.with_no_client_auth(),
+        };
+        let server_name = match &self.server_name {
+            Some(name) => Some(parse_server_name(name)?),
+            None => None,
+        };
+        Ok(TlsConnector {
+            connector: tokio_rustls::TlsConnector::from(Arc::new(config)),
+            server_name,
+        })
+    }
+}
+
+/// 在 TCP 连接上完成 TLS 握手
+pub(crate) struct TlsConnector {
+    connector: tokio_rustls::TlsConnector,
+    server_name: Option<ServerName<'static>>,
+}
+
+impl TlsConnector {
+    pub(crate) async fn connect(
+        &self,
+        host: &str,
+        socket: TcpStream,
+    ) -> ZKResult<TlsStream<TcpStream>> {
+        let server_name = match &self.server_name {
+            Some(name) => name.clone(),
+            None => parse_server_name(host_name(host))?,
+        };
+        self.connector
+            .connect(server_name, socket)
+            .await
+            .map_err(|e| tls_error(format!("handshake with {} error: {}", host, e)))
+    }
+}
+
+/// 去掉 `host:port` 中的端口以及 IPv6 地址的方括号
+fn host_name(host: &str) -> &str {
+    let name = match host.rsplit_once(':') {
+        Some((name, port)) if port.parse::<u16>().is_ok() => name,
+        _ => host,
+    };
+    name.trim_start_matches('[').trim_end_matches(']')
+}
+
+fn parse_server_name(name: &str) -> ZKResult<ServerName<'static>> {
+    ServerName::try_from(name.to_string())
+        .map_err(|_| tls_error(format!("invalid server name {}", name)))
+}
+
+fn tls_error(message: String) -> ZKError {
+    ZKError::TlsError(message)
+}
+
+/// 校验证书链，按照配置决定是否忽略主机名不匹配
+#[derive(Debug)]
+struct CertVerifier {
+    inner: Arc<WebPkiServerVerifier>,
+    verify_hostname: bool,
+}
+
+impl ServerCertVerifier for CertVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, Error> {
+        match self.inner.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            ocsp_response,
+            now,
+        ) {
+            Err(Error::InvalidCertificate(
+                CertificateError::NotValidForName | CertificateError::NotValidForNameContext { .. },
+            )) if !self.verify_hostname => Ok(ServerCertVerified::assertion()),
+            result => result,
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_host_name() {
+        assert_eq!(host_name("zk1.example.com:2281"), "zk1.example.com");
+        assert_eq!(host_name("127.0.0.1:2281"), "127.0.0.1");
+        assert_eq!(host_name("[::1]:2281"), "::1");
+        assert_eq!(host_name("localhost"), "localhost");
+    }
+
+    #[test]
+    fn test_invalid_pem() {
+        assert!(TlsConfig::new().ca_pem("not a pem").connector().is_err());
+        assert!(TlsConfig::new()
+            .ca_file("/path/not/exists/ca.pem")
+            .connector()
+            .is_err());
+        assert!(TlsConfig::new().connector().is_ok());
+    }
+}
@@ -0,0 +1,45 @@
+//! # 传输层模块
+//! 屏蔽普通 TCP 连接和 TLS 连接的差异，Sender/Receiver 只依赖 `AsyncRead`/`AsyncWrite`
+
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
+
+use crate::builder::ClientConfig;
+#[cfg(feature = "tls")]
+use crate::tls::TlsConnector;
+use crate::ZKResult;
+
+pub(crate) trait Stream: AsyncRead + AsyncWrite + Unpin + Send + Sync {}
+
+impl<T> Stream for T where T: AsyncRead + AsyncWrite + Unpin + Send + Sync {}
+
+pub(crate) type BoxedStream = Box<dyn Stream>;
+
+/// 根据配置建立普通 TCP 连接或者 TLS 连接
+pub(crate) struct Connector {
+    #[cfg(feature = "tls")]
+    tls: Option<TlsConnector>,
+}
+
+impl Connector {
+    /// 证书等配置有误时返回异常
+    pub(crate) fn new(_config: &ClientConfig) -> ZKResult<Connector> {
+        Ok(Connector {
+            #[cfg(feature = "tls")]
+            tls: _config
+                .tls
+                .as_ref()
+                .map(|tls| tls.connector())
+                .transpose()?,
+        })
+    }
+
+    pub(crate) async fn connect(&self, host: &str) -> ZKResult<BoxedStream> {
+        let socket = TcpStream::connect(host).await?;
+        #[cfg(feature = "tls")]
+        if let Some(tls) = &self.tls {
+            return Ok(Box::new(tls.connect(host, socket).await?));
+        }
+        Ok(Box::new(socket))
+    }
+}
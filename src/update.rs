@@ -0,0 +1,236 @@
+//! # 条件写入模块
+//! 基于版本号的乐观并发控制实现常用的写入模式：不存在时创建、存在时更新的 [`ZooKeeper::create_or_set`]，
+//! 只在不存在时创建的 [`ZooKeeper::create_if_absent`]，以及读取-修改-写入的 [`ZooKeeper::update`]
+
+use crate::constants::{CreateMode, VersionType};
+use crate::error::ServerErrorCode;
+use crate::protocol::req::ACL;
+use crate::protocol::resp::Stat;
+use crate::{ZKError, ZKResult, ZooKeeper};
+
+/// 条件写入的配置
+/// # Examples
+///
+/// ```rust,ignore
+/// let options = UpdateOptions::default().max_attempts(20).acl(CREATOR_ALL_ACL.clone());
+/// zk.update_with("/counter", &options, |old| Some(increment(old))).await?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct UpdateOptions {
+    max_attempts: usize,
+    acl_list: Option<Vec<ACL>>,
+    create_mode: CreateMode,
+}
+
+impl Default for UpdateOptions {
+    fn default() -> Self {
+        UpdateOptions {
+            max_attempts: 10,
+            acl_list: None,
+            create_mode: CreateMode::Persistent,
+        }
+    }
+}
+
+impl UpdateOptions {
+    /// 因为并发修改（`BadVersion`、`NoNode`、`NodeExists`）失败时最多尝试的次数，默认 10 次
+    pub fn max_attempts(mut self, max_attempts: usize) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    /// 节点不存在时创建节点使用的 ACL 列表，默认为 [`ZooKeeper::default_acl`]
+    pub fn acl(mut self, acl_list: Vec<ACL>) -> Self {
+        self.acl_list = Some(acl_list);
+        self
+    }
+
+    /// 节点不存在时创建节点使用的模式，默认为持久节点，不支持顺序节点
+    pub fn create_mode(mut self, create_mode: CreateMode) -> Self {
+        self.create_mode = create_mode;
+        self
+    }
+}
+
+/// 其他客户端同时修改了节点，重新读取后可以重试。创建节点时的 `NoNode` 表示父节点不存在，不需要重试
+fn is_conflict(e: &ZKError, creating: bool) -> bool {
+    match e.code() {
+        Some(ServerErrorCode::NodeExists) => creating,
+        Some(ServerErrorCode::BadVersion | ServerErrorCode::NoNode) => !creating,
+        _ => false,
+    }
+}
+
+impl ZooKeeper {
+    /// 使用默认配置写入数据，参考 [`ZooKeeper::create_or_set_with`]
+    /// # Examples
+    /// ```rust,ignore
+    /// let stat = zk.create_or_set("/config", b"data").await?;
+    /// ```
+    pub async fn create_or_set(&self, path: impl AsRef<str>, data: &[u8]) -> ZKResult<Stat> {
+        self.create_or_set_with(path, data, &UpdateOptions::default())
+            .await
+    }
+
+    /// 节点存在时覆盖数据，不存在时创建节点，重复执行的结果相同。
+    /// 与其他客户端同时创建、删除节点时会重试，超过 `max_attempts` 时返回最后一次的错误
+    /// # Examples
+    /// ```rust,ignore
+    /// let options = UpdateOptions::default().create_mode(CreateMode::Ephemeral);
+    /// let stat = zk.create_or_set_with("/workers/w1", b"busy", &options).await?;
+    /// ```
+    ///
+    /// # Args
+    /// - `path`： 目标路径，必须以 "/" 开头
+    /// - `data`： 节点数据
+    /// - `options`： 创建节点的配置，参考 [`UpdateOptions`]
+    /// # Returns
+    /// - `Stat`： 写入之后节点的统计对象，请查看 [`Stat`]
+    pub async fn create_or_set_with(
+        &self,
+        path: impl AsRef<str>,
+        data: &[u8],
+        options: &UpdateOptions,
+    ) -> ZKResult<Stat> {
+        let path = path.as_ref();
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            match self.set(path, data).await {
+                Err(e) if e.code() == Some(ServerErrorCode::NoNode) => {}
+                result => return result,
+            }
+            match self.create_for_update(path, data, options).await {
+                // 节点刚刚被其他客户端创建，重新覆盖数据
+                Err(e) if is_conflict(&e, true) && attempt < options.max_attempts => {}
+                result => return result,
+            }
+        }
+    }
+
+    /// 使用默认配置创建节点，参考 [`ZooKeeper::create_if_absent_with`]
+    /// # Examples
+    /// ```rust,ignore
+    /// let created = zk.create_if_absent("/config", b"defaults").await?;
+    /// ```
+    pub async fn create_if_absent(&self, path: impl AsRef<str>, data: &[u8]) -> ZKResult<bool> {
+        self.create_if_absent_with(path, data, &UpdateOptions::default())
+            .await
+    }
+
+    /// 节点不存在时创建节点，已经存在时不做任何修改
+    /// # Examples
+    /// ```rust,ignore
+    /// let options = UpdateOptions::default().acl(CREATOR_ALL_ACL.clone());
+    /// if zk.create_if_absent_with("/config", b"defaults", &options).await? {
+    ///     println!("initialized");
+    /// }
+    /// ```
+    ///
+    /// # Args
+    /// - `path`： 目标路径，必须以 "/" 开头
+    /// - `data`： 节点数据
+    /// - `options`： 创建节点的配置，参考 [`UpdateOptions`]
+    /// # Returns
+    /// - `bool`：节点是否由本次调用创建
+    pub async fn create_if_absent_with(
+        &self,
+        path: impl AsRef<str>,
+        data: &[u8],
+        options: &UpdateOptions,
+    ) -> ZKResult<bool> {
+        match self.create_for_update(path.as_ref(), data, options).await {
+            Ok(_) => Ok(true),
+            Err(e) if e.code() == Some(ServerErrorCode::NodeExists) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// 使用默认配置更新节点，参考 [`ZooKeeper::update_with`]
+    /// # Examples
+    /// ```rust,ignore
+    /// let stat = zk.update("/counter", |old| {
+    ///     let count = old.map(|data| String::from_utf8_lossy(data).parse::<u64>().unwrap()).unwrap_or(0);
+    ///     Some((count + 1).to_string().into_bytes())
+    /// }).await?;
+    /// ```
+    pub async fn update<F>(&self, path: impl AsRef<str>, f: F) -> ZKResult<Option<Stat>>
+    where
+        F: FnMut(Option<&[u8]>) -> Option<Vec<u8>>,
+    {
+        self.update_with(path, &UpdateOptions::default(), f).await
+    }
+
+    /// 读取节点数据，通过 `f` 计算新的数据，再带上读取时的版本号写入。
+    /// 期间节点被其他客户端修改时重新读取并再次调用 `f`，因此 `f` 可能会被调用多次
+    /// # Examples
+    /// ```rust,ignore
+    /// let options = UpdateOptions::default().max_attempts(3);
+    /// zk.update_with("/config", &options, |old| old.map(|data| data.to_ascii_uppercase())).await?;
+    /// ```
+    ///
+    /// # Args
+    /// - `path`： 目标路径，必须以 "/" 开头
+    /// - `options`： 重试次数以及节点不存在时创建节点的配置，参考 [`UpdateOptions`]
+    /// - `f`： 参数为当前数据，节点不存在时为 `None`；返回新的数据，返回 `None` 表示不写入
+    /// # Returns
+    /// - `Some(Stat)`：写入之后节点的统计对象
+    /// - `None`：`f` 返回了 `None`，没有写入
+    /// # Errors
+    ///
+    /// 尝试次数超过 `max_attempts` 时返回最后一次的 `BadVersion`、`NoNode` 或者 `NodeExists`
+    pub async fn update_with<F>(
+        &self,
+        path: impl AsRef<str>,
+        options: &UpdateOptions,
+        mut f: F,
+    ) -> ZKResult<Option<Stat>>
+    where
+        F: FnMut(Option<&[u8]>) -> Option<Vec<u8>>,
+    {
+        let path = path.as_ref();
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let mut stat = Stat::default();
+            let old = match self.get(path, Some(&mut stat)).await {
+                Ok(data) => Some(data),
+                Err(e) if e.code() == Some(ServerErrorCode::NoNode) => None,
+                Err(e) => return Err(e),
+            };
+            let data = match f(old.as_deref()) {
+                Some(data) => data,
+                None => return Ok(None),
+            };
+            let result = if old.is_some() {
+                self.setv(path, &data, VersionType::Version(stat.version))
+                    .await
+            } else {
+                self.create_for_update(path, &data, options).await
+            };
+            match result {
+                Ok(stat) => return Ok(Some(stat)),
+                Err(e) if is_conflict(&e, old.is_none()) && attempt < options.max_attempts => {
+                    debug!("Update `{}` conflicted on attempt {}: {}", path, attempt, e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    async fn create_for_update(
+        &self,
+        path: &str,
+        data: &[u8],
+        options: &UpdateOptions,
+    ) -> ZKResult<Stat> {
+        let acl_list = options
+            .acl_list
+            .clone()
+            .unwrap_or_else(|| self.default_acl());
+        let (_, stat) = self
+            .create2(path, Some(data), acl_list, options.create_mode)
+            .await?;
+        Ok(stat)
+    }
+}
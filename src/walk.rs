@@ -0,0 +1,296 @@
+//! # 子树遍历模块
+//! [`ZooKeeper::walk`] 以异步 [`Stream`] 的形式遍历子树，支持广度优先和深度优先（先序）两种顺序。
+//! 多个节点的 `childrens`、`get` 请求会并发执行，但是输出顺序始终与串行遍历相同
+
+use std::collections::VecDeque;
+use std::fmt::{Debug, Formatter};
+use std::sync::Arc;
+
+use futures_util::stream::{self, Stream};
+use tokio::task::JoinHandle;
+
+use crate::error::ServerErrorCode;
+use crate::protocol::resp::Stat;
+use crate::{paths, ZKError, ZKResult, ZooKeeper};
+
+/// 遍历顺序
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum WalkOrder {
+    /// 广度优先，逐层输出
+    BreadthFirst,
+    /// 深度优先，父节点先于子节点输出，子节点的整个子树先于下一个兄弟节点输出
+    DepthFirst,
+}
+
+/// 子树遍历的配置
+/// # Examples
+///
+/// ```rust,ignore
+/// let options = WalkOptions::default()
+///     .order(WalkOrder::DepthFirst)
+///     .max_depth(2)
+///     .filter(|path| !path.ends_with("/tmp"))
+///     .with_data(true)
+///     .concurrency(16);
+/// ```
+#[derive(Clone)]
+pub struct WalkOptions {
+    order: WalkOrder,
+    max_depth: Option<usize>,
+    filter: Option<Arc<dyn Fn(&str) -> bool + Send + Sync>>,
+    with_data: bool,
+    concurrency: usize,
+}
+
+impl Debug for WalkOptions {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WalkOptions")
+            .field("order", &self.order)
+            .field("max_depth", &self.max_depth)
+            .field("filter", &self.filter.is_some())
+            .field("with_data", &self.with_data)
+            .field("concurrency", &self.concurrency)
+            .finish()
+    }
+}
+
+impl Default for WalkOptions {
+    fn default() -> Self {
+        WalkOptions {
+            order: WalkOrder::BreadthFirst,
+            max_depth: None,
+            filter: None,
+            with_data: false,
+            concurrency: 8,
+        }
+    }
+}
+
+impl WalkOptions {
+    /// 遍历顺序，默认为广度优先，参考 [`WalkOrder`]
+    pub fn order(mut self, order: WalkOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// 最大深度，起始节点的深度为 0，默认不限制
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    /// 路径过滤条件，返回 false 的节点以及它的子树都不会被访问
+    pub fn filter(mut self, filter: impl Fn(&str) -> bool + Send + Sync + 'static) -> Self {
+        self.filter = Some(Arc::new(filter));
+        self
+    }
+
+    /// 是否读取节点数据，默认为 false
+    pub fn with_data(mut self, with_data: bool) -> Self {
+        self.with_data = with_data;
+        self
+    }
+
+    /// 同时请求的节点数，默认为 8
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    fn accept(&self, path: &str) -> bool {
+        self.filter.as_ref().is_none_or(|filter| filter(path))
+    }
+}
+
+/// 遍历得到的节点
+/// - `path`：节点路径，不包含 chroot
+/// - `depth`：相对于起始节点的深度，起始节点为 0
+/// - `stat`：节点统计数据，参考 [`Stat`]
+/// - `data`：节点数据，只有开启 [`WalkOptions::with_data`] 时才有
+#[derive(Debug, Clone)]
+pub struct WalkEntry {
+    pub path: String,
+    pub depth: usize,
+    pub stat: Stat,
+    pub data: Option<Vec<u8>>,
+}
+
+/// 节点的请求结果，节点已经被删除时为 `None`
+type Fetched = ZKResult<Option<(WalkEntry, Vec<String>)>>;
+
+enum Slot {
+    Pending(String, usize),
+    Running(JoinHandle<Fetched>),
+}
+
+/// 待访问的节点保存在队列中，队首的 `concurrency` 个节点提前发起请求，按照队列顺序输出
+struct Walker {
+    zk: ZooKeeper,
+    options: WalkOptions,
+    queue: VecDeque<Slot>,
+}
+
+impl Walker {
+    fn start_fetches(&mut self) {
+        for slot in self.queue.iter_mut().take(self.options.concurrency) {
+            if let Slot::Pending(path, depth) = slot {
+                let zk = self.zk.clone();
+                let path = std::mem::take(path);
+                let depth = *depth;
+                let with_data = self.options.with_data;
+                let descend = self.options.max_depth.is_none_or(|max| depth < max);
+                *slot = Slot::Running(tokio::spawn(async move {
+                    fetch(zk, path, depth, with_data, descend).await
+                }));
+            }
+        }
+    }
+
+    async fn next(&mut self) -> Option<ZKResult<WalkEntry>> {
+        loop {
+            self.start_fetches();
+            let fetched = match self.queue.pop_front()? {
+                Slot::Running(handle) => handle.await.unwrap_or(Err(ZKError::UnknownError)),
+                Slot::Pending(..) => unreachable!("fetch of queue head is always started"),
+            };
+            let (entry, children) = match fetched {
+                Ok(Some(fetched)) => fetched,
+                Ok(None) => continue,
+                Err(e) => {
+                    self.queue.clear();
+                    return Some(Err(e));
+                }
+            };
+            let children = children
+                .iter()
+                .map(|child| paths::join(&entry.path, child))
+                .filter(|child| self.options.accept(child))
+                .map(|child| Slot::Pending(child, entry.depth + 1));
+            match self.options.order {
+                WalkOrder::BreadthFirst => self.queue.extend(children),
+                WalkOrder::DepthFirst => {
+                    for child in children.collect::<Vec<_>>().into_iter().rev() {
+                        self.queue.push_front(child);
+                    }
+                }
+            }
+            return Some(Ok(entry));
+        }
+    }
+}
+
+impl Drop for Walker {
+    fn drop(&mut self) {
+        for slot in self.queue.iter() {
+            if let Slot::Running(handle) = slot {
+                handle.abort();
+            }
+        }
+    }
+}
+
+async fn fetch(
+    zk: ZooKeeper,
+    path: String,
+    depth: usize,
+    with_data: bool,
+    descend: bool,
+) -> Fetched {
+    let mut stat = Stat::default();
+    let mut children_stat = Stat::default();
+    let (data, children) = match (with_data, descend) {
+        (true, true) => {
+            let (data, children) = tokio::join!(
+                zk.get(path.as_str(), Some(&mut stat)),
+                zk.childrens(path.as_str(), &mut children_stat)
+            );
+            (Some(data), children)
+        }
+        (true, false) => (
+            Some(zk.get(path.as_str(), Some(&mut stat)).await),
+            Ok(Vec::new()),
+        ),
+        (false, true) => {
+            let children = zk.childrens(path.as_str(), &mut stat).await;
+            (None, children)
+        }
+        (false, false) => match zk.exists(path.as_str()).await? {
+            Some(s) => {
+                stat = s;
+                (None, Ok(Vec::new()))
+            }
+            None => return Ok(None),
+        },
+    };
+    let data = match data.transpose() {
+        Ok(data) => data,
+        Err(e) if e.code() == Some(ServerErrorCode::NoNode) => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let children = match children {
+        Ok(children) => children,
+        Err(e) if e.code() == Some(ServerErrorCode::NoNode) => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let entry = WalkEntry {
+        path,
+        depth,
+        stat,
+        data,
+    };
+    Ok(Some((entry, children)))
+}
+
+impl ZooKeeper {
+    /// 使用默认配置广度优先遍历子树，参考 [`ZooKeeper::walk_with`]
+    /// # Examples
+    /// ```rust,ignore
+    /// use futures_util::StreamExt;
+    ///
+    /// let mut entries = Box::pin(zk.walk("/app"));
+    /// while let Some(entry) = entries.next().await {
+    ///     let entry = entry?;
+    ///     println!("{} {}", entry.path, entry.stat.num_children);
+    /// }
+    /// ```
+    pub fn walk(&self, path: impl AsRef<str>) -> impl Stream<Item = ZKResult<WalkEntry>> {
+        self.walk_with(path, WalkOptions::default())
+    }
+
+    /// 遍历以 `path` 为根的子树，遍历过程中被删除的节点会被跳过，其他错误会结束遍历
+    /// # Examples
+    /// ```rust,ignore
+    /// let options = WalkOptions::default().order(WalkOrder::DepthFirst).with_data(true);
+    /// let entries: Vec<_> = zk.walk_with("/app", options).collect().await;
+    /// ```
+    ///
+    /// # Args
+    /// - `path`： 起始路径，必须以 "/" 开头
+    /// - `options`： 遍历配置，参考 [`WalkOptions`]
+    /// # Returns
+    /// - `Stream`：按照遍历顺序输出的节点，参考 [`WalkEntry`]
+    pub fn walk_with(
+        &self,
+        path: impl AsRef<str>,
+        options: WalkOptions,
+    ) -> impl Stream<Item = ZKResult<WalkEntry>> {
+        let path = path.as_ref();
+        let mut queue = VecDeque::new();
+        let invalid = paths::validate_path(path).err();
+        if invalid.is_none() && options.accept(path) {
+            queue.push_back(Slot::Pending(path.to_string(), 0));
+        }
+        let walker = Walker {
+            zk: self.clone(),
+            options,
+            queue,
+        };
+        stream::unfold((walker, invalid), |(mut walker, invalid)| async move {
+            if let Some(e) = invalid {
+                return Some((Err(e), (walker, None)));
+            }
+            let next = walker.next().await?;
+            Some((next, (walker, None)))
+        })
+    }
+}
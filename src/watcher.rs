@@ -4,6 +4,7 @@ use std::fmt::Debug;
 use std::sync::Mutex;
 
 use crate::constants::{EventType, KeeperState};
+use crate::protocol::req::SetWatchesRequest;
 use crate::protocol::resp::WatcherEvent;
 use crate::ZKResult;
 
@@ -36,6 +37,7 @@ pub trait Watcher: Debug + Send {
 #[derive(Debug)]
 pub(crate) struct WatcherManager {
     disable_auto_watch_reset: bool,
+    default_watcher: Mutex<Option<Box<dyn Watcher>>>,
     data_watches: Mutex<HashMap<String, Vec<Box<dyn Watcher>>>>,
     exists_watches: Mutex<HashMap<String, Vec<Box<dyn Watcher>>>>,
     child_watches: Mutex<HashMap<String, Vec<Box<dyn Watcher>>>>,
@@ -98,9 +100,13 @@ impl WatcherManager {
         Ok(())
     }
 
-    pub(crate) fn new(disable_auto_watch_reset: bool) -> Self {
+    pub(crate) fn new(
+        disable_auto_watch_reset: bool,
+        default_watcher: Option<Box<dyn Watcher>>,
+    ) -> Self {
         WatcherManager {
             disable_auto_watch_reset,
+            default_watcher: Mutex::new(default_watcher),
             data_watches: Mutex::new(HashMap::new()),
             exists_watches: Mutex::new(HashMap::new()),
             child_watches: Mutex::new(HashMap::new()),
@@ -109,6 +115,34 @@ impl WatcherManager {
         }
     }
 
+    /// 重连后需要重新向服务端注册的回调
+    pub(crate) fn set_watches_request(&self, relative_zxid: i64) -> Option<SetWatchesRequest> {
+        if self.disable_auto_watch_reset {
+            return None;
+        }
+        let paths = |watches: &Mutex<HashMap<String, Vec<Box<dyn Watcher>>>>| {
+            watches
+                .lock()
+                .unwrap()
+                .keys()
+                .cloned()
+                .collect::<Vec<String>>()
+        };
+        let request = SetWatchesRequest::new(
+            relative_zxid,
+            paths(&self.data_watches),
+            paths(&self.exists_watches),
+            paths(&self.child_watches),
+            paths(&self.persistent_watches),
+            paths(&self.persistent_recursive_watches),
+        );
+        if request.is_empty() {
+            None
+        } else {
+            Some(request)
+        }
+    }
+
     fn add_watches(
         &self,
         path: &str,
@@ -125,13 +159,21 @@ impl WatcherManager {
         todo!()
     }
 
+    fn process_in_place(event: &WatchedEvent, watches: &HashMap<String, Vec<Box<dyn Watcher>>>) {
+        for v in watches.values() {
+            for w in v.iter() {
+                w.process(event);
+            }
+        }
+    }
+
     fn trigger_persistent_watches(&self, event: &WatchedEvent) {
         if let Some(v) = self.persistent_watches.lock().unwrap().get(&event.path) {
             for ww in v.iter() {
                 ww.process(event);
             }
         }
-        for p in PathIterable::new(&event.path, i32::max_value()) {
+        for p in PathIterable::new(&event.path, i32::MAX) {
             if let Some(v) = self.persistent_recursive_watches.lock().unwrap().get(p) {
                 for ww in v.iter() {
                     ww.process(event);
@@ -147,37 +189,30 @@ impl WatcherManager {
         let mut watchers: Vec<Box<dyn Watcher>> = Vec::new();
         match event.event_type {
             EventType::None => {
+                if let Some(w) = self.default_watcher.lock().unwrap().as_ref() {
+                    w.process(event);
+                }
                 let clear = self.disable_auto_watch_reset
                     && event.keep_state.ne(&KeeperState::SyncConnected);
-                // data_watches
-                for (_, v) in self.data_watches.lock().unwrap().iter_mut() {
-                    watchers.append(v);
-                }
-                if clear {
-                    self.data_watches.lock().unwrap().clear();
-                }
-                // exists_watches
-                for (_, v) in self.exists_watches.lock().unwrap().iter_mut() {
-                    watchers.append(v);
-                }
-                if clear {
-                    self.exists_watches.lock().unwrap().clear();
-                }
-                // child_watches
-                for (_, v) in self.child_watches.lock().unwrap().iter_mut() {
-                    watchers.append(v);
-                }
-                if clear {
-                    self.child_watches.lock().unwrap().clear();
+                // 连接状态变化通知所有回调，只有不自动重置回调时才需要移除一次性回调
+                for watches in [
+                    &self.data_watches,
+                    &self.exists_watches,
+                    &self.child_watches,
+                ] {
+                    let mut guard = watches.lock().unwrap();
+                    if clear {
+                        for (_, mut v) in guard.drain() {
+                            watchers.append(&mut v);
+                        }
+                    } else {
+                        Self::process_in_place(event, &guard);
+                    }
                 }
                 // persistent_watches
-                for (_, v) in self.persistent_watches.lock().unwrap().iter_mut() {
-                    watchers.append(v);
-                }
+                Self::process_in_place(event, &self.persistent_watches.lock().unwrap());
                 // persistent_recursive_watches
-                for (_, v) in self.persistent_recursive_watches.lock().unwrap().iter_mut() {
-                    watchers.append(v);
-                }
+                Self::process_in_place(event, &self.persistent_recursive_watches.lock().unwrap());
             }
             EventType::NodeCreated | EventType::NodeDataChanged => {
                 self.add_watches(&event.path, &mut watchers, &self.data_watches);
@@ -0,0 +1,108 @@
+mod common;
+
+use std::time::Duration;
+
+use repeekooz::{ConnectionState, KeeperState, Perms, Scheme, States, ZKError, ZooKeeper, ACL};
+
+use common::{StandInServer, DIGEST_AUTH};
+
+fn auth_count(server: &StandInServer) -> usize {
+    server
+        .received_ops()
+        .iter()
+        .filter(|op| **op == 100)
+        .count()
+}
+
+#[tokio::test]
+async fn add_auth_is_replayed_after_reconnect() {
+    let server = StandInServer::start().await;
+    let zk = ZooKeeper::new(server.addr.as_str(), Duration::from_secs(3))
+        .await
+        .unwrap();
+    let mut listener = zk.subscribe_state();
+
+    zk.add_auth("digest", DIGEST_AUTH).await.unwrap();
+    assert_eq!(auth_count(&server), 1);
+
+    server.drop_connections();
+    assert_eq!(
+        listener.recv().await.unwrap().state,
+        ConnectionState::Suspended
+    );
+    assert_eq!(
+        listener.recv().await.unwrap().state,
+        ConnectionState::Reconnected
+    );
+    // 重连后自动重新发送鉴权信息
+    zk.delete("/repeekooz").await.unwrap();
+    assert_eq!(auth_count(&server), 2);
+    assert_eq!(zk.state().unwrap(), States::Connected);
+}
+
+#[tokio::test]
+async fn add_auth_failed() {
+    let server = StandInServer::start().await;
+    let zk = ZooKeeper::new(server.addr.as_str(), Duration::from_secs(3))
+        .await
+        .unwrap();
+    let mut listener = zk.subscribe_state();
+
+    let result = zk.add_auth("digest", "user:wrong").await;
+    assert!(matches!(result, Err(ZKError::ServerError(_, -115))));
+    let event = listener.recv().await.unwrap();
+    assert_eq!(event.state, ConnectionState::AuthFailed);
+    assert_eq!(event.keeper_state, KeeperState::AuthFailed);
+    assert_eq!(zk.state().unwrap(), States::AuthFailed);
+    assert!(zk.delete("/repeekooz").await.is_err());
+}
+
+#[tokio::test]
+async fn permission_evaluator_uses_session_auth() {
+    let server = StandInServer::start().await;
+    let zk = ZooKeeper::new(server.addr.as_str(), Duration::from_secs(3))
+        .await
+        .unwrap();
+    let acl_list = vec![ACL::new(
+        Perms::ALL,
+        Scheme::Digest(Scheme::generate_digest(DIGEST_AUTH)),
+    )];
+    assert!(zk
+        .permission_evaluator()
+        .evaluate(&acl_list)
+        .perms
+        .is_empty());
+
+    zk.add_auth("digest", DIGEST_AUTH).await.unwrap();
+    assert!(zk
+        .permission_evaluator()
+        .evaluate(&acl_list)
+        .allows(Perms::ALL));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn concurrent_add_auth_gets_its_own_reply() {
+    let server = StandInServer::start().await;
+    for _ in 0..20 {
+        let zk = ZooKeeper::new(server.addr.as_str(), Duration::from_secs(3))
+            .await
+            .unwrap();
+        let (good, bad) = tokio::join!(
+            zk.add_auth("digest", DIGEST_AUTH),
+            zk.add_auth("digest", "user:wrong")
+        );
+        // 错误的鉴权先发送时服务端会断开连接，正确的鉴权只能成功或者失去连接
+        assert!(!matches!(good, Err(ZKError::ServerError(_, -115))));
+        assert!(matches!(bad, Err(ZKError::ServerError(_, -115))));
+        // 被拒绝的鉴权信息不会保存
+        let acl_list = vec![ACL::new(
+            Perms::ALL,
+            Scheme::Digest(Scheme::generate_digest("user:wrong")),
+        )];
+        assert!(zk
+            .permission_evaluator()
+            .evaluate(&acl_list)
+            .perms
+            .is_empty());
+    }
+}
@@ -0,0 +1,97 @@
+mod common;
+
+use repeekooz::{BlobOptions, CreateMode, ServerErrorCode, Stat, ZKError, ACL};
+
+use common::StandInServer;
+
+fn blob(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i % 251) as u8).collect()
+}
+
+#[tokio::test]
+async fn blob_round_trip() {
+    let server = StandInServer::start_with_tree().await;
+    let zk = server.connect().await;
+    let options = BlobOptions::default().chunk_size(100).batch_size(300);
+
+    let data = blob(1000);
+    let stat = zk.put_blob_with("/routes", &data, &options).await.unwrap();
+    assert_eq!(stat.version, 1);
+    let chunks = server.node_children("/routes").unwrap();
+    assert_eq!(chunks.len(), 10);
+    assert_eq!(chunks[0], "chunk-0000000000");
+    assert_eq!(
+        server.node_data("/routes/chunk-0000000000").unwrap(),
+        data[..100]
+    );
+    let mut stat = Stat::default();
+    assert_eq!(zk.get_blob("/routes", Some(&mut stat)).await.unwrap(), data);
+    assert_eq!(stat.version, 1);
+
+    // 发布新的数据后旧的分块被删除
+    let data = blob(250);
+    zk.put_blob_with("/routes", &data, &options).await.unwrap();
+    assert_eq!(
+        server.node_children("/routes").unwrap(),
+        ["chunk-0000000010", "chunk-0000000011", "chunk-0000000012"]
+    );
+    assert_eq!(zk.get_blob("/routes", None).await.unwrap(), data);
+
+    zk.put_blob("/routes", b"").await.unwrap();
+    assert!(server.node_children("/routes").unwrap().is_empty());
+    assert!(zk.get_blob("/routes", None).await.unwrap().is_empty());
+
+    zk.put_blob("/routes", &data).await.unwrap();
+    zk.delete_blob("/routes").await.unwrap();
+    assert!(server.node_data("/routes").is_none());
+}
+
+#[tokio::test]
+async fn blob_checksum_mismatch() {
+    let server = StandInServer::start_with_tree().await;
+    let zk = server.connect().await;
+    let options = BlobOptions::default().chunk_size(100);
+    zk.put_blob_with("/routes", &blob(300), &options)
+        .await
+        .unwrap();
+
+    zk.set("/routes/chunk-0000000001", &[0; 100]).await.unwrap();
+    let e = zk.get_blob("/routes", None).await.unwrap_err();
+    assert!(matches!(e, ZKError::DecodeError { codec: "blob", .. }));
+    assert_eq!(e.path(), Some("/routes"));
+    assert!(e.to_string().contains("checksum mismatch"));
+
+    // 普通节点不是清单
+    zk.set("/routes", b"plain").await.unwrap();
+    let e = zk.get_blob("/routes", None).await.unwrap_err();
+    assert!(matches!(e, ZKError::DecodeError { codec: "blob", .. }));
+
+    let e = zk.get_blob("/missing", None).await.unwrap_err();
+    assert_eq!(e.code(), Some(ServerErrorCode::NoNode));
+}
+
+#[tokio::test]
+async fn put_blob_keeps_plain_data() {
+    let server = StandInServer::start_with_tree().await;
+    let zk = server.connect().await;
+    zk.create(
+        "/plain",
+        Some(b"user data"),
+        ACL::world_acl(),
+        CreateMode::Persistent,
+    )
+    .await
+    .unwrap();
+    let e = zk.put_blob("/plain", &blob(300)).await.unwrap_err();
+    assert!(matches!(e, ZKError::DecodeError { codec: "blob", .. }));
+    assert!(e.to_string().contains("not a blob manifest"));
+    assert_eq!(server.node_data("/plain").unwrap(), b"user data");
+    assert!(server.node_children("/plain").unwrap().is_empty());
+
+    // 空节点可以作为清单节点
+    zk.create("/empty", None, ACL::world_acl(), CreateMode::Persistent)
+        .await
+        .unwrap();
+    zk.put_blob("/empty", &blob(300)).await.unwrap();
+    assert_eq!(zk.get_blob("/empty", None).await.unwrap(), blob(300));
+}
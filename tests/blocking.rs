@@ -0,0 +1,166 @@
+#![cfg(feature = "blocking")]
+
+mod common;
+
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use repeekooz::blocking::ZooKeeper;
+use repeekooz::{
+    CreateMode, EventType, Op, RetryNTimes, ServerErrorCode, WatchedEvent, Watcher, ACL,
+};
+use tokio::runtime::Runtime;
+
+use common::StandInServer;
+
+/// 替身服务端运行在独立的运行时中，测试线程本身不在异步上下文里
+fn start_server() -> (Runtime, StandInServer) {
+    let runtime = Runtime::new().unwrap();
+    let server = runtime.block_on(StandInServer::start_with_tree());
+    (runtime, server)
+}
+
+#[derive(Debug)]
+struct ChannelWatcher(mpsc::Sender<String>);
+
+impl Watcher for ChannelWatcher {
+    fn process(&self, event: &WatchedEvent) {
+        // 连接状态变化会通知所有回调，连接建立的通知可能晚于回调注册
+        if matches!(event.event_type, EventType::None) {
+            return;
+        }
+        let _ = self
+            .0
+            .send(format!("{:?} {}", event.event_type, event.path));
+    }
+}
+
+#[test]
+fn blocking_operations_from_plain_threads() {
+    let (_runtime, server) = start_server();
+    let zk = ZooKeeper::new(server.addr.as_str(), Duration::from_secs(3)).unwrap();
+    zk.create_all(
+        "/app/config",
+        Some(b"v1"),
+        ACL::world_acl(),
+        CreateMode::Persistent,
+    )
+    .unwrap();
+    assert_eq!(zk.get("/app/config", None).unwrap(), b"v1");
+    let stat = zk.set("/app/config", b"v2").unwrap();
+    assert_eq!(stat.version, 1);
+    zk.multi(vec![
+        Op::check("/app/config", 1),
+        Op::create("/app/jobs", None, ACL::world_acl(), CreateMode::Persistent),
+    ])
+    .unwrap();
+
+    // clone 出来的客户端在多个普通线程中共享同一个会话
+    let workers: Vec<_> = (0..4)
+        .map(|_| {
+            let zk = zk.clone();
+            thread::spawn(move || {
+                zk.create(
+                    "/app/jobs/job-",
+                    None,
+                    ACL::world_acl(),
+                    CreateMode::PersistentSequential,
+                )
+                .unwrap()
+            })
+        })
+        .collect();
+    for worker in workers {
+        worker.join().unwrap();
+    }
+    let jobs = zk.sequential_children("/app/jobs", "job-").unwrap();
+    let sequences: Vec<i32> = jobs.iter().map(|job| job.sequence()).collect();
+    assert_eq!(sequences, [0, 1, 2, 3]);
+
+    let paths: Vec<String> = zk.walk("/app").map(|entry| entry.unwrap().path).collect();
+    assert_eq!(paths.len(), 7);
+    assert_eq!(paths[..3], ["/app", "/app/config", "/app/jobs"]);
+
+    let deleted = zk.delete_all("/app").unwrap();
+    assert_eq!(deleted.len(), 7);
+    assert_eq!(zk.exists("/app").unwrap(), None);
+    zk.close().unwrap();
+}
+
+#[test]
+fn blocking_watchers_are_notified() {
+    let (_runtime, server) = start_server();
+    let zk = ZooKeeper::new(server.addr.as_str(), Duration::from_secs(3)).unwrap();
+    let (tx, rx) = mpsc::channel();
+    zk.create(
+        "/config",
+        Some(b"v1"),
+        ACL::world_acl(),
+        CreateMode::Persistent,
+    )
+    .unwrap();
+    zk.getw("/config", Some(ChannelWatcher(tx.clone())), None)
+        .unwrap();
+    zk.childrenw("/config", Some(ChannelWatcher(tx.clone())))
+        .unwrap();
+    assert_eq!(
+        zk.existsw("/missing", Some(ChannelWatcher(tx))).unwrap(),
+        None
+    );
+
+    let other = zk.clone();
+    thread::spawn(move || {
+        other.set("/config", b"v2").unwrap();
+        other
+            .create("/config/db", None, ACL::world_acl(), CreateMode::Persistent)
+            .unwrap();
+        other
+            .create("/missing", None, ACL::world_acl(), CreateMode::Persistent)
+            .unwrap();
+    })
+    .join()
+    .unwrap();
+
+    let timeout = Duration::from_secs(3);
+    let mut events: Vec<_> = (0..3).map(|_| rx.recv_timeout(timeout).unwrap()).collect();
+    events.sort();
+    assert_eq!(
+        events,
+        [
+            "NodeChildrenChanged /config",
+            "NodeCreated /missing",
+            "NodeDataChanged /config",
+        ]
+    );
+    // 一次性回调触发之后不会再收到通知
+    zk.set("/config", b"v3").unwrap();
+    assert!(rx.recv_timeout(Duration::from_millis(200)).is_err());
+}
+
+#[test]
+fn blocking_retry_after_connection_loss() {
+    let (_runtime, server) = start_server();
+    let zk = ZooKeeper::new(server.addr.as_str(), Duration::from_secs(3)).unwrap();
+    zk.create(
+        "/a",
+        Some(b"data"),
+        ACL::world_acl(),
+        CreateMode::Persistent,
+    )
+    .unwrap();
+
+    // 第一次读取的响应丢失并断开连接，重连后重试成功
+    server.drop_next_reply(4);
+    let policy = RetryNTimes::new(10, Duration::from_millis(50));
+    let data = zk.retry_with(&policy, |zk| zk.get("/a", None)).unwrap();
+    assert_eq!(data, b"data");
+    assert_eq!(
+        server.received_ops().iter().filter(|op| **op == 4).count(),
+        2
+    );
+
+    // 不可重试的错误直接返回
+    let e = zk.retry(|zk| zk.get("/b", None)).unwrap_err();
+    assert_eq!(e.code(), Some(ServerErrorCode::NoNode));
+}
@@ -0,0 +1,88 @@
+#![cfg(all(feature = "json", feature = "bincode"))]
+
+mod common;
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use repeekooz::{BincodeCodec, CreateMode, JsonCodec, Stat, ZKError, ZooKeeper, ACL};
+
+use common::StandInServer;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Route {
+    shard: String,
+    replicas: Vec<String>,
+}
+
+#[tokio::test]
+async fn typed_data_round_trip() {
+    let server = StandInServer::start_with_tree().await;
+    let zk = ZooKeeper::new(server.addr.as_str(), Duration::from_secs(3))
+        .await
+        .unwrap();
+    let route = Route {
+        shard: "s1".to_string(),
+        replicas: vec!["10.0.0.1".to_string(), "10.0.0.2".to_string()],
+    };
+    zk.create_as("/route", &route, ACL::world_acl(), CreateMode::Persistent)
+        .await
+        .unwrap();
+    assert_eq!(
+        server.node_data("/route").unwrap(),
+        br#"{"shard":"s1","replicas":["10.0.0.1","10.0.0.2"]}"#
+    );
+    let decoded: Route = zk.get_as("/route").await.unwrap();
+    assert_eq!(decoded, route);
+
+    let stat = zk
+        .set_as_with("/route", &route, &BincodeCodec)
+        .await
+        .unwrap();
+    assert_eq!(stat.version, 1);
+    let mut stat = Stat::default();
+    let decoded = zk
+        .get_as_with::<Route>("/route", &BincodeCodec, Some(&mut stat))
+        .await
+        .unwrap();
+    assert_eq!(decoded, route);
+    assert_eq!(stat.version, 1);
+
+    // 默认的 JSON 和显式指定 JsonCodec 的结果相同
+    zk.set_as("/route", &route).await.unwrap();
+    let decoded = zk
+        .get_as_with::<Route>("/route", &JsonCodec, None)
+        .await
+        .unwrap();
+    assert_eq!(decoded, route);
+}
+
+#[tokio::test]
+async fn decode_error_includes_path() {
+    let server = StandInServer::start_with_tree().await;
+    let zk = ZooKeeper::new(server.addr.as_str(), Duration::from_secs(3))
+        .await
+        .unwrap();
+    zk.create(
+        "/legacy",
+        Some(b"not json"),
+        ACL::world_acl(),
+        CreateMode::Persistent,
+    )
+    .await
+    .unwrap();
+    let e = zk.get_as::<Route>("/legacy").await.unwrap_err();
+    assert_eq!(e.path(), Some("/legacy"));
+    assert!(matches!(e, ZKError::DecodeError { codec: "json", .. }));
+    assert!(e
+        .to_string()
+        .starts_with("Decode data of `/legacy` with json"));
+
+    // 节点不存在时返回服务端错误
+    let e = zk
+        .get_as_with::<Route>("/missing", &JsonCodec, None)
+        .await
+        .unwrap_err();
+    assert!(e.is_node_error());
+}
@@ -0,0 +1,649 @@
+//! 测试用的 ZooKeeper 替身服务端，只实现了握手、心跳、鉴权和关闭会话，用于在没有真实服务端的情况下测试连接的生命周期。
+//! 通过 [`StandInServer::start_with_tree`] 启动时会额外维护一棵内存数据树，用于测试节点相关的操作和一次性回调
+#![allow(unused)]
+
+mod tree;
+
+use std::collections::HashSet;
+use std::sync::atomic::Ordering::{Acquire, Relaxed, Release};
+use std::sync::atomic::{AtomicBool, AtomicUsize};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use bytes::{Buf, BufMut, BytesMut};
+use md5::{Digest, Md5};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{broadcast, mpsc, Notify};
+
+use repeekooz::ZooKeeper;
+
+use tree::{
+    DataTree, NODE_CHILDREN_CHANGED, NODE_CREATED, NODE_DATA_CHANGED, NODE_DELETED, NO_NODE,
+};
+
+/// 第一个会话的 ID，之后新建的会话依次递增
+const SESSION_ID: i64 = 0x1234;
+/// 替身服务端唯一接受的鉴权信息
+pub const DIGEST_AUTH: &str = "user:password";
+/// 替身服务端 SASL 使用的 realm 和 nonce
+const SASL_REALM: &str = "zk-sasl-md5";
+const SASL_NONCE: &str = "repeekooz-nonce";
+
+trait Socket: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Socket for T {}
+
+#[derive(Clone)]
+enum Acceptor {
+    Plain,
+    #[cfg(feature = "tls")]
+    Tls(tokio_rustls::TlsAcceptor),
+}
+
+impl Acceptor {
+    async fn accept(&self, socket: TcpStream) -> Option<Box<dyn Socket>> {
+        match self {
+            Acceptor::Plain => Some(Box::new(socket)),
+            #[cfg(feature = "tls")]
+            Acceptor::Tls(acceptor) => match acceptor.accept(socket).await {
+                Ok(socket) => Some(Box::new(socket)),
+                Err(_) => None,
+            },
+        }
+    }
+}
+
+/// 一次性回调的种类
+#[derive(Clone, Copy, PartialEq)]
+enum WatchKind {
+    Data,
+    Exists,
+    Child,
+}
+
+impl WatchKind {
+    /// 该种类的回调是否会被事件触发
+    fn triggered_by(self, event_type: i32) -> bool {
+        match self {
+            WatchKind::Data => matches!(event_type, NODE_DELETED | NODE_DATA_CHANGED),
+            WatchKind::Exists => {
+                matches!(event_type, NODE_CREATED | NODE_DELETED | NODE_DATA_CHANGED)
+            }
+            WatchKind::Child => matches!(event_type, NODE_DELETED | NODE_CHILDREN_CHANGED),
+        }
+    }
+}
+
+struct Watch {
+    kind: WatchKind,
+    path: String,
+    conn: usize,
+    notify_tx: mpsc::UnboundedSender<BytesMut>,
+}
+
+/// 所有连接共享的服务端状态
+#[derive(Default)]
+struct Shared {
+    expired: AtomicBool,
+    ops: Mutex<Vec<i32>>,
+    sasl: Mutex<Option<(String, String)>>,
+    tree: Option<Mutex<DataTree>>,
+    drop_reply: Mutex<Option<i32>>,
+    skip_reply: Mutex<Option<i32>>,
+    reads_paused: AtomicBool,
+    pause: Notify,
+    refuse: AtomicBool,
+    next_conn: AtomicUsize,
+    next_session: AtomicUsize,
+    watches: Mutex<Vec<Watch>>,
+}
+
+impl Shared {
+    /// 触发路径上的回调，每个连接同一个事件只通知一次
+    fn trigger(&self, event_type: i32, path: &str) {
+        let mut notified = HashSet::new();
+        self.watches.lock().unwrap().retain(|watch| {
+            if watch.path != path || !watch.kind.triggered_by(event_type) {
+                return true;
+            }
+            if notified.insert(watch.conn) {
+                let mut frame = BytesMut::new();
+                frame.put_i32(-1);
+                frame.put_i64(-1);
+                frame.put_i32(0);
+                frame.put_i32(event_type);
+                // SyncConnected
+                frame.put_i32(3);
+                write_buffer(&mut frame, path.as_bytes());
+                let _ = watch.notify_tx.send(frame);
+            }
+            false
+        });
+    }
+}
+
+pub struct StandInServer {
+    pub addr: String,
+    drop_tx: broadcast::Sender<()>,
+    shared: Arc<Shared>,
+}
+
+impl StandInServer {
+    pub async fn start() -> StandInServer {
+        StandInServer::start_with_sasl(None).await
+    }
+
+    /// 要求客户端使用 DIGEST-MD5 完成 SASL 鉴权，`credential` 为 (用户名, 密码)
+    pub async fn start_with_sasl(credential: Option<(&str, &str)>) -> StandInServer {
+        StandInServer::start_with(Acceptor::Plain, credential, false).await
+    }
+
+    /// 维护内存数据树，节点相关的请求按照 ZooKeeper 的语义处理
+    pub async fn start_with_tree() -> StandInServer {
+        StandInServer::start_with(Acceptor::Plain, None, true).await
+    }
+
+    /// 在 `secureClientPort` 上提供服务，使用 tests/tls 下的证书，`client_auth` 表示是否要求客户端证书
+    #[cfg(feature = "tls")]
+    pub async fn start_tls(client_auth: bool) -> StandInServer {
+        use tokio_rustls::rustls::pki_types::pem::PemObject;
+        use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+        use tokio_rustls::rustls::server::WebPkiClientVerifier;
+        use tokio_rustls::rustls::{crypto, RootCertStore, ServerConfig};
+
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/tls");
+        let certs = CertificateDer::pem_file_iter(format!("{}/server.pem", dir))
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let key = PrivateKeyDer::from_pem_file(format!("{}/server.key", dir)).unwrap();
+        let provider = Arc::new(crypto::ring::default_provider());
+        let builder = ServerConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()
+            .unwrap();
+        let builder = if client_auth {
+            let mut roots = RootCertStore::empty();
+            roots
+                .add(CertificateDer::from_pem_file(format!("{}/ca.pem", dir)).unwrap())
+                .unwrap();
+            let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider)
+                .build()
+                .unwrap();
+            builder.with_client_cert_verifier(verifier)
+        } else {
+            builder.with_no_client_auth()
+        };
+        let config = builder.with_single_cert(certs, key).unwrap();
+        let acceptor = tokio_rustls::TlsAcceptor::from(Arc::new(config));
+        StandInServer::start_with(Acceptor::Tls(acceptor), None, false).await
+    }
+
+    async fn start_with(
+        acceptor: Acceptor,
+        credential: Option<(&str, &str)>,
+        tree: bool,
+    ) -> StandInServer {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let (drop_tx, _) = broadcast::channel(16);
+        let shared = Arc::new(Shared {
+            tree: tree.then(|| Mutex::new(DataTree::default())),
+            ..Shared::default()
+        });
+        let server_drop_tx = drop_tx.clone();
+        let server_shared = shared.clone();
+        tokio::spawn(async move {
+            while let Ok((socket, _)) = listener.accept().await {
+                if server_shared.refuse.load(Acquire) {
+                    continue;
+                }
+                let drop_rx = server_drop_tx.subscribe();
+                let acceptor = acceptor.clone();
+                let shared = server_shared.clone();
+                tokio::spawn(async move {
+                    if let Some(socket) = acceptor.accept(socket).await {
+                        serve(socket, drop_rx, shared).await;
+                    }
+                });
+            }
+        });
+        let server = StandInServer {
+            addr,
+            drop_tx,
+            shared,
+        };
+        server.set_sasl(credential);
+        server
+    }
+
+    /// 使用默认配置连接到替身服务端，会话超时时间为 3 秒
+    pub async fn connect(&self) -> ZooKeeper {
+        ZooKeeper::new(self.addr.as_str(), Duration::from_secs(3))
+            .await
+            .unwrap()
+    }
+
+    /// 修改之后的连接需要的 SASL 鉴权信息，`None` 表示拒绝所有 SASL 鉴权
+    pub fn set_sasl(&self, credential: Option<(&str, &str)>) {
+        *self.shared.sasl.lock().unwrap() = credential.map(|(u, p)| (u.to_string(), p.to_string()));
+    }
+
+    /// 收到的所有请求的 OpCode
+    pub fn received_ops(&self) -> Vec<i32> {
+        self.shared.ops.lock().unwrap().clone()
+    }
+
+    /// 下一个 `op` 请求照常处理，但是不返回响应并断开连接，模拟请求已经生效而响应丢失
+    pub fn drop_next_reply(&self, op: i32) {
+        *self.shared.drop_reply.lock().unwrap() = Some(op);
+    }
+
+    /// 下一个 `op` 请求照常处理，但是不返回响应，连接保持可用，模拟响应迟迟没有到达
+    pub fn skip_next_reply(&self, op: i32) {
+        *self.shared.skip_reply.lock().unwrap() = Some(op);
+    }
+
+    /// 数据树中节点的数据，节点不存在时返回 `None`
+    pub fn node_data(&self, path: &str) -> Option<Vec<u8>> {
+        let tree = self.shared.tree.as_ref()?.lock().unwrap();
+        tree.get(path).ok().map(|node| node.data.clone())
+    }
+
+    /// 数据树中节点的子节点列表，节点不存在时返回 `None`
+    pub fn node_children(&self, path: &str) -> Option<Vec<String>> {
+        let tree = self.shared.tree.as_ref()?.lock().unwrap();
+        tree.get(path)
+            .ok()
+            .map(|node| node.children.iter().cloned().collect())
+    }
+
+    /// 断开当前所有连接，模拟网络故障，之后的连接恢复读取请求
+    pub fn drop_connections(&self) {
+        self.shared.reads_paused.store(false, Release);
+        let _ = self.drop_tx.send(());
+    }
+
+    /// 当前的连接不再读取请求，直到 [`StandInServer::drop_connections`]，客户端写入大的请求时会被阻塞
+    pub fn pause_reads(&self) {
+        self.shared.reads_paused.store(true, Release);
+        self.shared.pause.notify_waiters();
+    }
+
+    /// 之后的连接建立后立即关闭，模拟服务端不可用
+    pub fn refuse_connections(&self) {
+        self.shared.refuse.store(true, Release);
+    }
+
+    /// 之后所有的重连都会被告知会话已经过期
+    pub fn expire_sessions(&self) {
+        self.shared.expired.store(true, Release);
+    }
+}
+
+async fn read_frame(socket: &mut Box<dyn Socket>) -> Option<BytesMut> {
+    let len = socket.read_i32().await.ok()?;
+    let mut buf = BytesMut::zeroed(len as usize);
+    socket.read_exact(&mut buf).await.ok()?;
+    Some(buf)
+}
+
+async fn write_frame(socket: &mut Box<dyn Socket>, buf: BytesMut) {
+    let mut frame = BytesMut::new();
+    frame.put_i32(buf.len() as i32);
+    frame.extend(buf);
+    let _ = socket.write_all(&frame).await;
+    let _ = socket.flush().await;
+}
+
+async fn serve(
+    mut socket: Box<dyn Socket>,
+    mut drop_rx: broadcast::Receiver<()>,
+    shared: Arc<Shared>,
+) {
+    // ConnectRequest
+    let mut req = match read_frame(&mut socket).await {
+        Some(req) => req,
+        None => return,
+    };
+    let _protocol_version = req.get_i32();
+    let _last_zxid = req.get_i64();
+    let timeout = req.get_i32();
+    // 重连时沿用原来的会话
+    let session_id = match req.get_i64() {
+        0 => SESSION_ID + shared.next_session.fetch_add(1, Relaxed) as i64,
+        session_id => session_id,
+    };
+    let mut resp = BytesMut::new();
+    resp.put_i32(0);
+    if shared.expired.load(Acquire) {
+        resp.put_i32(0);
+        resp.put_i64(0);
+        // 过期会话的临时节点随之删除
+        if let Some(tree) = &shared.tree {
+            let mut tree = tree.lock().unwrap();
+            tree.remove_ephemerals(session_id);
+            for (event_type, path) in std::mem::take(&mut tree.events) {
+                shared.trigger(event_type, &path);
+            }
+        }
+    } else {
+        resp.put_i32(timeout);
+        resp.put_i64(session_id);
+    }
+    resp.put_i32(16);
+    resp.put_slice(&[0; 16]);
+    resp.put_u8(0);
+    write_frame(&mut socket, resp).await;
+
+    let conn = shared.next_conn.fetch_add(1, Relaxed);
+    let (notify_tx, mut notify_rx) = mpsc::unbounded_channel();
+    loop {
+        if shared.reads_paused.load(Acquire) {
+            let _ = drop_rx.recv().await;
+            return;
+        }
+        let mut req = tokio::select! {
+            _ = shared.pause.notified() => continue,
+            req = read_frame(&mut socket) => match req {
+                Some(req) => req,
+                None => return,
+            },
+            Some(notification) = notify_rx.recv() => {
+                write_frame(&mut socket, notification).await;
+                continue;
+            }
+            _ = drop_rx.recv() => return,
+        };
+        let xid = req.get_i32();
+        let op = req.get_i32();
+        shared.ops.lock().unwrap().push(op);
+        let mut body = BytesMut::new();
+        let err = match op {
+            100 => check_auth(&mut req),
+            102 => match check_sasl(&mut req, shared.sasl.lock().unwrap().as_ref()) {
+                Some(token) => {
+                    write_buffer(&mut body, &token);
+                    0
+                }
+                None => -115,
+            },
+            _ => match &shared.tree {
+                Some(tree) => {
+                    let watch = watch_request(op, &req);
+                    let mut tree = tree.lock().unwrap();
+                    let err = match handle_tree_op(&mut tree, op, session_id, &mut req, &mut body) {
+                        Ok(()) => 0,
+                        Err(err) => err,
+                    };
+                    // 节点不存在时 Exists 同样会注册回调
+                    if let Some((kind, path)) = watch {
+                        if err == 0 || (kind == WatchKind::Exists && err == NO_NODE) {
+                            shared.watches.lock().unwrap().push(Watch {
+                                kind,
+                                path,
+                                conn,
+                                notify_tx: notify_tx.clone(),
+                            });
+                        }
+                    }
+                    for (event_type, path) in std::mem::take(&mut tree.events) {
+                        shared.trigger(event_type, &path);
+                    }
+                    err
+                }
+                None => {
+                    // Exists 和 SetData 返回一个空的 Stat
+                    if op == 3 || op == 5 {
+                        body.put_slice(&[0; 68]);
+                    }
+                    0
+                }
+            },
+        };
+        let drop_reply = {
+            let mut drop_reply = shared.drop_reply.lock().unwrap();
+            drop_reply.take_if(|drop_op| *drop_op == op).is_some()
+        };
+        if drop_reply {
+            return;
+        }
+        let skip_reply = {
+            let mut skip_reply = shared.skip_reply.lock().unwrap();
+            skip_reply.take_if(|skip_op| *skip_op == op).is_some()
+        };
+        if skip_reply {
+            continue;
+        }
+        let mut resp = BytesMut::new();
+        resp.put_i32(xid);
+        resp.put_i64(1);
+        resp.put_i32(err);
+        if err == 0 {
+            resp.extend(body);
+        }
+        write_frame(&mut socket, resp).await;
+        // CloseSession 或者鉴权失败后断开连接
+        if op == -11 || (err != 0 && (op == 100 || op == 102)) {
+            return;
+        }
+    }
+}
+
+/// 读请求中需要注册的回调，不需要时返回 `None`
+fn watch_request(op: i32, req: &BytesMut) -> Option<(WatchKind, String)> {
+    let kind = match op {
+        3 => WatchKind::Exists,
+        4 => WatchKind::Data,
+        8 | 12 => WatchKind::Child,
+        _ => return None,
+    };
+    let mut req = req.clone();
+    let path = read_string(&mut req);
+    (req.get_u8() != 0).then_some((kind, path))
+}
+
+/// 在数据树上执行请求，响应写入 `body`，失败时返回错误码
+fn handle_tree_op(
+    tree: &mut DataTree,
+    op: i32,
+    session_id: i64,
+    req: &mut BytesMut,
+    body: &mut BytesMut,
+) -> Result<(), i32> {
+    match op {
+        // Create、Create2、CreateContainer
+        1 | 15 | 19 => {
+            let path = read_string(req);
+            let data = read_buffer(req);
+            let acl_start = req.clone();
+            let acl_count = req.get_i32();
+            for _ in 0..acl_count {
+                let _perms = req.get_i32();
+                let _scheme = read_string(req);
+                let _id = read_string(req);
+            }
+            let acl = acl_start[..acl_start.len() - req.len()].to_vec();
+            let flags = req.get_i32();
+            let path = tree.create(&path, data, acl, flags, session_id)?;
+            write_buffer(body, path.as_bytes());
+            if op == 15 || op == 19 {
+                tree.get(&path)?.write_stat(body);
+            }
+        }
+        // Delete
+        2 => {
+            let path = read_string(req);
+            let version = req.get_i32();
+            tree.delete(&path, version)?;
+        }
+        // Exists
+        3 => tree.get(&read_string(req))?.write_stat(body),
+        // GetData
+        4 => {
+            let node = tree.get(&read_string(req))?;
+            write_buffer(body, &node.data);
+            node.write_stat(body);
+        }
+        // SetData
+        5 => {
+            let path = read_string(req);
+            let data = read_buffer(req);
+            let version = req.get_i32();
+            tree.set(&path, data, version)?.write_stat(body);
+        }
+        // GetACL
+        6 => {
+            let node = tree.get(&read_string(req))?;
+            body.put_slice(&node.acl);
+            node.write_stat(body);
+        }
+        // GetChildren、GetChildren2
+        8 | 12 => {
+            let node = tree.get(&read_string(req))?;
+            body.put_i32(node.children.len() as i32);
+            for child in node.children.iter() {
+                write_buffer(body, child.as_bytes());
+            }
+            if op == 12 {
+                node.write_stat(body);
+            }
+        }
+        // Check
+        13 => {
+            let path = read_string(req);
+            let version = req.get_i32();
+            tree.check(&path, version)?;
+        }
+        // Multi，在副本上依次执行，全部成功才替换原来的数据树
+        14 => {
+            let mut snapshot = tree.clone();
+            let mut results = Vec::new();
+            let mut failed = false;
+            loop {
+                let sub_op = req.get_i32();
+                let done = req.get_u8() != 0;
+                let _err = req.get_i32();
+                if done {
+                    break;
+                }
+                let mut sub_body = BytesMut::new();
+                let result = handle_tree_op(&mut snapshot, sub_op, session_id, req, &mut sub_body);
+                failed |= result.is_err();
+                results.push((sub_op, result.map(|_| sub_body)));
+            }
+            let mut seen_error = false;
+            for (sub_op, result) in results {
+                if !failed {
+                    body.put_i32(sub_op);
+                    body.put_u8(0);
+                    body.put_i32(0);
+                    body.put_slice(&result.unwrap());
+                    continue;
+                }
+                // 失败之前的操作为 0，之后的操作为 RuntimeInconsistency
+                let err = match result {
+                    _ if seen_error => -2,
+                    Ok(_) => 0,
+                    Err(err) => err,
+                };
+                seen_error |= err != 0;
+                body.put_i32(-1);
+                body.put_u8(0);
+                body.put_i32(err);
+                body.put_i32(err);
+            }
+            body.put_i32(-1);
+            body.put_u8(1);
+            body.put_i32(-1);
+            if !failed {
+                *tree = snapshot;
+            }
+        }
+        // CloseSession
+        -11 => tree.remove_ephemerals(session_id),
+        _ => {}
+    }
+    Ok(())
+}
+
+fn read_string(req: &mut BytesMut) -> String {
+    String::from_utf8(read_buffer(req)).unwrap()
+}
+
+fn write_buffer(body: &mut BytesMut, buf: &[u8]) {
+    body.put_i32(buf.len() as i32);
+    body.put_slice(buf);
+}
+
+fn read_buffer(req: &mut BytesMut) -> Vec<u8> {
+    let len = req.get_i32();
+    if len < 0 {
+        return Vec::new();
+    }
+    req.split_to(len as usize).to_vec()
+}
+
+/// 检查 AuthPacket，返回响应的错误码
+fn check_auth(req: &mut BytesMut) -> i32 {
+    let _auth_type = req.get_i32();
+    let scheme = read_buffer(req);
+    let auth = read_buffer(req);
+    if scheme == b"digest" && auth == DIGEST_AUTH.as_bytes() {
+        0
+    } else {
+        -115
+    }
+}
+
+/// 检查 SASL token，返回下一个 challenge，`None` 表示鉴权失败
+fn check_sasl(req: &mut BytesMut, credential: Option<&(String, String)>) -> Option<Vec<u8>> {
+    let (username, password) = credential?;
+    let token = String::from_utf8(read_buffer(req)).ok()?;
+    if token.is_empty() {
+        let challenge = format!(
+            "realm=\"{}\",nonce=\"{}\",qop=\"auth\",charset=utf-8,algorithm=md5-sess",
+            SASL_REALM, SASL_NONCE
+        );
+        return Some(challenge.into_bytes());
+    }
+    // 客户端不会发送包含逗号或者转义的值，简单拆分即可
+    let directives: Vec<(&str, &str)> = token
+        .split(',')
+        .filter_map(|d| d.split_once('='))
+        .map(|(k, v)| (k, v.trim_matches('"')))
+        .collect();
+    let find = |key: &str| {
+        directives
+            .iter()
+            .find(|(k, _)| *k == key)
+            .map(|(_, v)| *v)
+            .unwrap_or_default()
+    };
+    if find("username") != username || find("nonce") != SASL_NONCE {
+        return None;
+    }
+    let mut a1 = Md5::digest(format!("{}:{}:{}", username, SASL_REALM, password)).to_vec();
+    a1.extend(format!(":{}:{}", SASL_NONCE, find("cnonce")).as_bytes());
+    let ha1 = hex(&Md5::digest(&a1));
+    let kd = |a2: String| {
+        let ha2 = hex(&Md5::digest(a2));
+        hex(&Md5::digest(format!(
+            "{}:{}:{}:{}:auth:{}",
+            ha1,
+            SASL_NONCE,
+            find("nc"),
+            find("cnonce"),
+            ha2
+        )))
+    };
+    if find("response") != kd(format!("AUTHENTICATE:{}", find("digest-uri"))) {
+        return None;
+    }
+    let rspauth = kd(format!(":{}", find("digest-uri")));
+    Some(format!("rspauth={}", rspauth).into_bytes())
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
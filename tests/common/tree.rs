@@ -0,0 +1,181 @@
+//! 替身服务端的内存数据树，只实现了测试需要的节点语义：顺序节点、临时节点、版本号和子节点列表
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use bytes::{BufMut, BytesMut};
+
+/// 服务端错误码
+pub const NO_NODE: i32 = -101;
+pub const BAD_VERSION: i32 = -103;
+pub const NO_CHILDREN_FOR_EPHEMERALS: i32 = -108;
+pub const NODE_EXISTS: i32 = -110;
+pub const NOT_EMPTY: i32 = -111;
+
+/// 回调事件类型，与 `EventType` 的取值一致
+pub const NODE_CREATED: i32 = 1;
+pub const NODE_DELETED: i32 = 2;
+pub const NODE_DATA_CHANGED: i32 = 3;
+pub const NODE_CHILDREN_CHANGED: i32 = 4;
+
+#[derive(Debug, Clone, Default)]
+pub struct Node {
+    pub data: Vec<u8>,
+    pub czxid: i64,
+    pub mzxid: i64,
+    pub pzxid: i64,
+    pub version: i32,
+    pub cversion: i32,
+    pub ephemeral_owner: i64,
+    pub children: BTreeSet<String>,
+    /// 请求中原样的 ACL 列表
+    pub acl: Vec<u8>,
+}
+
+impl Node {
+    pub fn write_stat(&self, buf: &mut BytesMut) {
+        buf.put_i64(self.czxid);
+        buf.put_i64(self.mzxid);
+        buf.put_i64(0);
+        buf.put_i64(0);
+        buf.put_i32(self.version);
+        buf.put_i32(self.cversion);
+        buf.put_i32(0);
+        buf.put_i64(self.ephemeral_owner);
+        buf.put_i32(self.data.len() as i32);
+        buf.put_i32(self.children.len() as i32);
+        buf.put_i64(self.pzxid);
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DataTree {
+    nodes: BTreeMap<String, Node>,
+    zxid: i64,
+    /// 修改产生的回调事件 (事件类型, 路径)，由服务端取出后通知注册了回调的连接
+    pub events: Vec<(i32, String)>,
+}
+
+impl Default for DataTree {
+    fn default() -> Self {
+        let mut nodes = BTreeMap::new();
+        nodes.insert("/".to_string(), Node::default());
+        DataTree {
+            nodes,
+            zxid: 0,
+            events: Vec::new(),
+        }
+    }
+}
+
+fn split(path: &str) -> (String, String) {
+    let (parent, name) = path.rsplit_once('/').unwrap();
+    let parent = if parent.is_empty() { "/" } else { parent };
+    (parent.to_string(), name.to_string())
+}
+
+impl DataTree {
+    pub fn get(&self, path: &str) -> Result<&Node, i32> {
+        self.nodes.get(path).ok_or(NO_NODE)
+    }
+
+    /// `mode` 与 `CreateMode` 的取值一致，返回实际创建的路径
+    pub fn create(
+        &mut self,
+        path: &str,
+        data: Vec<u8>,
+        acl: Vec<u8>,
+        mode: i32,
+        session_id: i64,
+    ) -> Result<String, i32> {
+        let (parent_path, _) = split(path);
+        let parent = self.nodes.get_mut(&parent_path).ok_or(NO_NODE)?;
+        if parent.ephemeral_owner != 0 {
+            return Err(NO_CHILDREN_FOR_EPHEMERALS);
+        }
+        let path = if mode == 2 || mode == 3 || mode == 6 {
+            format!("{}{:010}", path, parent.cversion)
+        } else {
+            path.to_string()
+        };
+        let (_, name) = split(&path);
+        if parent.children.contains(&name) {
+            return Err(NODE_EXISTS);
+        }
+        self.zxid += 1;
+        parent.children.insert(name);
+        parent.cversion += 1;
+        parent.pzxid = self.zxid;
+        let node = Node {
+            data,
+            acl,
+            czxid: self.zxid,
+            mzxid: self.zxid,
+            pzxid: self.zxid,
+            ephemeral_owner: if mode == 1 || mode == 3 {
+                session_id
+            } else {
+                0
+            },
+            ..Node::default()
+        };
+        self.nodes.insert(path.clone(), node);
+        self.events.push((NODE_CREATED, path.clone()));
+        self.events.push((NODE_CHILDREN_CHANGED, parent_path));
+        Ok(path)
+    }
+
+    pub fn delete(&mut self, path: &str, version: i32) -> Result<(), i32> {
+        let node = self.get(path)?;
+        if version != -1 && version != node.version {
+            return Err(BAD_VERSION);
+        }
+        if !node.children.is_empty() {
+            return Err(NOT_EMPTY);
+        }
+        self.zxid += 1;
+        self.nodes.remove(path);
+        let (parent_path, name) = split(path);
+        let parent = self.nodes.get_mut(&parent_path).unwrap();
+        parent.children.remove(&name);
+        parent.cversion += 1;
+        parent.pzxid = self.zxid;
+        self.events.push((NODE_DELETED, path.to_string()));
+        self.events.push((NODE_CHILDREN_CHANGED, parent_path));
+        Ok(())
+    }
+
+    pub fn set(&mut self, path: &str, data: Vec<u8>, version: i32) -> Result<&Node, i32> {
+        let zxid = self.zxid + 1;
+        let node = self.nodes.get_mut(path).ok_or(NO_NODE)?;
+        if version != -1 && version != node.version {
+            return Err(BAD_VERSION);
+        }
+        self.zxid = zxid;
+        node.data = data;
+        node.version += 1;
+        node.mzxid = zxid;
+        self.events.push((NODE_DATA_CHANGED, path.to_string()));
+        Ok(&*node)
+    }
+
+    pub fn check(&self, path: &str, version: i32) -> Result<(), i32> {
+        let node = self.get(path)?;
+        if version != -1 && version != node.version {
+            return Err(BAD_VERSION);
+        }
+        Ok(())
+    }
+
+    /// 删除会话创建的所有临时节点
+    pub fn remove_ephemerals(&mut self, session_id: i64) {
+        let paths: Vec<String> = self
+            .nodes
+            .iter()
+            .filter(|(_, node)| node.ephemeral_owner == session_id)
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in paths {
+            let _ = self.delete(&path, -1);
+        }
+    }
+}
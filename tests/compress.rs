@@ -0,0 +1,93 @@
+#![cfg(all(feature = "gzip", feature = "zstd"))]
+
+mod common;
+
+use std::time::Duration;
+
+use repeekooz::{Compression, CreateMode, ZooKeeper, ACL};
+
+use common::StandInServer;
+
+async fn connect(server: &StandInServer, compression: Option<Compression>) -> ZooKeeper {
+    let mut builder =
+        ZooKeeper::builder(server.addr.as_str()).session_timeout(Duration::from_secs(3));
+    if let Some(compression) = compression {
+        builder = builder.compression(compression);
+    }
+    builder.build().await.unwrap()
+}
+
+#[tokio::test]
+async fn transparent_compression() {
+    let server = StandInServer::start_with_tree().await;
+    let writer = connect(&server, Some(Compression::gzip())).await;
+    let routes = "10.0.0.1:8080 shard-1\n".repeat(2000).into_bytes();
+    writer
+        .create(
+            "/routes",
+            Some(&routes),
+            ACL::world_acl(),
+            CreateMode::Persistent,
+        )
+        .await
+        .unwrap();
+    let stored = server.node_data("/routes").unwrap();
+    assert!(stored.len() < routes.len() / 10);
+    assert_eq!(writer.get("/routes", None).await.unwrap(), routes);
+
+    // 读取时根据头部选择算法，与自身配置的算法无关
+    let reader = connect(&server, Some(Compression::zstd())).await;
+    assert_eq!(reader.get("/routes", None).await.unwrap(), routes);
+    reader.set("/routes", &routes).await.unwrap();
+    assert_ne!(server.node_data("/routes").unwrap(), stored);
+    assert_eq!(writer.get("/routes", None).await.unwrap(), routes);
+}
+
+#[tokio::test]
+async fn reader_without_compression() {
+    let server = StandInServer::start_with_tree().await;
+    let plain = connect(&server, None).await;
+    let routes = "10.0.0.1:8080 shard-1\n".repeat(2000).into_bytes();
+    for (path, compression) in [
+        ("/gzip", Compression::gzip()),
+        ("/zstd", Compression::zstd()),
+    ] {
+        let writer = connect(&server, Some(compression)).await;
+        writer
+            .create(
+                path,
+                Some(&routes),
+                ACL::world_acl(),
+                CreateMode::Persistent,
+            )
+            .await
+            .unwrap();
+        assert!(server.node_data(path).unwrap().len() < routes.len() / 10);
+        // 没有配置压缩的客户端同样根据头部自动解压
+        assert_eq!(plain.get(path, None).await.unwrap(), routes);
+    }
+    // 没有配置压缩的客户端写入的数据不压缩
+    plain.set("/gzip", &routes).await.unwrap();
+    assert_eq!(server.node_data("/gzip").unwrap(), routes);
+}
+
+#[tokio::test]
+async fn legacy_and_small_data() {
+    let server = StandInServer::start_with_tree().await;
+    let plain = connect(&server, None).await;
+    plain
+        .create(
+            "/legacy",
+            Some(b"uncompressed"),
+            ACL::world_acl(),
+            CreateMode::Persistent,
+        )
+        .await
+        .unwrap();
+    let zk = connect(&server, Some(Compression::gzip().min_size(64))).await;
+    assert_eq!(zk.get("/legacy", None).await.unwrap(), b"uncompressed");
+
+    // 小于 min_size 的数据不压缩
+    zk.set("/legacy", b"still small").await.unwrap();
+    assert_eq!(server.node_data("/legacy").unwrap(), b"still small");
+}
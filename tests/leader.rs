@@ -0,0 +1,200 @@
+mod common;
+
+use std::time::Duration;
+
+use futures_util::StreamExt;
+use repeekooz::recipes::leader::{LeaderLatch, LeaderSelector, LeadershipEvent};
+use repeekooz::ZKError;
+use tokio::sync::mpsc;
+
+use common::StandInServer;
+
+async fn start(server: &StandInServer, id: &str) -> LeaderLatch {
+    let latch = LeaderLatch::new(&server.connect().await, "/election", id).unwrap();
+    latch.start().unwrap();
+    latch
+}
+
+const TIMEOUT: Duration = Duration::from_secs(3);
+
+#[tokio::test]
+async fn lowest_node_leads_and_hands_over() {
+    let server = StandInServer::start_with_tree().await;
+    let a = start(&server, "a").await;
+    assert!(a.await_leadership_timeout(TIMEOUT).await.unwrap());
+    let b = start(&server, "b").await;
+    let c = start(&server, "c").await;
+    let mut b_events = Box::pin(b.events());
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(!b.has_leadership() && !c.has_leadership());
+    assert_eq!(a.leader_id().await.unwrap().as_deref(), Some("a"));
+    assert_eq!(c.participant_ids().await.unwrap(), ["a", "b", "c"]);
+
+    // 中间的参与者退出不影响领导者
+    c.close().await.unwrap();
+    assert!(a.has_leadership());
+    assert_eq!(a.participant_ids().await.unwrap(), ["a", "b"]);
+
+    a.close().await.unwrap();
+    assert!(!a.has_leadership());
+    assert!(matches!(
+        a.await_leadership().await,
+        Err(ZKError::NotHeld { .. })
+    ));
+    assert!(b.await_leadership_timeout(TIMEOUT).await.unwrap());
+    assert_eq!(b_events.next().await, Some(LeadershipEvent::Gained));
+    assert_eq!(b.leader_id().await.unwrap().as_deref(), Some("b"));
+
+    // 重新参与选举时排在最后
+    a.start().unwrap();
+    assert!(!a
+        .await_leadership_timeout(Duration::from_millis(100))
+        .await
+        .unwrap());
+    drop(b);
+    assert!(a.await_leadership_timeout(TIMEOUT).await.unwrap());
+}
+
+#[tokio::test]
+async fn suspension_drops_leadership_until_reconnected() {
+    let server = StandInServer::start_with_tree().await;
+    let latch = start(&server, "a").await;
+    assert!(latch.await_leadership_timeout(TIMEOUT).await.unwrap());
+    let node = latch.our_node().unwrap();
+    let mut events = Box::pin(latch.events());
+
+    server.drop_connections();
+    let event = tokio::time::timeout(TIMEOUT, events.next()).await.unwrap();
+    assert_eq!(event, Some(LeadershipEvent::Lost));
+    // 会话没有过期，重连之后沿用原来的节点
+    let event = tokio::time::timeout(TIMEOUT, events.next()).await.unwrap();
+    assert_eq!(event, Some(LeadershipEvent::Gained));
+    assert!(latch.has_leadership());
+    assert_eq!(latch.our_node(), Some(node));
+}
+
+#[tokio::test]
+async fn session_expiry_ends_election() {
+    let server = StandInServer::start_with_tree().await;
+    let latch = start(&server, "a").await;
+    assert!(latch.await_leadership_timeout(TIMEOUT).await.unwrap());
+    let mut events = Box::pin(latch.events());
+
+    server.expire_sessions();
+    server.drop_connections();
+    let event = tokio::time::timeout(TIMEOUT, events.next()).await.unwrap();
+    assert_eq!(event, Some(LeadershipEvent::Lost));
+    let e = latch.await_leadership().await.unwrap_err();
+    assert!(!matches!(e, ZKError::NotHeld { .. }));
+    assert!(latch.start().is_err());
+    assert_eq!(latch.our_node(), None);
+}
+
+/// 任务开始时发送 `<id> start`，被取消或者结束时发送 `<id> end`
+struct Report(String, mpsc::UnboundedSender<String>);
+
+impl Drop for Report {
+    fn drop(&mut self) {
+        let _ = self.1.send(format!("{} end", self.0));
+    }
+}
+
+async fn selector(
+    server: &StandInServer,
+    id: &str,
+    hold: Option<Duration>,
+    tx: &mpsc::UnboundedSender<String>,
+) -> LeaderSelector {
+    let tx = tx.clone();
+    let zk = server.connect().await;
+    let owned_id = id.to_string();
+    LeaderSelector::new(&zk, "/election", id, move |_| {
+        let report = Report(owned_id.clone(), tx.clone());
+        async move {
+            let _ = report.1.send(format!("{} start", report.0));
+            match hold {
+                Some(hold) => tokio::time::sleep(hold).await,
+                None => std::future::pending().await,
+            }
+        }
+    })
+    .unwrap()
+}
+
+async fn next(rx: &mut mpsc::UnboundedReceiver<String>) -> String {
+    tokio::time::timeout(TIMEOUT, rx.recv())
+        .await
+        .unwrap()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn selectors_take_turns_when_requeued() {
+    let server = StandInServer::start_with_tree().await;
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let hold = Some(Duration::from_millis(50));
+    let a = selector(&server, "a", hold, &tx).await.auto_requeue(true);
+    let b = selector(&server, "b", hold, &tx).await.auto_requeue(true);
+    a.start().unwrap();
+    assert_eq!(next(&mut rx).await, "a start");
+    b.start().unwrap();
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    assert!(a.has_leadership() && !b.has_leadership());
+    assert_eq!(b.leader_id().await.unwrap().as_deref(), Some("a"));
+    assert_eq!(b.participant_ids().await.unwrap(), ["a", "b"]);
+
+    // 任务结束之后重新排在队尾
+    let mut turns = Vec::new();
+    for _ in 0..7 {
+        turns.push(next(&mut rx).await);
+    }
+    assert_eq!(
+        turns,
+        ["a end", "b start", "b end", "a start", "a end", "b start", "b end"]
+    );
+    a.close().await.unwrap();
+    b.close().await.unwrap();
+    assert!(a.participant_ids().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn selector_without_requeue_leads_once() {
+    let server = StandInServer::start_with_tree().await;
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let a = selector(&server, "a", Some(Duration::from_millis(10)), &tx).await;
+    a.start().unwrap();
+    assert_eq!(next(&mut rx).await, "a start");
+    assert_eq!(next(&mut rx).await, "a end");
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(rx.try_recv().is_err());
+    assert!(!a.has_leadership());
+    assert_eq!(a.leader_id().await.unwrap(), None);
+
+    a.requeue();
+    assert_eq!(next(&mut rx).await, "a start");
+    assert_eq!(next(&mut rx).await, "a end");
+}
+
+#[tokio::test]
+async fn selector_task_is_cancelled() {
+    let server = StandInServer::start_with_tree().await;
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let a = selector(&server, "a", None, &tx).await;
+    let b = selector(&server, "b", None, &tx).await;
+    a.start().unwrap();
+    assert_eq!(next(&mut rx).await, "a start");
+    b.start().unwrap();
+
+    // 主动关闭时取消任务，下一个参与者接替
+    a.close().await.unwrap();
+    assert_eq!(next(&mut rx).await, "a end");
+    assert_eq!(next(&mut rx).await, "b start");
+    assert!(b.has_leadership());
+
+    // 会话过期时取消任务
+    server.expire_sessions();
+    server.drop_connections();
+    assert_eq!(next(&mut rx).await, "b end");
+    assert!(!b.has_leadership());
+    assert!(b.start().is_err());
+}
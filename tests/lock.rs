@@ -0,0 +1,102 @@
+mod common;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use repeekooz::recipes::lock::InterProcessMutex;
+use repeekooz::ZKError;
+
+use common::StandInServer;
+
+#[tokio::test]
+async fn mutex_is_exclusive_with_increasing_tokens() {
+    let server = StandInServer::start_with_tree().await;
+    let busy = Arc::new(AtomicBool::new(false));
+    let tokens = Arc::new(Mutex::new(Vec::new()));
+    let mut tasks = Vec::new();
+    for _ in 0..3 {
+        let zk = server.connect().await;
+        let busy = busy.clone();
+        let tokens = tokens.clone();
+        tasks.push(tokio::spawn(async move {
+            let mutex = InterProcessMutex::new(&zk, "/locks/job").unwrap();
+            for _ in 0..3 {
+                mutex.acquire().await.unwrap();
+                assert!(!busy.swap(true, Ordering::SeqCst));
+                tokens.lock().unwrap().push(mutex.fencing_token().unwrap());
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                busy.store(false, Ordering::SeqCst);
+                mutex.release().await.unwrap();
+            }
+        }));
+    }
+    for task in tasks {
+        task.await.unwrap();
+    }
+    let tokens = tokens.lock().unwrap().clone();
+    assert_eq!(tokens.len(), 9);
+    assert!(tokens.windows(2).all(|pair| pair[0] < pair[1]));
+    assert_eq!(server.node_children("/locks/job").unwrap().len(), 0);
+}
+
+#[tokio::test]
+async fn mutex_is_reentrant() {
+    let server = StandInServer::start_with_tree().await;
+    let zk = server.connect().await;
+    let mutex = InterProcessMutex::new(&zk, "/locks/job").unwrap();
+    assert!(!mutex.is_acquired());
+    mutex.acquire().await.unwrap();
+    let token = mutex.fencing_token();
+    let node = mutex.lock_node().unwrap();
+    assert!(mutex.try_acquire(Duration::from_millis(10)).await.unwrap());
+    assert_eq!(mutex.fencing_token(), token);
+    assert_eq!(
+        mutex.participant_nodes().await.unwrap(),
+        std::slice::from_ref(&node)
+    );
+
+    mutex.release().await.unwrap();
+    assert!(mutex.is_acquired());
+    assert!(zk.exists(&node).await.unwrap().is_some());
+    mutex.release().await.unwrap();
+    assert!(!mutex.is_acquired());
+    assert_eq!(zk.exists(&node).await.unwrap(), None);
+    assert!(matches!(
+        mutex.release().await,
+        Err(ZKError::NotHeld { .. })
+    ));
+    assert!(InterProcessMutex::new(&zk, "locks").is_err());
+}
+
+#[tokio::test]
+async fn mutex_timeout_and_holder_session_close() {
+    let server = StandInServer::start_with_tree().await;
+    let holder = server.connect().await;
+    let zk = server.connect().await;
+    let held = InterProcessMutex::new(&holder, "/locks/job").unwrap();
+    held.acquire().await.unwrap();
+
+    // 超时之后自己的节点被删除，不会阻塞其他参与者
+    let mutex = InterProcessMutex::new(&zk, "/locks/job").unwrap();
+    assert!(!mutex.try_acquire(Duration::from_millis(100)).await.unwrap());
+    assert_eq!(server.node_children("/locks/job").unwrap().len(), 1);
+
+    // 取消的 acquire 在后台删除节点
+    let cancelled = tokio::time::timeout(Duration::from_millis(100), mutex.acquire()).await;
+    assert!(cancelled.is_err());
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(server.node_children("/locks/job").unwrap().len(), 1);
+
+    // 持有者的会话关闭后临时节点被删除，等待者获得锁
+    let waiter = tokio::spawn(async move {
+        mutex.try_acquire(Duration::from_secs(3)).await.unwrap();
+        mutex
+    });
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let token = held.fencing_token().unwrap();
+    holder.close().await.unwrap();
+    let mutex = waiter.await.unwrap();
+    assert!(mutex.is_acquired());
+    assert!(mutex.fencing_token().unwrap() > token);
+}
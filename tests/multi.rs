@@ -0,0 +1,71 @@
+mod common;
+
+use repeekooz::{CreateMode, Op, OpCode, OpResult, ServerErrorCode, ACL};
+
+use common::StandInServer;
+
+#[tokio::test]
+async fn multi_commits_all_ops() {
+    let server = StandInServer::start_with_tree().await;
+    let zk = server.connect().await;
+    zk.create("/a", Some(b"1"), ACL::world_acl(), CreateMode::Persistent)
+        .await
+        .unwrap();
+    zk.create("/b", None, ACL::world_acl(), CreateMode::Persistent)
+        .await
+        .unwrap();
+
+    let results = zk
+        .multi(vec![
+            Op::check("/a", 0),
+            Op::create(
+                "/a/seq-",
+                Some(b"x"),
+                ACL::world_acl(),
+                CreateMode::PersistentSequential,
+            ),
+            Op::set_data("/a", b"2", 0),
+            Op::delete("/b", -1),
+        ])
+        .await
+        .unwrap();
+    assert_eq!(results.len(), 4);
+    assert_eq!(results[0], OpResult::Check);
+    assert_eq!(
+        results[1],
+        OpResult::Create {
+            path: "/a/seq-0000000000".to_string()
+        }
+    );
+    assert!(matches!(&results[2], OpResult::SetData { stat } if stat.version == 1));
+    assert_eq!(results[3], OpResult::Delete);
+    assert_eq!(server.node_data("/a").unwrap(), b"2");
+    assert_eq!(server.node_data("/a/seq-0000000000").unwrap(), b"x");
+    assert!(server.node_data("/b").is_none());
+
+    assert!(zk.multi(Vec::new()).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn multi_rolls_back_on_failure() {
+    let server = StandInServer::start_with_tree().await;
+    let zk = server.connect().await;
+    zk.create("/a", Some(b"1"), ACL::world_acl(), CreateMode::Persistent)
+        .await
+        .unwrap();
+
+    let e = zk
+        .multi(vec![
+            Op::create("/a/child", None, ACL::world_acl(), CreateMode::Persistent),
+            Op::set_data("/a", b"2", 7),
+            Op::delete("/a", -1),
+        ])
+        .await
+        .unwrap_err();
+    // 错误中是真正失败的操作，而不是之后的 RuntimeInconsistency
+    assert_eq!(e.code(), Some(ServerErrorCode::BadVersion));
+    assert_eq!(e.op(), Some(OpCode::SetData));
+    assert_eq!(e.path(), Some("/a"));
+    assert!(server.node_data("/a/child").is_none());
+    assert_eq!(server.node_data("/a").unwrap(), b"1");
+}
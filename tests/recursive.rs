@@ -0,0 +1,152 @@
+mod common;
+
+use repeekooz::{
+    CreateMode, DeleteOptions, ParentOptions, ServerErrorCode, ZKError, ZooKeeper, ACL,
+    CREATOR_ALL_ACL, READ_ACL_UNSAFE,
+};
+
+use common::StandInServer;
+
+#[tokio::test]
+async fn create_all_missing_parents() {
+    let server = StandInServer::start_with_tree().await;
+    let zk = server.connect().await;
+    let path = zk
+        .create_all(
+            "/a/b/c",
+            Some(b"leaf"),
+            CREATOR_ALL_ACL.clone(),
+            CreateMode::Persistent,
+        )
+        .await
+        .unwrap();
+    assert_eq!(path, "/a/b/c");
+    assert_eq!(server.node_data("/a/b/c").unwrap(), b"leaf");
+    assert!(server.node_data("/a/b").unwrap().is_empty());
+    // 父节点默认使用目标节点的 ACL
+    assert_eq!(zk.get_acl("/a", None).await.unwrap(), *CREATOR_ALL_ACL);
+
+    // 部分父节点已经存在
+    let options = ParentOptions::default().acl(READ_ACL_UNSAFE.clone());
+    let path = zk
+        .create_all_with(
+            "/a/x/y/item-",
+            None,
+            ACL::world_acl(),
+            CreateMode::PersistentSequential,
+            &options,
+        )
+        .await
+        .unwrap();
+    assert_eq!(path, "/a/x/y/item-0000000000");
+    assert_eq!(zk.get_acl("/a/x", None).await.unwrap(), *READ_ACL_UNSAFE);
+    assert_eq!(zk.get_acl("/a/x/y", None).await.unwrap(), *READ_ACL_UNSAFE);
+    assert_eq!(
+        zk.get_acl(path.as_str(), None).await.unwrap(),
+        ACL::world_acl()
+    );
+
+    // 目标节点已经存在
+    let e = zk
+        .create_all("/a/b/c", None, ACL::world_acl(), CreateMode::Persistent)
+        .await
+        .unwrap_err();
+    assert_eq!(e.code(), Some(ServerErrorCode::NodeExists));
+    assert_eq!(e.path(), Some("/a/b/c"));
+}
+
+#[tokio::test]
+async fn create_all_concurrent_parents() {
+    let server = StandInServer::start_with_tree().await;
+    let zk = server.connect().await;
+    let other = server.connect().await;
+    let (first, second) = tokio::join!(
+        zk.create_all("/x/y/z/1", None, ACL::world_acl(), CreateMode::Persistent),
+        other.create_all("/x/y/z/2", None, ACL::world_acl(), CreateMode::Persistent),
+    );
+    assert_eq!(first.unwrap(), "/x/y/z/1");
+    assert_eq!(second.unwrap(), "/x/y/z/2");
+    assert_eq!(server.node_children("/x/y/z").unwrap(), ["1", "2"]);
+}
+
+async fn create_tree(zk: &ZooKeeper) {
+    for path in ["/app/a/1", "/app/a/2", "/app/b", "/app/c/x/y"] {
+        zk.create_all(path, None, ACL::world_acl(), CreateMode::Persistent)
+            .await
+            .unwrap();
+    }
+}
+
+#[tokio::test]
+async fn delete_all_subtree() {
+    let server = StandInServer::start_with_tree().await;
+    let zk = server.connect().await;
+    create_tree(&zk).await;
+
+    let options = DeleteOptions::default().dry_run(true);
+    let planned = zk.delete_all_with("/app", &options).await.unwrap();
+    // 叶子节点在父节点之前
+    assert_eq!(
+        planned,
+        [
+            "/app/a/1",
+            "/app/a/2",
+            "/app/a",
+            "/app/b",
+            "/app/c/x/y",
+            "/app/c/x",
+            "/app/c",
+            "/app"
+        ]
+    );
+    assert!(server.node_data("/app/a/1").is_some());
+
+    let options = DeleteOptions::default().batch_size(3);
+    assert_eq!(zk.delete_all_with("/app", &options).await.unwrap(), planned);
+    assert!(server.node_data("/app").is_none());
+    assert!(zk.delete_all("/app").await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn delete_all_guards() {
+    let server = StandInServer::start_with_tree().await;
+    let zk = server.connect().await;
+    create_tree(&zk).await;
+
+    let e = zk
+        .delete_all_with("/app", &DeleteOptions::default().max_nodes(7))
+        .await
+        .unwrap_err();
+    assert!(matches!(e, ZKError::TooManyNodes { limit: 7, .. }));
+    assert_eq!(e.path(), Some("/app"));
+    assert!(server.node_data("/app/a/1").is_some());
+
+    for path in ["/", "/zookeeper", "/zookeeper/quota"] {
+        let e = zk.delete_all(path).await.unwrap_err();
+        assert!(matches!(e, ZKError::PathError(..)));
+    }
+
+    // 关闭保护后删除 "/" 会清空所有数据，但不会删除根节点
+    let options = DeleteOptions::default().protect_system(false);
+    assert_eq!(zk.delete_all_with("/", &options).await.unwrap().len(), 8);
+    assert!(server.node_children("/").unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn delete_all_concurrent_deleters() {
+    let server = StandInServer::start_with_tree().await;
+    let zk = server.connect().await;
+    let other = server.connect().await;
+    create_tree(&zk).await;
+
+    let options = DeleteOptions::default().batch_size(2);
+    let (first, second) = tokio::join!(
+        zk.delete_all_with("/app", &options),
+        other.delete_all_with("/app", &options),
+    );
+    let mut deleted = first.unwrap();
+    deleted.extend(second.unwrap());
+    deleted.sort();
+    assert_eq!(deleted.len(), 8);
+    assert!(server.node_data("/app").is_none());
+}
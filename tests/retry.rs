@@ -0,0 +1,107 @@
+mod common;
+
+use std::time::Duration;
+
+use repeekooz::{
+    ConnectionState, CreateMode, OpCode, RetryNTimes, ServerErrorCode, ZooKeeper, ACL,
+};
+
+use common::StandInServer;
+
+async fn connect(server: &StandInServer) -> ZooKeeper {
+    ZooKeeper::builder(server.addr.as_str())
+        .session_timeout(Duration::from_secs(3))
+        .retry_policy(RetryNTimes::new(10, Duration::from_millis(50)))
+        .build()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn retry_after_connection_loss() {
+    let server = StandInServer::start_with_tree().await;
+    let zk = connect(&server).await;
+    zk.create(
+        "/a",
+        Some(b"data"),
+        ACL::world_acl(),
+        CreateMode::Persistent,
+    )
+    .await
+    .unwrap();
+    let mut listener = zk.subscribe_state();
+
+    server.drop_connections();
+    assert_eq!(
+        listener.recv().await.unwrap().state,
+        ConnectionState::Suspended
+    );
+    let e = zk.get("/a", None).await.unwrap_err();
+    assert!(e.is_connection_loss());
+    assert_eq!(e.op(), Some(OpCode::GetData));
+    let data = zk
+        .retry(|zk| async move { zk.get("/a", None).await })
+        .await
+        .unwrap();
+    assert_eq!(data, b"data");
+
+    // 不可重试的错误直接返回
+    let e = zk
+        .retry(|zk| async move { zk.get("/b", None).await })
+        .await
+        .unwrap_err();
+    assert_eq!(e.code(), Some(ServerErrorCode::NoNode));
+    assert_eq!(e.path(), Some("/b"));
+}
+
+#[tokio::test]
+async fn protected_sequential_create() {
+    let server = StandInServer::start_with_tree().await;
+    let zk = connect(&server).await;
+    zk.create("/queue", None, ACL::world_acl(), CreateMode::Persistent)
+        .await
+        .unwrap();
+
+    // 请求已经生效但是响应丢失，重试时不会重复创建
+    server.drop_next_reply(1);
+    let path = zk
+        .create_with_retry(
+            "/queue/item-",
+            Some(b"1"),
+            ACL::world_acl(),
+            CreateMode::PersistentSequential,
+        )
+        .await
+        .unwrap();
+    let children = server.node_children("/queue").unwrap();
+    assert_eq!(children.len(), 1);
+    assert_eq!(path, format!("/queue/{}", children[0]));
+    assert!(children[0].starts_with("_c_"));
+    assert!(children[0].ends_with("-item-0000000000"));
+    assert_eq!(server.node_data(&path).unwrap(), b"1");
+}
+
+#[tokio::test]
+async fn ephemeral_create_owned_by_session() {
+    let server = StandInServer::start_with_tree().await;
+    let zk = connect(&server).await;
+
+    server.drop_next_reply(1);
+    let path = zk
+        .create_with_retry("/leader", None, ACL::world_acl(), CreateMode::Ephemeral)
+        .await
+        .unwrap();
+    assert_eq!(path, "/leader");
+    assert_eq!(
+        zk.exists("/leader").await.unwrap().unwrap().ephemeral_owner,
+        zk.session_id().unwrap()
+    );
+
+    // 持久节点无法判断是否由本次调用创建
+    server.drop_next_reply(1);
+    let e = zk
+        .create_with_retry("/config", None, ACL::world_acl(), CreateMode::Persistent)
+        .await
+        .unwrap_err();
+    assert_eq!(e.code(), Some(ServerErrorCode::NodeExists));
+}
@@ -0,0 +1,127 @@
+mod common;
+
+use std::time::Duration;
+
+use repeekooz::recipes::rwlock::InterProcessReadWriteLock;
+use repeekooz::ConnectionState;
+
+use common::StandInServer;
+
+async fn lock(server: &StandInServer) -> InterProcessReadWriteLock {
+    InterProcessReadWriteLock::new(&server.connect().await, "/locks/config").unwrap()
+}
+
+const SHORT: Duration = Duration::from_millis(100);
+
+#[tokio::test]
+async fn readers_share_and_writers_exclude() {
+    let server = StandInServer::start_with_tree().await;
+    let (reader1, reader2, writer) = (
+        lock(&server).await,
+        lock(&server).await,
+        lock(&server).await,
+    );
+    reader1.read_lock().acquire().await.unwrap();
+    assert!(reader2.read_lock().try_acquire(SHORT).await.unwrap());
+    assert!(!writer.write_lock().try_acquire(SHORT).await.unwrap());
+
+    // 写者排队之后，新的读者需要等待写者
+    let waiting = tokio::spawn(async move {
+        writer.write_lock().acquire().await.unwrap();
+        writer
+    });
+    tokio::time::sleep(SHORT).await;
+    let reader3 = lock(&server).await;
+    assert!(!reader3.read_lock().try_acquire(SHORT).await.unwrap());
+    reader1.read_lock().release().await.unwrap();
+    reader2.read_lock().release().await.unwrap();
+    let writer = waiting.await.unwrap();
+    assert!(writer.write_lock().is_acquired());
+    assert!(!reader1.read_lock().try_acquire(SHORT).await.unwrap());
+
+    writer.write_lock().release().await.unwrap();
+    assert!(reader1.read_lock().try_acquire(SHORT).await.unwrap());
+    assert!(reader3.read_lock().try_acquire(SHORT).await.unwrap());
+}
+
+#[tokio::test]
+async fn write_lock_downgrades_to_read() {
+    let server = StandInServer::start_with_tree().await;
+    let (owner, writer, reader) = (
+        lock(&server).await,
+        lock(&server).await,
+        lock(&server).await,
+    );
+    owner.write_lock().acquire().await.unwrap();
+    assert!(!reader.read_lock().try_acquire(SHORT).await.unwrap());
+    // 持有写锁时直接获得读锁
+    assert!(owner.read_lock().try_acquire(SHORT).await.unwrap());
+    owner.write_lock().release().await.unwrap();
+    assert!(owner.read_lock().is_acquired());
+
+    assert!(reader.read_lock().try_acquire(SHORT).await.unwrap());
+    assert!(!writer.write_lock().try_acquire(SHORT).await.unwrap());
+    owner.read_lock().release().await.unwrap();
+    reader.read_lock().release().await.unwrap();
+    assert!(writer.write_lock().try_acquire(SHORT).await.unwrap());
+}
+
+#[tokio::test]
+async fn downgrade_blocks_queued_writer() {
+    let server = StandInServer::start_with_tree().await;
+    let (owner, writer) = (lock(&server).await, lock(&server).await);
+    owner.write_lock().acquire().await.unwrap();
+    // 写者在降级之前排队，降级得到的读节点排在它后面
+    let waiting = tokio::spawn(async move {
+        writer.write_lock().acquire().await.unwrap();
+        writer
+    });
+    tokio::time::sleep(SHORT).await;
+    owner.read_lock().acquire().await.unwrap();
+    owner.write_lock().release().await.unwrap();
+
+    tokio::time::sleep(SHORT * 3).await;
+    assert!(!waiting.is_finished());
+    assert!(owner.read_lock().is_acquired());
+
+    owner.read_lock().release().await.unwrap();
+    let writer = waiting.await.unwrap();
+    assert!(writer.write_lock().is_acquired());
+}
+
+#[tokio::test]
+async fn waiter_survives_reconnect() {
+    let server = StandInServer::start_with_tree().await;
+    let (reader, writer) = (lock(&server).await, lock(&server).await);
+    reader.read_lock().acquire().await.unwrap();
+    let waiting = tokio::spawn(async move {
+        writer
+            .write_lock()
+            .try_acquire(Duration::from_secs(3))
+            .await
+            .unwrap()
+    });
+    tokio::time::sleep(SHORT).await;
+    server.drop_connections();
+    tokio::time::sleep(SHORT).await;
+    // 会话没有过期，锁节点仍然有效
+    assert!(reader.read_lock().is_acquired());
+    reader.read_lock().release().await.unwrap();
+    assert!(waiting.await.unwrap());
+}
+
+#[tokio::test]
+async fn session_expiry_loses_lock() {
+    let server = StandInServer::start_with_tree().await;
+    let zk = server.connect().await;
+    let lock = InterProcessReadWriteLock::new(&zk, "/locks/config").unwrap();
+    lock.write_lock().acquire().await.unwrap();
+    let mut listener = zk.subscribe_state();
+
+    server.expire_sessions();
+    server.drop_connections();
+    while listener.recv().await.unwrap().state != ConnectionState::Lost {}
+    assert!(!lock.write_lock().is_acquired());
+    assert!(lock.write_lock().acquire().await.is_err());
+    assert_eq!(server.node_children("/locks/config").unwrap().len(), 0);
+}
@@ -0,0 +1,110 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use repeekooz::{
+    ConnectionState, EventType, KeeperState, OpCode, SaslConfig, ServerErrorCode, States,
+    WatchedEvent, Watcher, ZKError, ZooKeeper,
+};
+
+use common::StandInServer;
+
+fn sasl_count(server: &StandInServer) -> usize {
+    server
+        .received_ops()
+        .iter()
+        .filter(|op| **op == 102)
+        .count()
+}
+
+#[derive(Debug, Clone, Default)]
+struct StateRecorder {
+    states: Arc<Mutex<Vec<KeeperState>>>,
+}
+
+impl Watcher for StateRecorder {
+    fn process(&self, event: &WatchedEvent) {
+        if let EventType::None = event.event_type {
+            self.states.lock().unwrap().push(event.keep_state);
+        }
+    }
+}
+
+#[tokio::test]
+async fn sasl_authenticated_on_every_connection() {
+    let server = StandInServer::start_with_sasl(Some(("bob", "secret"))).await;
+    let recorder = StateRecorder::default();
+    let zk = ZooKeeper::builder(server.addr.as_str())
+        .session_timeout(Duration::from_secs(3))
+        .sasl(SaslConfig::digest_md5("bob", "secret"))
+        .default_watcher(recorder.clone())
+        .build()
+        .await
+        .unwrap();
+    // SASL 交换在其他请求之前完成
+    zk.delete("/repeekooz").await.unwrap();
+    assert_eq!(server.received_ops(), vec![102, 102, 2]);
+    let mut listener = zk.subscribe_state();
+
+    server.drop_connections();
+    assert_eq!(
+        listener.recv().await.unwrap().state,
+        ConnectionState::Suspended
+    );
+    let event = listener.recv().await.unwrap();
+    assert_eq!(event.state, ConnectionState::Reconnected);
+    assert_eq!(event.keeper_state, KeeperState::SyncConnected);
+    let event = listener.recv().await.unwrap();
+    assert_eq!(event.state, ConnectionState::Reconnected);
+    assert_eq!(event.keeper_state, KeeperState::SaslAuthenticated);
+    assert_eq!(sasl_count(&server), 4);
+    assert_eq!(zk.state().unwrap(), States::Connected);
+
+    // 回调在 EventTask 中异步执行
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(
+        recorder.states.lock().unwrap()[..2],
+        [KeeperState::SyncConnected, KeeperState::SaslAuthenticated]
+    );
+}
+
+#[tokio::test]
+async fn sasl_auth_failed() {
+    let server = StandInServer::start_with_sasl(Some(("bob", "secret"))).await;
+    let result = ZooKeeper::builder(server.addr.as_str())
+        .session_timeout(Duration::from_secs(3))
+        .sasl(SaslConfig::digest_md5("bob", "wrong"))
+        .build()
+        .await;
+    assert!(matches!(result, Err(ZKError::ServerError(_, -115))));
+    assert_eq!(sasl_count(&server), 2);
+}
+
+#[tokio::test]
+async fn sasl_auth_failed_after_reconnect() {
+    let server = StandInServer::start_with_sasl(Some(("bob", "secret"))).await;
+    let zk = ZooKeeper::builder(server.addr.as_str())
+        .session_timeout(Duration::from_secs(3))
+        .sasl(SaslConfig::digest_md5("bob", "secret"))
+        .build()
+        .await
+        .unwrap();
+    let mut listener = zk.subscribe_state();
+
+    // 服务端不再接受 SASL 鉴权
+    server.set_sasl(None);
+    server.drop_connections();
+    assert_eq!(
+        listener.recv().await.unwrap().state,
+        ConnectionState::Suspended
+    );
+    let event = listener.recv().await.unwrap();
+    assert_eq!(event.state, ConnectionState::AuthFailed);
+    assert_eq!(event.keeper_state, KeeperState::AuthFailed);
+    assert_eq!(zk.state().unwrap(), States::AuthFailed);
+    let e = zk.delete("/repeekooz").await.unwrap_err();
+    assert_eq!(e.code(), Some(ServerErrorCode::AuthFailed));
+    assert_eq!(e.op(), Some(OpCode::Delete));
+    assert_eq!(e.path(), Some("/repeekooz"));
+}
@@ -0,0 +1,148 @@
+mod common;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use repeekooz::recipes::semaphore::InterProcessSemaphore;
+use repeekooz::{CreateMode, ServerErrorCode, ZKError, ACL};
+
+use common::StandInServer;
+
+const SHORT: Duration = Duration::from_millis(100);
+
+#[tokio::test]
+async fn semaphore_caps_concurrent_holders() {
+    let server = StandInServer::start_with_tree().await;
+    let running = Arc::new(AtomicUsize::new(0));
+    let peak = Arc::new(AtomicUsize::new(0));
+    let mut tasks = Vec::new();
+    for _ in 0..4 {
+        let zk = server.connect().await;
+        let (running, peak) = (running.clone(), peak.clone());
+        tasks.push(tokio::spawn(async move {
+            let semaphore = InterProcessSemaphore::new(&zk, "/semaphores/db", 2).unwrap();
+            for _ in 0..3 {
+                let lease = semaphore.acquire().await.unwrap();
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(now, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                running.fetch_sub(1, Ordering::SeqCst);
+                lease.release().await.unwrap();
+            }
+        }));
+    }
+    for task in tasks {
+        task.await.unwrap();
+    }
+    assert_eq!(peak.load(Ordering::SeqCst), 2);
+    let zk = server.connect().await;
+    let semaphore = InterProcessSemaphore::new(&zk, "/semaphores/db", 2).unwrap();
+    assert!(semaphore.lease_nodes().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn leases_time_out_and_return_on_drop() {
+    let server = StandInServer::start_with_tree().await;
+    let zk = server.connect().await;
+    let semaphore = InterProcessSemaphore::new(&zk, "/semaphores/db", 2).unwrap();
+    let mut leases = semaphore.acquire_many(2).await.unwrap();
+    assert_eq!(
+        semaphore.lease_nodes().await.unwrap(),
+        [leases[0].node(), leases[1].node()]
+    );
+
+    let other = InterProcessSemaphore::new(&server.connect().await, "/semaphores/db", 2).unwrap();
+    assert!(other.try_acquire(SHORT).await.unwrap().is_none());
+    assert_eq!(semaphore.lease_nodes().await.unwrap().len(), 2);
+
+    // 只能获得一个租约时超时，已经获得的租约也被归还
+    drop(leases.remove(0));
+    assert!(other.try_acquire_many(2, SHORT).await.unwrap().is_none());
+    assert_eq!(semaphore.lease_nodes().await.unwrap().len(), 1);
+    let lease = other.try_acquire(SHORT).await.unwrap().unwrap();
+    assert_eq!(semaphore.lease_nodes().await.unwrap().len(), 2);
+    drop(lease);
+    tokio::time::sleep(SHORT).await;
+    assert_eq!(semaphore.lease_nodes().await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn shared_count_limits_leases() {
+    let server = StandInServer::start_with_tree().await;
+    let zk = server.connect().await;
+    let semaphore =
+        InterProcessSemaphore::with_shared_count(&zk, "/semaphores/db", "/limits/db").unwrap();
+    let e = semaphore.try_acquire(SHORT).await.unwrap_err();
+    assert_eq!(e.code(), Some(ServerErrorCode::NoNode));
+
+    zk.create_all(
+        "/limits/db",
+        Some(b"many"),
+        ACL::world_acl(),
+        CreateMode::Persistent,
+    )
+    .await
+    .unwrap();
+    let e = semaphore.try_acquire(SHORT).await.unwrap_err();
+    assert!(matches!(e, ZKError::InvalidCount { .. }));
+
+    zk.set("/limits/db", b"1").await.unwrap();
+    let _lease = semaphore.acquire().await.unwrap();
+    let other = server.connect().await;
+    let waiting = tokio::spawn(async move {
+        let semaphore =
+            InterProcessSemaphore::with_shared_count(&other, "/semaphores/db", "/limits/db")
+                .unwrap();
+        semaphore
+            .try_acquire(Duration::from_secs(3))
+            .await
+            .unwrap()
+            .is_some()
+    });
+    tokio::time::sleep(SHORT).await;
+    assert!(!waiting.is_finished());
+    // 调大计数之后等待者被唤醒
+    zk.set("/limits/db", b"2").await.unwrap();
+    assert!(waiting.await.unwrap());
+}
+
+#[tokio::test]
+async fn acquire_many_validates_count_and_does_not_deadlock() {
+    let server = StandInServer::start_with_tree().await;
+    let zk = server.connect().await;
+    let semaphore = Arc::new(InterProcessSemaphore::new(&zk, "/semaphores/db", 2).unwrap());
+    assert!(matches!(
+        semaphore.acquire_many(0).await,
+        Err(ZKError::InvalidLeaseCount { count: 0, .. })
+    ));
+    assert!(matches!(
+        semaphore.try_acquire_many(3, SHORT).await,
+        Err(ZKError::InvalidLeaseCount { count: 3, .. })
+    ));
+
+    // 多个参与者同时获取全部租约，不会各自拿到一个之后互相等待
+    let mut tasks = Vec::new();
+    for i in 0..4 {
+        let semaphore = if i % 2 == 0 {
+            semaphore.clone()
+        } else {
+            let zk = server.connect().await;
+            Arc::new(InterProcessSemaphore::new(&zk, "/semaphores/db", 2).unwrap())
+        };
+        tasks.push(tokio::spawn(async move {
+            let leases = semaphore.acquire_many(2).await.unwrap();
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            for lease in leases {
+                lease.release().await.unwrap();
+            }
+        }));
+    }
+    for task in tasks {
+        tokio::time::timeout(Duration::from_secs(5), task)
+            .await
+            .unwrap()
+            .unwrap();
+    }
+    assert!(semaphore.lease_nodes().await.unwrap().is_empty());
+}
@@ -0,0 +1,67 @@
+mod common;
+
+use std::time::Duration;
+
+use repeekooz::{CreateMode, ZooKeeper, ACL};
+
+use common::StandInServer;
+
+#[tokio::test]
+async fn sequential_children_sorted_by_sequence() {
+    let server = StandInServer::start_with_tree().await;
+    let zk = ZooKeeper::new(server.addr.as_str(), Duration::from_secs(3))
+        .await
+        .unwrap();
+    zk.create("/locks", None, ACL::world_acl(), CreateMode::Persistent)
+        .await
+        .unwrap();
+    zk.create(
+        "/locks/b-",
+        None,
+        ACL::world_acl(),
+        CreateMode::EphemeralSequential,
+    )
+    .await
+    .unwrap();
+    let protected = zk
+        .create_with_retry(
+            "/locks/a-",
+            None,
+            ACL::world_acl(),
+            CreateMode::EphemeralSequential,
+        )
+        .await
+        .unwrap();
+    zk.create(
+        "/locks/b-",
+        None,
+        ACL::world_acl(),
+        CreateMode::EphemeralSequential,
+    )
+    .await
+    .unwrap();
+    zk.create(
+        "/locks/config",
+        None,
+        ACL::world_acl(),
+        CreateMode::Persistent,
+    )
+    .await
+    .unwrap();
+
+    let all = zk.sequential_children("/locks", "").await.unwrap();
+    let sequences: Vec<(&str, i32)> = all.iter().map(|n| (n.prefix(), n.sequence())).collect();
+    assert_eq!(sequences, [("b-", 0), ("a-", 1), ("b-", 2)]);
+    assert_eq!(all[0].protected_id(), None);
+    assert!(protected.ends_with(all[1].name()));
+    assert_eq!(all[1].protected_id().map(str::len), Some(32));
+
+    let b: Vec<String> = zk
+        .sequential_children("/locks", "b-")
+        .await
+        .unwrap()
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+    assert_eq!(b, ["b-0000000000", "b-0000000002"]);
+}
@@ -0,0 +1,208 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use repeekooz::{
+    ConnectionState, CreateMode, EventType, KeeperState, ServerErrorCode, States, WatchedEvent,
+    Watcher, ZooKeeper, ACL,
+};
+
+use common::StandInServer;
+
+#[tokio::test]
+async fn connection_state_transitions() {
+    let server = StandInServer::start().await;
+    let zk = ZooKeeper::new(server.addr.as_str(), Duration::from_secs(3))
+        .await
+        .unwrap();
+    assert_eq!(zk.state().unwrap(), States::Connected);
+    let session_id = zk.session_id().unwrap();
+    let mut listener = zk.subscribe_state();
+
+    // 断线后自动重连，会话不变
+    server.drop_connections();
+    let event = listener.recv().await.unwrap();
+    assert_eq!(event.state, ConnectionState::Suspended);
+    assert_eq!(event.keeper_state, KeeperState::Disconnected);
+    let event = listener.recv().await.unwrap();
+    assert_eq!(event.state, ConnectionState::Reconnected);
+    zk.wait_until_connected(Duration::from_secs(1))
+        .await
+        .unwrap();
+    assert_eq!(zk.session_id().unwrap(), session_id);
+    zk.delete("/repeekooz").await.unwrap();
+
+    // 重连时服务端告知会话过期
+    server.expire_sessions();
+    server.drop_connections();
+    let event = listener.recv().await.unwrap();
+    assert_eq!(event.state, ConnectionState::Suspended);
+    let event = listener.recv().await.unwrap();
+    assert_eq!(event.state, ConnectionState::Lost);
+    assert_eq!(event.keeper_state, KeeperState::Expired);
+    assert_eq!(zk.state().unwrap(), States::Closed);
+    assert!(zk.delete("/repeekooz").await.is_err());
+    assert!(zk
+        .wait_until_connected(Duration::from_secs(1))
+        .await
+        .is_err());
+}
+
+#[derive(Debug, Clone, Default)]
+struct StateRecorder {
+    states: Arc<Mutex<Vec<KeeperState>>>,
+}
+
+impl Watcher for StateRecorder {
+    fn process(&self, event: &WatchedEvent) {
+        if let EventType::None = event.event_type {
+            self.states.lock().unwrap().push(event.keep_state);
+        }
+    }
+}
+
+#[tokio::test]
+async fn builder_resets_watches_and_closes() {
+    let server = StandInServer::start().await;
+    let recorder = StateRecorder::default();
+    let zk = ZooKeeper::builder(server.addr.as_str())
+        .session_timeout(Duration::from_secs(3))
+        .request_timeout(Duration::from_secs(1))
+        .default_watcher(recorder.clone())
+        .build()
+        .await
+        .unwrap();
+    let mut listener = zk.subscribe_state();
+
+    zk.existsw("/repeekooz", Some(recorder.clone()))
+        .await
+        .unwrap();
+    server.drop_connections();
+    assert_eq!(
+        listener.recv().await.unwrap().state,
+        ConnectionState::Suspended
+    );
+    assert_eq!(
+        listener.recv().await.unwrap().state,
+        ConnectionState::Reconnected
+    );
+    zk.delete("/repeekooz").await.unwrap();
+    // 重连后重新注册回调
+    assert!(server.received_ops().contains(&101));
+
+    zk.close().await.unwrap();
+    assert_eq!(zk.state().unwrap(), States::Closed);
+    assert_eq!(
+        listener.recv().await.unwrap().state,
+        ConnectionState::Closed
+    );
+    assert!(zk.delete("/repeekooz").await.is_err());
+    // 回调在 EventTask 中异步执行
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(
+        recorder.states.lock().unwrap().first(),
+        Some(&KeeperState::SyncConnected)
+    );
+    assert_eq!(
+        recorder.states.lock().unwrap().last(),
+        Some(&KeeperState::Closed)
+    );
+}
+
+#[tokio::test]
+async fn request_timeout_fails_unanswered_request() {
+    let server = StandInServer::start_with_tree().await;
+    let zk = ZooKeeper::builder(server.addr.as_str())
+        .session_timeout(Duration::from_secs(3))
+        .request_timeout(Duration::from_millis(200))
+        .build()
+        .await
+        .unwrap();
+    zk.create(
+        "/config",
+        Some(b"v1"),
+        ACL::world_acl(),
+        CreateMode::Persistent,
+    )
+    .await
+    .unwrap();
+
+    // GetData 的响应一直没有到达
+    server.skip_next_reply(4);
+    let e = zk.get("/config", None).await.unwrap_err();
+    assert_eq!(e.code(), Some(ServerErrorCode::RequestTimeout));
+    assert!(e.is_retryable());
+
+    // 连接仍然可用，之后的请求不受影响
+    assert_eq!(zk.state().unwrap(), States::Connected);
+    assert_eq!(zk.get("/config", None).await.unwrap(), b"v1");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn queued_requests_are_not_replayed_after_reconnect() {
+    let server = StandInServer::start_with_tree().await;
+    let zk = ZooKeeper::new(server.addr.as_str(), Duration::from_secs(10))
+        .await
+        .unwrap();
+    for path in ["/big", "/small"] {
+        zk.create(path, Some(b"v1"), ACL::world_acl(), CreateMode::Persistent)
+            .await
+            .unwrap();
+    }
+    let mut listener = zk.subscribe_state();
+
+    // 服务端不再读取，大的请求写不完，之后的请求留在发送队列中
+    server.pause_reads();
+    let requests = tokio::spawn({
+        let zk = zk.clone();
+        async move {
+            let data = vec![0; 8 << 20];
+            tokio::join!(zk.set("/big", &data), zk.set("/small", b"v2"))
+        }
+    });
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    server.drop_connections();
+    let (big, small) = requests.await.unwrap();
+    assert!(big.unwrap_err().is_connection_loss());
+    assert!(small.unwrap_err().is_connection_loss());
+    assert_eq!(
+        listener.recv().await.unwrap().state,
+        ConnectionState::Suspended
+    );
+    assert_eq!(
+        listener.recv().await.unwrap().state,
+        ConnectionState::Reconnected
+    );
+    // 排队中的请求已经失败，不会在新的连接上发送
+    assert_eq!(zk.get("/small", None).await.unwrap(), b"v1");
+
+    // 大的数据包完整写入
+    let data = vec![1; 8 << 20];
+    zk.set("/big", &data).await.unwrap();
+    assert_eq!(server.node_data("/big").unwrap(), data);
+}
+
+#[tokio::test]
+async fn session_times_out_locally_while_disconnected() {
+    let server = StandInServer::start().await;
+    let zk = ZooKeeper::new(server.addr.as_str(), Duration::from_secs(1))
+        .await
+        .unwrap();
+    let mut listener = zk.subscribe_state();
+
+    server.refuse_connections();
+    server.drop_connections();
+    let start = tokio::time::Instant::now();
+    assert_eq!(
+        listener.recv().await.unwrap().state,
+        ConnectionState::Suspended
+    );
+    // 连不上服务端，会话超时之后在本地判定会话过期
+    let event = listener.recv().await.unwrap();
+    assert_eq!(event.state, ConnectionState::Lost);
+    assert_eq!(event.keeper_state, KeeperState::Expired);
+    assert!(start.elapsed() < Duration::from_secs(3));
+    assert_eq!(zk.state().unwrap(), States::Closed);
+}
@@ -6,7 +6,7 @@ use futures_timer::Delay;
 use tokio::time::Duration;
 
 use repeekooz::{AddWatchMode, ACL};
-use repeekooz::{CreateMode, Scheme};
+use repeekooz::{CreateMode, Perms, Scheme};
 use repeekooz::{Stat, WatchedEvent, Watcher, ZKResult, ZooKeeper};
 
 const DEFAULT_ZK_SERVER: &str = "127.0.0.1:2181";
@@ -81,13 +81,13 @@ async fn full_test() {
 
     // set_acl
     let acl_list = vec![ACL {
-        perms: 15,
+        perms: Perms::from(15),
         scheme: Scheme::World,
     }];
     zk.set_acl(basic_path, acl_list, -1).await.unwrap();
     let vec = zk.get_acl(basic_path, None).await.unwrap();
     let acl_list = vec![ACL {
-        perms: 15,
+        perms: Perms::from(15),
         scheme: Scheme::World,
     }];
     assert_eq!(vec, acl_list);
@@ -0,0 +1,97 @@
+#![cfg(feature = "tls")]
+mod common;
+
+use std::time::Duration;
+
+use repeekooz::{ConnectionState, States, TlsConfig, ZKError, ZooKeeper};
+
+use common::StandInServer;
+
+const TLS_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/tls");
+
+fn tls_config() -> TlsConfig {
+    TlsConfig::new().ca_file(format!("{}/ca.pem", TLS_DIR))
+}
+
+async fn connect(server: &StandInServer, tls: TlsConfig) -> Result<ZooKeeper, ZKError> {
+    ZooKeeper::builder(server.addr.as_str())
+        .session_timeout(Duration::from_secs(3))
+        .tls(tls)
+        .build()
+        .await
+}
+
+#[tokio::test]
+async fn tls_connect_and_reconnect() {
+    let server = StandInServer::start_tls(false).await;
+    let zk = connect(&server, tls_config()).await.unwrap();
+    zk.delete("/repeekooz").await.unwrap();
+    let mut listener = zk.subscribe_state();
+
+    server.drop_connections();
+    assert_eq!(
+        listener.recv().await.unwrap().state,
+        ConnectionState::Suspended
+    );
+    assert_eq!(
+        listener.recv().await.unwrap().state,
+        ConnectionState::Reconnected
+    );
+    zk.delete("/repeekooz").await.unwrap();
+    assert_eq!(server.received_ops(), vec![2, 2]);
+    zk.close().await.unwrap();
+    assert_eq!(zk.state().unwrap(), States::Closed);
+}
+
+#[tokio::test]
+async fn tls_mutual_auth() {
+    let server = StandInServer::start_tls(true).await;
+    // 服务端要求客户端证书
+    assert!(connect(&server, tls_config()).await.is_err());
+
+    let tls = tls_config().client_auth_file(
+        format!("{}/client.pem", TLS_DIR),
+        format!("{}/client.key", TLS_DIR),
+    );
+    let zk = connect(&server, tls).await.unwrap();
+    zk.delete("/repeekooz").await.unwrap();
+}
+
+#[tokio::test]
+async fn tls_verify_hostname() {
+    let server = StandInServer::start_tls(false).await;
+    // 证书中没有这个名称
+    let result = connect(&server, tls_config().server_name("zk.example.com")).await;
+    assert!(matches!(result, Err(ZKError::TlsError(_))));
+
+    let tls = tls_config()
+        .server_name("zk.example.com")
+        .verify_hostname(false);
+    let zk = connect(&server, tls).await.unwrap();
+    zk.delete("/repeekooz").await.unwrap();
+
+    // 关闭主机名校验后仍然校验证书链
+    let result = connect(&server, TlsConfig::new().verify_hostname(false)).await;
+    assert!(matches!(result, Err(ZKError::TlsError(_))));
+}
+
+#[tokio::test]
+async fn tls_connect_by_hostname() {
+    let server = StandInServer::start_tls(false).await;
+    // 主机名在连接时解析，并且作为校验证书时使用的服务端名称
+    let port = server.addr.rsplit_once(':').unwrap().1;
+    let zk = ZooKeeper::builder(format!("localhost:{}", port))
+        .session_timeout(Duration::from_secs(3))
+        .tls(tls_config())
+        .build()
+        .await
+        .unwrap();
+    zk.delete("/repeekooz").await.unwrap();
+}
+
+#[tokio::test]
+async fn tls_invalid_config() {
+    let server = StandInServer::start_tls(false).await;
+    let result = connect(&server, TlsConfig::new().ca_pem("not a pem")).await;
+    assert!(matches!(result, Err(ZKError::TlsError(_))));
+}
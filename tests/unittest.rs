@@ -6,7 +6,7 @@ use futures_timer::Delay;
 use tokio::time::Duration;
 
 use repeekooz::{AddWatchMode, ACL};
-use repeekooz::{CreateMode, Scheme};
+use repeekooz::{CreateMode, Perms, Scheme};
 use repeekooz::{Stat, WatchedEvent, Watcher, ZKResult, ZooKeeper};
 
 const DEFAULT_ZK_SERVER: &str = "127.0.0.1:2181";
@@ -140,7 +140,7 @@ async fn set_acl() {
         .unwrap();
 
     let acl_list = vec![ACL {
-        perms: 21,
+        perms: Perms::from(21),
         scheme: Scheme::World,
     }];
     let x = zk.set_acl("/xjj", acl_list, -1).await;
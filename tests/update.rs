@@ -0,0 +1,127 @@
+mod common;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use repeekooz::{CreateMode, ServerErrorCode, UpdateOptions, CREATOR_ALL_ACL, READ_ACL_UNSAFE};
+use tokio::runtime::Handle;
+
+use common::StandInServer;
+
+fn count(data: Option<&[u8]>) -> u64 {
+    data.map(|data| String::from_utf8_lossy(data).parse().unwrap())
+        .unwrap_or(0)
+}
+
+#[tokio::test]
+async fn create_or_set_and_create_if_absent() {
+    let server = StandInServer::start_with_tree().await;
+    let zk = server.connect().await;
+    let stat = zk.create_or_set("/config", b"v1").await.unwrap();
+    assert_eq!((stat.version, stat.data_length), (0, 2));
+    let stat = zk.create_or_set("/config", b"v22").await.unwrap();
+    assert_eq!((stat.version, stat.data_length), (1, 3));
+    assert_eq!(server.node_data("/config").unwrap(), b"v22");
+
+    assert!(!zk.create_if_absent("/config", b"defaults").await.unwrap());
+    assert_eq!(server.node_data("/config").unwrap(), b"v22");
+    let options = UpdateOptions::default().acl(READ_ACL_UNSAFE.clone());
+    assert!(zk
+        .create_if_absent_with("/limits", b"defaults", &options)
+        .await
+        .unwrap());
+    assert_eq!(zk.get_acl("/limits", None).await.unwrap(), *READ_ACL_UNSAFE);
+
+    let e = zk
+        .create_or_set("/missing/config", b"v1")
+        .await
+        .unwrap_err();
+    assert_eq!(e.code(), Some(ServerErrorCode::NoNode));
+}
+
+#[tokio::test]
+async fn update_concurrent_counter() {
+    let server = StandInServer::start_with_tree().await;
+    let zk = server.connect().await;
+    let options = UpdateOptions::default().max_attempts(100);
+    let tasks: Vec<_> = (0..8)
+        .map(|_| {
+            let zk = zk.clone();
+            let options = options.clone();
+            tokio::spawn(async move {
+                for _ in 0..5 {
+                    zk.update_with("/counter", &options, |old| {
+                        Some((count(old) + 1).to_string().into_bytes())
+                    })
+                    .await
+                    .unwrap()
+                    .unwrap();
+                }
+            })
+        })
+        .collect();
+    for task in tasks {
+        task.await.unwrap();
+    }
+    assert_eq!(server.node_data("/counter").unwrap(), b"40");
+
+    // 返回 None 时不写入
+    let stat = zk.update("/counter", |_| None).await.unwrap();
+    assert_eq!(stat, None);
+    assert_eq!(zk.update("/absent", |_| None).await.unwrap(), None);
+    assert_eq!(zk.exists("/absent").await.unwrap(), None);
+
+    let options = UpdateOptions::default()
+        .acl(CREATOR_ALL_ACL.clone())
+        .create_mode(CreateMode::Ephemeral);
+    let stat = zk
+        .update_with("/owner", &options, |old| {
+            assert_eq!(old, None);
+            Some(b"me".to_vec())
+        })
+        .await
+        .unwrap()
+        .unwrap();
+    assert_ne!(stat.ephemeral_owner, 0);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn update_retries_on_bad_version() {
+    let server = StandInServer::start_with_tree().await;
+    let zk = server.connect().await;
+    let other = server.connect().await;
+    zk.create_or_set("/config", b"0").await.unwrap();
+
+    // 前两次计算新数据时节点被其他客户端修改
+    let calls = Arc::new(AtomicUsize::new(0));
+    let interfere = |calls: Arc<AtomicUsize>, conflicts: usize| {
+        let other = other.clone();
+        move |old: Option<&[u8]>| {
+            if calls.fetch_add(1, Ordering::SeqCst) < conflicts {
+                tokio::task::block_in_place(|| {
+                    Handle::current().block_on(other.set("/config", b"other"))
+                })
+                .unwrap();
+            }
+            Some([old.unwrap(), b"+"].concat())
+        }
+    };
+    let stat = zk
+        .update("/config", interfere(calls.clone(), 2))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+    assert_eq!(stat.version, 3);
+    assert_eq!(server.node_data("/config").unwrap(), b"other+");
+
+    // 超过尝试次数后返回 BadVersion
+    let calls = Arc::new(AtomicUsize::new(0));
+    let options = UpdateOptions::default().max_attempts(2);
+    let e = zk
+        .update_with("/config", &options, interfere(calls.clone(), usize::MAX))
+        .await
+        .unwrap_err();
+    assert_eq!(e.code(), Some(ServerErrorCode::BadVersion));
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+}
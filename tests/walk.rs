@@ -0,0 +1,114 @@
+mod common;
+
+use futures_util::StreamExt;
+
+use repeekooz::{CreateMode, WalkOptions, WalkOrder, ZKError, ZooKeeper, ACL};
+
+use common::StandInServer;
+
+async fn create_tree(zk: &ZooKeeper) {
+    for path in ["/app/a/1", "/app/a/2", "/app/b", "/app/c/x/y"] {
+        zk.create_all(
+            path,
+            Some(path.as_bytes()),
+            ACL::world_acl(),
+            CreateMode::Persistent,
+        )
+        .await
+        .unwrap();
+    }
+}
+
+async fn walk(zk: &ZooKeeper, options: WalkOptions) -> Vec<String> {
+    zk.walk_with("/app", options)
+        .map(|entry| entry.unwrap().path)
+        .collect()
+        .await
+}
+
+#[tokio::test]
+async fn walk_order() {
+    let server = StandInServer::start_with_tree().await;
+    let zk = server.connect().await;
+    create_tree(&zk).await;
+
+    let bfs = [
+        "/app",
+        "/app/a",
+        "/app/b",
+        "/app/c",
+        "/app/a/1",
+        "/app/a/2",
+        "/app/c/x",
+        "/app/c/x/y",
+    ];
+    let dfs = [
+        "/app",
+        "/app/a",
+        "/app/a/1",
+        "/app/a/2",
+        "/app/b",
+        "/app/c",
+        "/app/c/x",
+        "/app/c/x/y",
+    ];
+    // 并发请求不影响输出顺序
+    for concurrency in [1, 3, 16] {
+        let options = WalkOptions::default().concurrency(concurrency);
+        assert_eq!(walk(&zk, options.clone()).await, bfs);
+        let options = options.order(WalkOrder::DepthFirst);
+        assert_eq!(walk(&zk, options).await, dfs);
+    }
+
+    let entries: Vec<_> = zk.walk("/app").collect().await;
+    let c = entries[3].as_ref().unwrap();
+    assert_eq!(c.depth, 1);
+    assert_eq!(c.stat.num_children, 1);
+    assert!(c.data.is_none());
+}
+
+#[tokio::test]
+async fn walk_depth_filter_and_data() {
+    let server = StandInServer::start_with_tree().await;
+    let zk = server.connect().await;
+    create_tree(&zk).await;
+
+    let options = WalkOptions::default().max_depth(1);
+    assert_eq!(
+        walk(&zk, options).await,
+        ["/app", "/app/a", "/app/b", "/app/c"]
+    );
+
+    // 被过滤的节点的子树不会被访问
+    let options = WalkOptions::default()
+        .order(WalkOrder::DepthFirst)
+        .filter(|path| path != "/app/a");
+    assert_eq!(
+        walk(&zk, options).await,
+        ["/app", "/app/b", "/app/c", "/app/c/x", "/app/c/x/y"]
+    );
+
+    let options = WalkOptions::default().with_data(true).max_depth(2);
+    let entries: Vec<_> = zk.walk_with("/app", options).collect().await;
+    assert_eq!(entries.len(), 7);
+    for entry in entries {
+        let entry = entry.unwrap();
+        let expected: &[u8] = match entry.path.as_str() {
+            "/app/a/1" | "/app/a/2" | "/app/b" => entry.path.as_bytes(),
+            _ => b"",
+        };
+        assert_eq!(entry.data.unwrap(), expected);
+    }
+}
+
+#[tokio::test]
+async fn walk_missing_and_invalid_path() {
+    let server = StandInServer::start_with_tree().await;
+    let zk = server.connect().await;
+    create_tree(&zk).await;
+    assert_eq!(zk.walk("/missing").count().await, 0);
+
+    let entries: Vec<_> = zk.walk("app").collect().await;
+    assert_eq!(entries.len(), 1);
+    assert!(matches!(entries[0], Err(ZKError::PathError(..))));
+}
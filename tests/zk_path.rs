@@ -0,0 +1,39 @@
+mod common;
+
+use std::time::Duration;
+
+use repeekooz::{CreateMode, ZKError, ZkPath, ZooKeeper, ACL};
+
+use common::StandInServer;
+
+#[tokio::test]
+async fn api_accepts_zk_path_and_string() {
+    let server = StandInServer::start_with_tree().await;
+    let zk = ZooKeeper::new(server.addr.as_str(), Duration::from_secs(3))
+        .await
+        .unwrap();
+    let app = ZkPath::new("/app").unwrap();
+    zk.create(&app, Some(b"1"), ACL::world_acl(), CreateMode::Persistent)
+        .await
+        .unwrap();
+    let config = app.join("config").unwrap();
+    zk.create(
+        config.clone(),
+        None,
+        ACL::world_acl(),
+        CreateMode::Persistent,
+    )
+    .await
+    .unwrap();
+
+    let owned = String::from("/app");
+    assert_eq!(zk.get(&owned, None).await.unwrap(), b"1");
+    assert_eq!(zk.children(owned).await.unwrap(), ["config"]);
+    assert!(zk.exists(config.as_str()).await.unwrap().is_some());
+
+    // 非法路径在发送请求之前被拒绝
+    for path in ["/app/./config", "/app//config", "/app/\u{1}"] {
+        let e = zk.get(path, None).await.unwrap_err();
+        assert!(matches!(e, ZKError::PathError(..)));
+    }
+}